@@ -0,0 +1,20 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS bags (
+    id          INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    created_by  INTEGER NOT NULL,
+    name        TEXT NOT NULL,
+    description TEXT NOT NULL,
+    created_at  TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Items and draws that were created before bags existed end up in a default bag
+INSERT INTO bags (id, created_by, name, description)
+    SELECT 1, MIN(added_by), 'Default bag', 'Items added before bags existed'
+    FROM bagitems
+    HAVING COUNT(*) > 0;
+
+ALTER TABLE bagitems ADD COLUMN bag_id INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE taken_items ADD COLUMN bag_id INTEGER NOT NULL DEFAULT 1;
+
+CREATE INDEX IF NOT EXISTS bagitems_bag_id ON bagitems (bag_id);
+CREATE INDEX IF NOT EXISTS taken_items_bag_id ON taken_items (bag_id);
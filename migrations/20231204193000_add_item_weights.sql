@@ -0,0 +1,6 @@
+-- Add migration script here
+ALTER TABLE bagitems ADD COLUMN weight INTEGER;
+
+ALTER TABLE bags ADD COLUMN small_weight INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE bags ADD COLUMN medium_weight INTEGER NOT NULL DEFAULT 2;
+ALTER TABLE bags ADD COLUMN large_weight INTEGER NOT NULL DEFAULT 3;
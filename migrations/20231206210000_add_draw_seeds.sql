@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE taken_items ADD COLUMN seed INTEGER;
+ALTER TABLE taken_items ADD COLUMN draw_pool TEXT;
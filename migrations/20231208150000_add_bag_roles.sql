@@ -0,0 +1,19 @@
+-- Add migration script here
+-- Roles are stored in user_permissions as 'bag:<bag id>:<role>' tokens, plus 'admin' for site admins
+
+-- Bag creators own their bags
+INSERT OR IGNORE INTO user_permissions (user_id, token)
+    SELECT created_by, 'bag:' || id || ':owner'
+    FROM bags;
+
+-- Everyone could edit every bag before roles existed, so keep it that way for existing bags
+INSERT OR IGNORE INTO user_permissions (user_id, token)
+    SELECT users.id, 'bag:' || bags.id || ':editor'
+    FROM users, bags
+    WHERE users.id != bags.created_by;
+
+-- The first user becomes the site admin
+INSERT OR IGNORE INTO user_permissions (user_id, token)
+    SELECT MIN(id), 'admin'
+    FROM users
+    HAVING COUNT(*) > 0;
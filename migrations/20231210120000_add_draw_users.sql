@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE taken_items ADD COLUMN drawn_by INTEGER;
+ALTER TABLE taken_items ADD COLUMN completed_by INTEGER;
+ALTER TABLE taken_items ADD COLUMN completed_at TIMESTAMP;
@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE taken_items ADD COLUMN rounds_remaining INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE taken_items ADD COLUMN abandoned BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE taken_items SET rounds_remaining = CASE WHEN done THEN 0 ELSE num_rounds END;
@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE bags ADD COLUMN default_rounds TEXT NOT NULL DEFAULT '1d6';
+ALTER TABLE bagitems ADD COLUMN rounds TEXT;
@@ -0,0 +1,19 @@
+-- Add migration script here
+ALTER TABLE bagitems ADD COLUMN par_level INTEGER;
+
+-- Finite items start out with a par level of whatever they held before any draws
+UPDATE bagitems SET par_level = quantity + (
+    SELECT COUNT(*) FROM taken_items WHERE taken_items.item_id = bagitems.id
+) WHERE infinite = FALSE;
+
+CREATE TABLE IF NOT EXISTS restocks (
+    id              INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    bag_id          INTEGER NOT NULL,
+    item_id         INTEGER NOT NULL,
+    restocked_by    INTEGER,
+    restocked_at    TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    quantity_before INTEGER NOT NULL,
+    quantity_after  INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS restocks_bag_id ON restocks (bag_id);
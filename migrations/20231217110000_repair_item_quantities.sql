@@ -0,0 +1,7 @@
+-- Add migration script here
+-- Draws have always decremented bagitems.quantity, so it's kept as the record of what's left
+-- and draws are no longer subtracted from it a second time. Clean up the rows that the old
+-- schema allowed to hold no quantity or flag at all, along with anything that went negative.
+UPDATE bagitems SET infinite = FALSE WHERE infinite IS NULL;
+UPDATE bagitems SET quantity = 0 WHERE quantity IS NULL OR quantity < 0;
+UPDATE bagitems SET par_level = NULL WHERE par_level <= 0;
@@ -0,0 +1,2 @@
+-- Add migration script here
+ALTER TABLE bags ADD COLUMN undo_window_secs INTEGER NOT NULL DEFAULT 60;
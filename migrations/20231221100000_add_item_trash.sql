@@ -0,0 +1,2 @@
+-- Add migration script here
+ALTER TABLE bagitems ADD COLUMN deleted_at TIMESTAMP;
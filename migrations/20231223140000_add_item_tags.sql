@@ -0,0 +1,15 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS tags (
+    id      INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    bag_id  INTEGER NOT NULL,
+    name    TEXT NOT NULL,
+    UNIQUE (bag_id, name)
+);
+
+CREATE TABLE IF NOT EXISTS item_tags (
+    item_id INTEGER NOT NULL,
+    tag_id  INTEGER NOT NULL,
+    PRIMARY KEY (item_id, tag_id)
+);
+
+CREATE INDEX IF NOT EXISTS item_tags_tag_id ON item_tags (tag_id);
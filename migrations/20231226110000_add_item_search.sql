@@ -0,0 +1,22 @@
+-- Add migration script here
+CREATE VIRTUAL TABLE IF NOT EXISTS bagitems_fts USING fts5(
+    name,
+    description,
+    content = 'bagitems',
+    content_rowid = 'id'
+);
+
+INSERT INTO bagitems_fts (bagitems_fts) VALUES ('rebuild');
+
+CREATE TRIGGER IF NOT EXISTS bagitems_fts_insert AFTER INSERT ON bagitems BEGIN
+    INSERT INTO bagitems_fts (rowid, name, description) VALUES (new.id, new.name, new.description);
+END;
+
+CREATE TRIGGER IF NOT EXISTS bagitems_fts_delete AFTER DELETE ON bagitems BEGIN
+    INSERT INTO bagitems_fts (bagitems_fts, rowid, name, description) VALUES ('delete', old.id, old.name, old.description);
+END;
+
+CREATE TRIGGER IF NOT EXISTS bagitems_fts_update AFTER UPDATE OF name, description ON bagitems BEGIN
+    INSERT INTO bagitems_fts (bagitems_fts, rowid, name, description) VALUES ('delete', old.id, old.name, old.description);
+    INSERT INTO bagitems_fts (rowid, name, description) VALUES (new.id, new.name, new.description);
+END;
@@ -0,0 +1,7 @@
+-- Add migration script here
+-- Whether the draw took a copy out of bagitems.quantity, so undoing it only puts back what it took.
+-- Earlier draws of finite items always did.
+ALTER TABLE taken_items ADD COLUMN decremented BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE taken_items SET decremented = TRUE WHERE item_id IN (
+    SELECT id FROM bagitems WHERE infinite = FALSE
+);
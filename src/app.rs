@@ -1,6 +1,6 @@
 use crate::auth::frontend::Auth;
 use crate::auth::provide_auth;
-use crate::bag::frontend::BagRoutes;
+use crate::bag::frontend::{provide_bag_context, BagRoutes};
 use crate::common::components::layout::*;
 use crate::error_template::ErrorTemplate;
 use crate::errors::RoadieAppError;
@@ -37,6 +37,7 @@ pub fn App() -> impl IntoView {
 
                 {
                     provide_auth();
+                    provide_bag_context();
                 }
                 <Routes>
                     <Route path="/" view=RoadieBagPage>
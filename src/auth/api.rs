@@ -11,14 +11,43 @@ cfg_if! {
         use leptos_axum::*;
         use sqlx::SqlitePool;
         use crate::db::db_pool;
-        use crate::auth::model::SQLUser;
+        use crate::auth::model::{Role, SQLUser, SQLUserPermission, ADMIN_TOKEN};
+        use axum_session_auth::{Auth, Rights};
         use bcrypt::{verify};
+        use http::Method;
 
         pub type AuthSession = axum_session_auth::AuthSession<User, i64, SessionSqlitePool, SqlitePool>;
         pub fn auth_session() -> Result<AuthSession, ServerFnError> {
             use_context::<AuthSession>()
                 .ok_or_else(|| ServerFnError::ServerError("Auth session missing".into()))
         }
+
+        async fn has_rights(auth: &AuthSession, rights: Rights, pool: &SqlitePool) -> bool {
+            match &auth.current_user {
+                Some(user) if !user.anonymous => {
+                    Auth::<User, i64, SqlitePool>::build([Method::GET, Method::POST], false)
+                        .requires(rights)
+                        .validate(user, &Method::POST, Some(pool))
+                        .await
+                }
+                _ => false
+            }
+        }
+
+        /// True when the current user is a site admin
+        pub async fn is_admin(auth: &AuthSession, pool: &SqlitePool) -> bool {
+            has_rights(auth, Rights::permission(ADMIN_TOKEN), pool).await
+        }
+
+        /// True when the current user holds `role`, or a role above it, in the bag. Admins pass
+        /// every check.
+        pub async fn has_role(auth: &AuthSession, bag_id: i64, role: Role, pool: &SqlitePool) -> bool {
+            let tokens: Vec<String> = role.at_least()
+                .map(|r| r.token(bag_id))
+                .chain(std::iter::once(ADMIN_TOKEN.to_string()))
+                .collect();
+            has_rights(auth, Rights::any(tokens.iter().map(|t| Rights::permission(t))), pool).await
+        }
     }
 }
 
@@ -75,7 +104,9 @@ pub async fn signup(
         )));
     }
 
-    SQLUser::create(username, password, &pool).await?;
+    let user_id = SQLUser::create(username, password, &pool).await?;
+    // The first person to sign up runs the place
+    SQLUserPermission::grant_to_first_user(user_id, ADMIN_TOKEN.into(), &pool).await?;
     Ok(Ok(()))
 }
 
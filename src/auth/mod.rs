@@ -5,11 +5,12 @@ pub mod frontend;
 pub mod model;
 pub(crate) mod tests;
 pub use frontend::provide_auth;
-pub use model::User;
+pub use model::{Role, User};
 
 cfg_if! {
     if #[cfg(feature="ssr")] {
         pub use api::auth_session;
         pub use api::AuthSession;
+        pub use api::{has_role, is_admin};
     }
 }
@@ -1,15 +1,17 @@
 use cfg_if::cfg_if;
 
 use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter, EnumString, IntoEnumIterator};
 cfg_if! {
     if #[cfg(feature="ssr")] {
         use sea_query_binder::SqlxBinder;
         #[cfg(feature="derive")]
         use sea_query::*;
-        use sea_query::{Query, Expr, IdenStatic, Func, SqliteQueryBuilder, SelectStatement, Asterisk};
+        use sea_query::{Query, Expr, IdenStatic, Func, SqliteQueryBuilder, SelectStatement, Asterisk, OnConflict, Order};
 
         use bcrypt::{hash, DEFAULT_COST};
-        use sqlx::SqlitePool;
+        use sqlx::prelude::*;
+        use sqlx::{Sqlite, SqlitePool};
         use axum_session_auth::{Authentication, HasPermission};
     }
 }
@@ -31,6 +33,61 @@ impl Default for User {
     }
 }
 
+/// Permission token that lets a user do anything in any bag
+pub const ADMIN_TOKEN: &str = "admin";
+
+/// What a user is allowed to do in a bag. Each role includes everything the roles before it
+/// can do, so an editor can also draw and view.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, EnumIter,
+    Display, EnumString,
+)]
+pub enum Role {
+    Viewer,
+    Player,
+    Editor,
+    Owner,
+}
+
+impl Role {
+    fn as_token_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Player => "player",
+            Role::Editor => "editor",
+            Role::Owner => "owner",
+        }
+    }
+
+    /// The permission token stored in `user_permissions` for this role in a bag
+    pub fn token(&self, bag_id: i64) -> String {
+        format!("bag:{}:{}", bag_id, self.as_token_str())
+    }
+
+    /// Prefix shared by every role token in a bag
+    pub fn bag_prefix(bag_id: i64) -> String {
+        format!("bag:{}:", bag_id)
+    }
+
+    /// Splits a token made by [`Role::token`] back into the bag id and role
+    pub fn from_token(token: &str) -> Option<(i64, Role)> {
+        let mut parts = token.split(':');
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some("bag"), Some(bag_id), Some(role), None) => {
+                let role = Role::iter().find(|r| r.as_token_str() == role)?;
+                Some((bag_id.parse().ok()?, role))
+            }
+            _ => None,
+        }
+    }
+
+    /// This role and every role above it
+    pub fn at_least(&self) -> impl Iterator<Item = Role> {
+        let min = *self;
+        Role::iter().filter(move |r| *r >= min)
+    }
+}
+
 cfg_if! {
     if #[cfg(feature = "ssr")] {
         use async_trait::async_trait;
@@ -54,7 +111,9 @@ cfg_if! {
             Table,
             Id,
             Username,
-            Password
+            Password,
+            #[iden="created_at"]
+            CreatedAt
         }
 
         #[derive(sqlx::FromRow, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -92,6 +151,22 @@ cfg_if! {
                 Ok(id)
             }
 
+            #[tracing::instrument(level = "info", skip(password, pool), fields(error), ret, err)]
+            pub async fn set_password(id: i64, password: String, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+                let password_hashed = hash(password, DEFAULT_COST).unwrap();
+
+                let (update_stmt, values) = Query::update()
+                    .table(UserTable::Table)
+                    .value(UserTable::Password, password_hashed)
+                    .and_where(Expr::col(UserTable::Id).eq(id))
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                sqlx::query_with(&update_stmt, values)
+                    .execute(pool)
+                    .await?;
+                Ok(())
+            }
+
             async fn get_one(mut query: SelectStatement, pool: &SqlitePool) -> Result<Option<Self>, sqlx::Error> {
                 let mut user_vec = Self::get_many(query.limit(1).take(), pool).await?;
                 if user_vec.len() >= 1 {
@@ -151,15 +226,143 @@ cfg_if! {
             Token
         }
 
-        /*#[derive(sqlx::FromRow, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+        #[derive(sqlx::FromRow, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
         pub struct SQLUserPermission {
             pub user_id: i64,
             pub token: String,
         }
 
         impl SQLUserPermission {
+            #[tracing::instrument(level = "info", skip(executor), fields(error), ret, err)]
+            pub async fn grant<'e, E: Executor<'e, Database = Sqlite>>(user_id: i64, token: String, executor: E) -> Result<(), sqlx::Error> {
+                let (q, values) = Query::insert()
+                    .into_table(UserPermissionsTable::Table)
+                    .columns([UserPermissionsTable::UserId, UserPermissionsTable::Token])
+                    .values_panic([user_id.into(), token.into()])
+                    .on_conflict(
+                        OnConflict::columns([UserPermissionsTable::UserId, UserPermissionsTable::Token])
+                            .do_nothing()
+                            .to_owned()
+                    )
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                sqlx::query_with(&q, values)
+                    .execute(executor)
+                    .await?;
+                Ok(())
+            }
 
-        }*/
+            /// Grants `token` only when `user_id` is the only user there is, checked in the same
+            /// statement so two people signing up at once can't both get it. True if it was granted.
+            #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
+            pub async fn grant_to_first_user(user_id: i64, token: String, pool: &SqlitePool) -> Result<bool, sqlx::Error> {
+                let (q, values) = Query::insert()
+                    .into_table(UserPermissionsTable::Table)
+                    .columns([UserPermissionsTable::UserId, UserPermissionsTable::Token])
+                    .select_from(
+                        Query::select()
+                            .expr(Expr::val(user_id))
+                            .expr(Expr::val(token))
+                            .and_where(
+                                Expr::exists(
+                                    Query::select()
+                                        .expr(Expr::val(1))
+                                        .from(UserTable::Table)
+                                        .and_where(Expr::col(UserTable::Id).ne(user_id))
+                                        .to_owned()
+                                )
+                                .not()
+                            )
+                            .to_owned()
+                    )
+                    .map_err(|e| sqlx::Error::Protocol(e.to_string()))?
+                    .on_conflict(
+                        OnConflict::columns([UserPermissionsTable::UserId, UserPermissionsTable::Token])
+                            .do_nothing()
+                            .to_owned()
+                    )
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                Ok(sqlx::query_with(&q, values)
+                    .execute(pool)
+                    .await?
+                    .rows_affected() > 0)
+            }
+
+            #[tracing::instrument(level = "info", skip(executor), fields(error), ret, err)]
+            pub async fn revoke<'e, E: Executor<'e, Database = Sqlite>>(user_id: i64, token: String, executor: E) -> Result<(), sqlx::Error> {
+                let (q, values) = Query::delete()
+                    .from_table(UserPermissionsTable::Table)
+                    .and_where(Expr::col(UserPermissionsTable::UserId).eq(user_id))
+                    .and_where(Expr::col(UserPermissionsTable::Token).eq(token))
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                sqlx::query_with(&q, values)
+                    .execute(executor)
+                    .await?;
+                Ok(())
+            }
+
+            /// Removes every token starting with `prefix`, optionally only for one user
+            #[tracing::instrument(level = "info", skip(executor), fields(error), ret, err)]
+            pub async fn revoke_prefix<'e, E: Executor<'e, Database = Sqlite>>(user_id: Option<i64>, prefix: String, executor: E) -> Result<(), sqlx::Error> {
+                let mut query = Query::delete();
+                query
+                    .from_table(UserPermissionsTable::Table)
+                    .and_where(Expr::col(UserPermissionsTable::Token).like(format!("{}%", prefix)));
+                if let Some(user_id) = user_id {
+                    query.and_where(Expr::col(UserPermissionsTable::UserId).eq(user_id));
+                }
+                let (q, values) = query.build_sqlx(SqliteQueryBuilder);
+                sqlx::query_with(&q, values)
+                    .execute(executor)
+                    .await?;
+                Ok(())
+            }
+
+            #[tracing::instrument(level = "debug", skip(pool), fields(error), ret, err)]
+            pub async fn exists(user_id: i64, token: &str, pool: &SqlitePool) -> Result<bool, sqlx::Error> {
+                let (q, values) = Query::select()
+                    .expr(Expr::val(1))
+                    .from(UserPermissionsTable::Table)
+                    .and_where(Expr::col(UserPermissionsTable::UserId).eq(user_id))
+                    .and_where(Expr::col(UserPermissionsTable::Token).eq(token))
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                Ok(sqlx::query_with(&q, values)
+                    .fetch_optional(pool)
+                    .await?
+                    .is_some())
+            }
+
+            #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
+            pub async fn for_user(user_id: i64, pool: &SqlitePool) -> Result<Vec<SQLUserPermission>, sqlx::Error> {
+                let (q, values) = Query::select()
+                    .columns([UserPermissionsTable::UserId, UserPermissionsTable::Token])
+                    .from(UserPermissionsTable::Table)
+                    .and_where(Expr::col(UserPermissionsTable::UserId).eq(user_id))
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                sqlx::query_as_with::<_, SQLUserPermission, _>(&q, values)
+                    .fetch_all(pool)
+                    .await
+            }
+
+            /// Every grant of tokens starting with `prefix`
+            #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
+            pub async fn with_prefix(prefix: String, pool: &SqlitePool) -> Result<Vec<SQLUserPermission>, sqlx::Error> {
+                let (q, values) = Query::select()
+                    .columns([UserPermissionsTable::UserId, UserPermissionsTable::Token])
+                    .from(UserPermissionsTable::Table)
+                    .and_where(Expr::col(UserPermissionsTable::Token).like(format!("{}%", prefix)))
+                    .order_by(UserPermissionsTable::UserId, Order::Asc)
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                sqlx::query_as_with::<_, SQLUserPermission, _>(&q, values)
+                    .fetch_all(pool)
+                    .await
+            }
+        }
 
         #[async_trait]
         impl Authentication<User, i64, SqlitePool> for User {
@@ -194,8 +397,16 @@ cfg_if! {
 
         #[async_trait]
         impl HasPermission<SqlitePool> for User {
-            async fn has(&self, _perm: &str, _pool: &Option<&SqlitePool>) -> bool {
-                true
+            async fn has(&self, perm: &str, pool: &Option<&SqlitePool>) -> bool {
+                match pool {
+                    Some(pool) if !self.anonymous => SQLUserPermission::exists(self.id, perm, pool)
+                        .await
+                        .unwrap_or_else(|e| {
+                            tracing::error!("Unable to check permission {} for user {}: {}", perm, self.id, e);
+                            false
+                        }),
+                    _ => false
+                }
             }
         }
     }
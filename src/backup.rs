@@ -0,0 +1,681 @@
+use cfg_if::cfg_if;
+
+cfg_if! {
+    if #[cfg(feature = "ssr")] {
+        use chrono::{DateTime, Utc};
+        use rand::distributions::{Alphanumeric, DistString};
+        use sea_query::{Asterisk, Expr, Func, Iden, IdenStatic, Order, Query, SelectStatement, SimpleExpr, SqliteQueryBuilder};
+        use sea_query_binder::SqlxBinder;
+        use serde::{Deserialize, Serialize};
+        use sqlx::sqlite::SqliteRow;
+        use sqlx::{FromRow, Row, SqliteConnection, SqlitePool};
+        use std::collections::{HashMap, HashSet};
+        use std::io::{Read, Write};
+        use strum::{Display, EnumString};
+        use thiserror::Error;
+
+        use crate::auth::model::{SQLUserPermission, UserPermissionsTable, UserTable};
+        use crate::auth::Role;
+        use crate::bag::draw::DrawCandidate;
+        use crate::bag::model::*;
+
+        /// Written at the top of every backup so other JSON files are turned away
+        pub const BACKUP_FORMAT: &str = "roadiebag-backup";
+        /// Bumped whenever the layout of [`Backup`] changes in a way older builds can't read
+        pub const BACKUP_FORMAT_VERSION: u32 = 1;
+
+        /// The newest migration this build knows about. Backups carry it so they're never
+        /// restored into a database that's missing columns they have data for.
+        pub fn schema_version() -> i64 {
+            sqlx::migrate!()
+                .migrations
+                .iter()
+                .map(|m| m.version)
+                .max()
+                .unwrap_or(0)
+        }
+
+        #[derive(Debug, Error)]
+        pub enum BackupError {
+            #[error("This isn't a roadiebag backup")]
+            NotABackup,
+            #[error("Backup format version {0} is newer than this build can read ({BACKUP_FORMAT_VERSION})")]
+            UnsupportedFormat(u32),
+            #[error("The backup is from schema version {backup}, newer than this build's {current}. Upgrade roadiebag before restoring it")]
+            NewerSchema { backup: i64, current: i64 },
+            #[error("Bag {0} doesn't exist")]
+            BagNotFound(i64),
+            #[error("{0} already exists")]
+            Conflict(String),
+            #[error(transparent)]
+            Database(#[from] sqlx::Error),
+            #[error(transparent)]
+            Json(#[from] serde_json::Error),
+        }
+
+        /// How much of the instance a backup holds
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+        #[serde(rename_all = "snake_case")]
+        pub enum BackupScope {
+            Instance,
+            /// One bag, along with everyone who has a role in it or shows up in its history
+            Bag(i64),
+        }
+
+        /// What a restore does with users and bags the database already has. Users are the
+        /// same when their names match, bags when both their id and name do.
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Display, EnumString)]
+        #[strum(serialize_all = "lowercase")]
+        pub enum ConflictPolicy {
+            /// Stop without changing anything
+            #[default]
+            Fail,
+            /// Keep what's there. Users in the backup are matched up with the existing ones and
+            /// existing bags are left out of the restore.
+            Skip,
+            /// Swap existing bags for the backup's copy and, when the backup has them, existing
+            /// users' passwords for the backed up ones
+            Replace,
+        }
+
+        /// Rows are kept as they are in the database, timestamps included, so a restore puts
+        /// back exactly what was there
+        #[derive(FromRow, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+        pub struct BackupUser {
+            pub id: i64,
+            pub username: String,
+            /// The bcrypt hash, only there when the backup was asked to include it
+            pub password: Option<String>,
+            pub created_at: Option<String>,
+        }
+
+        #[derive(FromRow, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+        pub struct BackupPermission {
+            pub user_id: i64,
+            pub token: String,
+        }
+
+        #[derive(FromRow, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+        pub struct BackupBag {
+            pub id: i64,
+            pub created_by: i64,
+            pub name: String,
+            pub description: String,
+            pub small_weight: i64,
+            pub medium_weight: i64,
+            pub large_weight: i64,
+            pub default_rounds: String,
+            pub undo_window_secs: i64,
+            pub created_at: Option<String>,
+        }
+
+        #[derive(FromRow, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+        pub struct BackupItem {
+            pub id: i64,
+            pub bag_id: i64,
+            pub added_by: i64,
+            pub name: String,
+            pub description: String,
+            pub quantity: Option<i64>,
+            pub size: Option<i64>,
+            pub infinite: Option<bool>,
+            pub weight: Option<i64>,
+            pub rounds: Option<String>,
+            pub par_level: Option<i64>,
+            pub created_at: Option<String>,
+            pub deleted_at: Option<String>,
+            #[sqlx(skip)]
+            #[serde(default)]
+            pub tags: Vec<String>,
+        }
+
+        #[derive(FromRow, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+        pub struct BackupDraw {
+            pub id: i64,
+            pub bag_id: i64,
+            pub item_id: i64,
+            pub extraction_time: Option<String>,
+            pub num_rounds: i64,
+            pub done: bool,
+            pub seed: Option<i64>,
+            /// The candidates the draw was made from, as JSON
+            pub draw_pool: Option<String>,
+            pub drawn_by: Option<i64>,
+            pub completed_by: Option<i64>,
+            pub completed_at: Option<String>,
+            pub rounds_remaining: i64,
+            pub abandoned: bool,
+            /// Whether the draw took a copy of the item, missing from older backups
+            #[serde(default)]
+            pub decremented: bool,
+        }
+
+        #[derive(FromRow, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+        pub struct BackupRestock {
+            pub id: i64,
+            pub bag_id: i64,
+            pub item_id: i64,
+            pub restocked_by: Option<i64>,
+            pub restocked_at: Option<String>,
+            pub quantity_before: i64,
+            pub quantity_after: i64,
+        }
+
+        /// A self describing copy of an instance or a single bag
+        #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+        pub struct Backup {
+            pub format: String,
+            pub format_version: u32,
+            pub schema_version: i64,
+            pub created_at: DateTime<Utc>,
+            pub scope: BackupScope,
+            pub includes_passwords: bool,
+            pub users: Vec<BackupUser>,
+            pub permissions: Vec<BackupPermission>,
+            pub bags: Vec<BackupBag>,
+            pub items: Vec<BackupItem>,
+            pub draws: Vec<BackupDraw>,
+            pub restocks: Vec<BackupRestock>,
+        }
+
+        /// What a restore did
+        #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+        pub struct RestoreReport {
+            pub users_created: usize,
+            /// Users that were already there and were used in place of the backed up ones
+            pub users_matched: usize,
+            pub bags_restored: usize,
+            pub bags_replaced: usize,
+            pub bags_skipped: usize,
+            pub items: usize,
+            pub draws: usize,
+            pub restocks: usize,
+            pub permissions: usize,
+        }
+
+        async fn fetch<T>(query: SelectStatement, pool: &SqlitePool) -> Result<Vec<T>, sqlx::Error>
+        where
+            T: for<'r> FromRow<'r, SqliteRow> + Send + Unpin,
+        {
+            let (q, v) = query.build_sqlx(SqliteQueryBuilder);
+            sqlx::query_as_with::<_, T, _>(&q, v).fetch_all(pool).await
+        }
+
+        /// Every id already used in the table
+        async fn ids<T: Iden + Copy + 'static>(table: T, id: T, conn: &mut SqliteConnection) -> Result<HashSet<i64>, sqlx::Error> {
+            let (q, v) = Query::select()
+                .column(id)
+                .from(table)
+                .to_owned()
+                .build_sqlx(SqliteQueryBuilder);
+            Ok(sqlx::query_with(&q, v)
+                .fetch_all(&mut *conn)
+                .await?
+                .iter()
+                .map(|row| row.get(0))
+                .collect())
+        }
+
+        /// Inserts a row under its old id when that's free, or a new one when it isn't.
+        /// Returns the id it ended up with.
+        async fn insert_keeping_id<T: Iden + Copy + 'static>(
+            table: T,
+            id_column: T,
+            id: i64,
+            taken: &mut HashSet<i64>,
+            mut columns: Vec<T>,
+            mut values: Vec<SimpleExpr>,
+            conn: &mut SqliteConnection,
+        ) -> Result<i64, sqlx::Error> {
+            if !taken.contains(&id) {
+                columns.insert(0, id_column);
+                values.insert(0, id.into());
+            }
+            let (q, v) = Query::insert()
+                .into_table(table)
+                .columns(columns)
+                .values_panic(values)
+                .to_owned()
+                .build_sqlx(SqliteQueryBuilder);
+            let new_id = sqlx::query_with(&q, v)
+                .execute(&mut *conn)
+                .await?
+                .last_insert_rowid();
+            taken.insert(new_id);
+            Ok(new_id)
+        }
+
+        /// Users restored without a password hash get a random password nobody knows, so they
+        /// can't log in until it's reset
+        fn locked_password() -> String {
+            let password = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+            // The password is never typed in, so the cost only needs to keep restores quick
+            bcrypt::hash(password, 4).unwrap()
+        }
+
+        /// Points the stored candidates at the items' new ids. The JSON is left as it was when
+        /// none of them moved.
+        fn remap_draw_pool(draw_pool: &Option<String>, item_ids: &HashMap<i64, i64>) -> Option<String> {
+            let draw_pool = draw_pool.as_ref()?;
+            match serde_json::from_str::<Vec<DrawCandidate>>(draw_pool) {
+                Ok(mut candidates) if candidates.iter().any(|c| item_ids.get(&c.item_id).is_some_and(|id| *id != c.item_id)) => {
+                    for candidate in candidates.iter_mut() {
+                        if let Some(id) = item_ids.get(&candidate.item_id) {
+                            candidate.item_id = *id;
+                        }
+                    }
+                    Some(serde_json::to_string(&candidates).unwrap_or_else(|_| draw_pool.clone()))
+                }
+                _ => Some(draw_pool.clone()),
+            }
+        }
+
+        impl Backup {
+            #[tracing::instrument(level = "info", skip(pool), err)]
+            pub async fn create(scope: BackupScope, include_passwords: bool, pool: &SqlitePool) -> Result<Backup, BackupError> {
+                let bag_id = match scope {
+                    BackupScope::Instance => None,
+                    BackupScope::Bag(bag_id) => Some(bag_id),
+                };
+
+                let bags: Vec<BackupBag> = fetch(
+                    Query::select()
+                        .column(Asterisk)
+                        .from(BagsTable::Table)
+                        .and_where_option(bag_id.map(|id| Expr::col(BagsTable::Id).eq(id)))
+                        .order_by(BagsTable::Id, Order::Asc)
+                        .to_owned(),
+                    pool
+                ).await?;
+                if let (Some(bag_id), true) = (bag_id, bags.is_empty()) {
+                    return Err(BackupError::BagNotFound(bag_id));
+                }
+
+                let mut items: Vec<BackupItem> = fetch(
+                    Query::select()
+                        .column(Asterisk)
+                        .from(BagItemsTable::Table)
+                        .and_where_option(bag_id.map(|id| Expr::col(BagItemsTable::BagId).eq(id)))
+                        .order_by(BagItemsTable::Id, Order::Asc)
+                        .to_owned(),
+                    pool
+                ).await?;
+                let mut tags = Tag::for_items(items.iter().map(|i| i.id).collect(), pool).await?;
+                for item in items.iter_mut() {
+                    item.tags = tags.remove(&item.id).unwrap_or_default();
+                    item.tags.sort();
+                }
+
+                let draws: Vec<BackupDraw> = fetch(
+                    Query::select()
+                        .column(Asterisk)
+                        .from(TakenItemsTable::Table)
+                        .and_where_option(bag_id.map(|id| Expr::col(TakenItemsTable::BagId).eq(id)))
+                        .order_by(TakenItemsTable::Id, Order::Asc)
+                        .to_owned(),
+                    pool
+                ).await?;
+
+                let restocks: Vec<BackupRestock> = fetch(
+                    Query::select()
+                        .column(Asterisk)
+                        .from(RestocksTable::Table)
+                        .and_where_option(bag_id.map(|id| Expr::col(RestocksTable::BagId).eq(id)))
+                        .order_by(RestocksTable::Id, Order::Asc)
+                        .to_owned(),
+                    pool
+                ).await?;
+
+                let permissions: Vec<BackupPermission> = fetch(
+                    Query::select()
+                        .columns([UserPermissionsTable::UserId, UserPermissionsTable::Token])
+                        .from(UserPermissionsTable::Table)
+                        .and_where_option(bag_id.map(|id| Expr::col(UserPermissionsTable::Token).like(format!("{}%", Role::bag_prefix(id)))))
+                        .order_by(UserPermissionsTable::UserId, Order::Asc)
+                        .order_by(UserPermissionsTable::Token, Order::Asc)
+                        .to_owned(),
+                    pool
+                ).await?;
+
+                // A single bag only needs the users it mentions
+                let user_ids = bag_id.map(|_| {
+                    bags.iter().map(|b| b.created_by)
+                        .chain(items.iter().map(|i| i.added_by))
+                        .chain(draws.iter().flat_map(|d| [d.drawn_by, d.completed_by]).flatten())
+                        .chain(restocks.iter().filter_map(|r| r.restocked_by))
+                        .chain(permissions.iter().map(|p| p.user_id))
+                        .collect::<HashSet<i64>>()
+                });
+                let mut users: Vec<BackupUser> = fetch(
+                    Query::select()
+                        .columns([UserTable::Id, UserTable::Username, UserTable::Password, UserTable::CreatedAt])
+                        .from(UserTable::Table)
+                        .and_where_option(user_ids.map(|ids| Expr::col(UserTable::Id).is_in(ids)))
+                        .order_by(UserTable::Id, Order::Asc)
+                        .to_owned(),
+                    pool
+                ).await?;
+                if !include_passwords {
+                    for user in users.iter_mut() {
+                        user.password = None;
+                    }
+                }
+
+                Ok(Backup {
+                    format: BACKUP_FORMAT.to_string(),
+                    format_version: BACKUP_FORMAT_VERSION,
+                    schema_version: schema_version(),
+                    created_at: Utc::now(),
+                    scope,
+                    includes_passwords: include_passwords,
+                    users,
+                    permissions,
+                    bags,
+                    items,
+                    draws,
+                    restocks,
+                })
+            }
+
+            pub fn write<W: Write>(&self, writer: W) -> Result<(), BackupError> {
+                Ok(serde_json::to_writer_pretty(writer, self)?)
+            }
+
+            /// Reads a backup, turning away anything this build can't restore
+            pub fn read<R: Read>(reader: R) -> Result<Backup, BackupError> {
+                let value: serde_json::Value = serde_json::from_reader(reader)?;
+                if value.get("format").and_then(|f| f.as_str()) != Some(BACKUP_FORMAT) {
+                    return Err(BackupError::NotABackup);
+                }
+                let backup: Backup = serde_json::from_value(value)?;
+                backup.check()?;
+                Ok(backup)
+            }
+
+            fn check(&self) -> Result<(), BackupError> {
+                if self.format != BACKUP_FORMAT {
+                    return Err(BackupError::NotABackup);
+                }
+                if self.format_version > BACKUP_FORMAT_VERSION {
+                    return Err(BackupError::UnsupportedFormat(self.format_version));
+                }
+                let current = schema_version();
+                if self.schema_version > current {
+                    return Err(BackupError::NewerSchema { backup: self.schema_version, current });
+                }
+                Ok(())
+            }
+
+            /// Puts everything in the backup into the database in one transaction. Rows keep
+            /// their ids where they're free, so restoring into an empty database gives back the
+            /// same ids, and get new ones with every reference updated where they aren't.
+            #[tracing::instrument(level = "info", skip(self, pool), err)]
+            pub async fn restore(&self, policy: ConflictPolicy, pool: &SqlitePool) -> Result<RestoreReport, BackupError> {
+                self.check()?;
+                let mut tx = pool.begin().await?;
+                let report = self.restore_with(policy, &mut tx).await?;
+                tx.commit().await?;
+                tracing::info!("Restored backup: {:?}", report);
+                Ok(report)
+            }
+
+            async fn restore_with(&self, policy: ConflictPolicy, conn: &mut SqliteConnection) -> Result<RestoreReport, BackupError> {
+                let mut report = RestoreReport::default();
+
+                let mut user_ids = HashMap::new();
+                let mut taken = ids(UserTable::Table, UserTable::Id, conn).await?;
+                for user in &self.users {
+                    let (q, v) = Query::select()
+                        .column(UserTable::Id)
+                        .from(UserTable::Table)
+                        .and_where(Expr::expr(Func::lower(Expr::col(UserTable::Username))).eq(user.username.to_lowercase()))
+                        .to_owned()
+                        .build_sqlx(SqliteQueryBuilder);
+                    let existing: Option<i64> = sqlx::query_with(&q, v)
+                        .fetch_optional(&mut *conn)
+                        .await?
+                        .map(|row| row.get(UserTable::Id.as_str()));
+                    let id = match (existing, policy) {
+                        (Some(_), ConflictPolicy::Fail) => {
+                            return Err(BackupError::Conflict(format!("User {}", user.username)));
+                        }
+                        (Some(id), _) => {
+                            if let (ConflictPolicy::Replace, Some(password)) = (policy, &user.password) {
+                                let (q, v) = Query::update()
+                                    .table(UserTable::Table)
+                                    .value(UserTable::Password, password)
+                                    .and_where(Expr::col(UserTable::Id).eq(id))
+                                    .to_owned()
+                                    .build_sqlx(SqliteQueryBuilder);
+                                sqlx::query_with(&q, v).execute(&mut *conn).await?;
+                            }
+                            report.users_matched += 1;
+                            id
+                        }
+                        (None, _) => {
+                            report.users_created += 1;
+                            insert_keeping_id(
+                                UserTable::Table,
+                                UserTable::Id,
+                                user.id,
+                                &mut taken,
+                                vec![UserTable::Username, UserTable::Password, UserTable::CreatedAt],
+                                vec![
+                                    user.username.to_lowercase().into(),
+                                    user.password.clone().unwrap_or_else(locked_password).into(),
+                                    user.created_at.clone().into(),
+                                ],
+                                conn
+                            ).await?
+                        }
+                    };
+                    user_ids.insert(user.id, id);
+                }
+                let user_id = |id: i64| user_ids.get(&id).copied().unwrap_or(id);
+
+                let mut bag_ids = HashMap::new();
+                let mut taken = ids(BagsTable::Table, BagsTable::Id, conn).await?;
+                for bag in &self.bags {
+                    let (q, v) = Query::select()
+                        .column(BagsTable::Name)
+                        .from(BagsTable::Table)
+                        .and_where(Expr::col(BagsTable::Id).eq(bag.id))
+                        .to_owned()
+                        .build_sqlx(SqliteQueryBuilder);
+                    let existing: Option<String> = sqlx::query_with(&q, v)
+                        .fetch_optional(&mut *conn)
+                        .await?
+                        .map(|row| row.get(BagsTable::Name.as_str()));
+                    if existing.as_ref() == Some(&bag.name) {
+                        match policy {
+                            ConflictPolicy::Fail => {
+                                return Err(BackupError::Conflict(format!("Bag {} ({})", bag.name, bag.id)));
+                            }
+                            ConflictPolicy::Skip => {
+                                report.bags_skipped += 1;
+                                continue;
+                            }
+                            ConflictPolicy::Replace => {
+                                Bag::delete_with(bag.id, conn).await?;
+                                taken.remove(&bag.id);
+                                report.bags_replaced += 1;
+                            }
+                        }
+                    } else {
+                        report.bags_restored += 1;
+                    }
+                    let id = insert_keeping_id(
+                        BagsTable::Table,
+                        BagsTable::Id,
+                        bag.id,
+                        &mut taken,
+                        vec![
+                            BagsTable::CreatedBy,
+                            BagsTable::Name,
+                            BagsTable::Description,
+                            BagsTable::SmallWeight,
+                            BagsTable::MediumWeight,
+                            BagsTable::LargeWeight,
+                            BagsTable::DefaultRounds,
+                            BagsTable::UndoWindowSecs,
+                            BagsTable::CreatedAt,
+                        ],
+                        vec![
+                            user_id(bag.created_by).into(),
+                            bag.name.clone().into(),
+                            bag.description.clone().into(),
+                            bag.small_weight.into(),
+                            bag.medium_weight.into(),
+                            bag.large_weight.into(),
+                            bag.default_rounds.clone().into(),
+                            bag.undo_window_secs.into(),
+                            bag.created_at.clone().into(),
+                        ],
+                        conn
+                    ).await?;
+                    bag_ids.insert(bag.id, id);
+                }
+
+                // Replaced bags took their items with them, so these are only read now
+                let mut item_ids = HashMap::new();
+                let mut taken = ids(BagItemsTable::Table, BagItemsTable::Id, conn).await?;
+                for item in &self.items {
+                    let bag_id = match bag_ids.get(&item.bag_id) {
+                        Some(bag_id) => *bag_id,
+                        None => continue,
+                    };
+                    let id = insert_keeping_id(
+                        BagItemsTable::Table,
+                        BagItemsTable::Id,
+                        item.id,
+                        &mut taken,
+                        vec![
+                            BagItemsTable::BagId,
+                            BagItemsTable::AddedBy,
+                            BagItemsTable::Name,
+                            BagItemsTable::Description,
+                            BagItemsTable::Quantity,
+                            BagItemsTable::Size,
+                            BagItemsTable::Infinite,
+                            BagItemsTable::Weight,
+                            BagItemsTable::Rounds,
+                            BagItemsTable::ParLevel,
+                            BagItemsTable::CreatedAt,
+                            BagItemsTable::DeletedAt,
+                        ],
+                        vec![
+                            bag_id.into(),
+                            user_id(item.added_by).into(),
+                            item.name.clone().into(),
+                            item.description.clone().into(),
+                            item.quantity.into(),
+                            item.size.into(),
+                            item.infinite.into(),
+                            item.weight.into(),
+                            item.rounds.clone().into(),
+                            item.par_level.into(),
+                            item.created_at.clone().into(),
+                            item.deleted_at.clone().into(),
+                        ],
+                        conn
+                    ).await?;
+                    Tag::set_for_item(id, bag_id, &item.tags, conn).await?;
+                    item_ids.insert(item.id, id);
+                    report.items += 1;
+                }
+
+                let mut taken = ids(TakenItemsTable::Table, TakenItemsTable::Id, conn).await?;
+                for draw in &self.draws {
+                    let (bag_id, item_id) = match (bag_ids.get(&draw.bag_id), item_ids.get(&draw.item_id)) {
+                        (Some(bag_id), Some(item_id)) => (*bag_id, *item_id),
+                        _ => continue,
+                    };
+                    insert_keeping_id(
+                        TakenItemsTable::Table,
+                        TakenItemsTable::Id,
+                        draw.id,
+                        &mut taken,
+                        vec![
+                            TakenItemsTable::BagId,
+                            TakenItemsTable::ItemId,
+                            TakenItemsTable::ExtractionTime,
+                            TakenItemsTable::NumRounds,
+                            TakenItemsTable::Done,
+                            TakenItemsTable::Seed,
+                            TakenItemsTable::DrawPool,
+                            TakenItemsTable::DrawnBy,
+                            TakenItemsTable::CompletedBy,
+                            TakenItemsTable::CompletedAt,
+                            TakenItemsTable::RoundsRemaining,
+                            TakenItemsTable::Abandoned,
+                            TakenItemsTable::Decremented,
+                        ],
+                        vec![
+                            bag_id.into(),
+                            item_id.into(),
+                            draw.extraction_time.clone().into(),
+                            draw.num_rounds.into(),
+                            draw.done.into(),
+                            draw.seed.into(),
+                            remap_draw_pool(&draw.draw_pool, &item_ids).into(),
+                            draw.drawn_by.map(user_id).into(),
+                            draw.completed_by.map(user_id).into(),
+                            draw.completed_at.clone().into(),
+                            draw.rounds_remaining.into(),
+                            draw.abandoned.into(),
+                            draw.decremented.into(),
+                        ],
+                        conn
+                    ).await?;
+                    report.draws += 1;
+                }
+
+                let mut taken = ids(RestocksTable::Table, RestocksTable::Id, conn).await?;
+                for restock in &self.restocks {
+                    let (bag_id, item_id) = match (bag_ids.get(&restock.bag_id), item_ids.get(&restock.item_id)) {
+                        (Some(bag_id), Some(item_id)) => (*bag_id, *item_id),
+                        _ => continue,
+                    };
+                    insert_keeping_id(
+                        RestocksTable::Table,
+                        RestocksTable::Id,
+                        restock.id,
+                        &mut taken,
+                        vec![
+                            RestocksTable::BagId,
+                            RestocksTable::ItemId,
+                            RestocksTable::RestockedBy,
+                            RestocksTable::RestockedAt,
+                            RestocksTable::QuantityBefore,
+                            RestocksTable::QuantityAfter,
+                        ],
+                        vec![
+                            bag_id.into(),
+                            item_id.into(),
+                            restock.restocked_by.map(user_id).into(),
+                            restock.restocked_at.clone().into(),
+                            restock.quantity_before.into(),
+                            restock.quantity_after.into(),
+                        ],
+                        conn
+                    ).await?;
+                    report.restocks += 1;
+                }
+
+                // Only roles in the restored bags come back. Site wide tokens like admin would
+                // otherwise be handed to whoever already owns a matching username.
+                for permission in &self.permissions {
+                    let restored = Role::from_token(&permission.token)
+                        .and_then(|(bag_id, role)| bag_ids.get(&bag_id).map(|bag_id| role.token(*bag_id)));
+                    let Some(token) = restored else {
+                        continue;
+                    };
+                    SQLUserPermission::grant(user_id(permission.user_id), token, &mut *conn).await?;
+                    report.permissions += 1;
+                }
+
+                Ok(report)
+            }
+        }
+    }
+}
@@ -1,18 +1,224 @@
 use cfg_if::cfg_if;
 use leptos::*;
 
+use super::dice::DiceExpr;
+use super::draw::DrawOutcome;
+use super::import::*;
 use super::model::*;
+use crate::auth::Role;
 use crate::errors::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 cfg_if! {
     if #[cfg(feature="ssr")] {
-        use crate::auth::auth_session;
+        use crate::auth::{auth_session, has_role, is_admin, AuthSession};
+        use crate::auth::model::SQLUser;
+        use crate::config::Config;
         use crate::db::db_pool;
         use chrono::Utc;
         use http::status::StatusCode;
         use leptos_axum::ResponseOptions;
+        use sqlx::SqlitePool;
+
+        /// Checks that the bag exists and the current user holds at least `role` in it. Sets the
+        /// response status and hands back the error to return when they don't.
+        async fn authorize(
+            auth: &AuthSession,
+            bag_id: i64,
+            role: Role,
+            pool: &SqlitePool,
+            response: &ResponseOptions,
+        ) -> Result<RoadieResult<Bag>, ServerFnError> {
+            match Bag::by_id(bag_id, pool).await? {
+                None => {
+                    response.set_status(StatusCode::NOT_FOUND);
+                    Ok(Err(RoadieAppError::NotFound))
+                }
+                Some(_) if !has_role(auth, bag_id, role, pool).await => {
+                    tracing::warn!("User {:?} needs {} in bag {}", auth.current_user, role, bag_id);
+                    response.set_status(StatusCode::FORBIDDEN);
+                    Ok(Err(RoadieAppError::Unauthorized))
+                }
+                Some(bag) => Ok(Ok(bag)),
+            }
+        }
+
+        /// Items can only be changed by whoever added them, or by someone who owns the bag
+        async fn can_modify(auth: &AuthSession, item: &BagItem, pool: &SqlitePool) -> bool {
+            let is_creator = auth.current_user
+                .as_ref()
+                .map(|u| u.id == item.added_by.id)
+                .unwrap_or(false);
+            is_creator || has_role(auth, item.bag_id, Role::Owner, pool).await
+        }
+
+        /// Loads a draw for someone who wants to change it. Only the player who drew it, or
+        /// someone who owns the bag, gets it back.
+        async fn load_draw(
+            auth: &AuthSession,
+            bag_id: i64,
+            draw_id: i64,
+            pool: &SqlitePool,
+            response: &ResponseOptions,
+        ) -> Result<RoadieResult<TakenBagItem>, ServerFnError> {
+            if let Err(e) = authorize(auth, bag_id, Role::Player, pool, response).await? {
+                return Ok(Err(e));
+            }
+            match TakenBagItem::by_id(draw_id, pool).await? {
+                Some(tbi) if tbi.bag_id == bag_id => {
+                    let is_drawer = match (&tbi.drawn_by, &auth.current_user) {
+                        (Some(drawer), Some(user)) => drawer.id == user.id,
+                        _ => false,
+                    };
+                    if is_drawer || has_role(auth, bag_id, Role::Owner, pool).await {
+                        Ok(Ok(tbi))
+                    } else {
+                        response.set_status(StatusCode::FORBIDDEN);
+                        Ok(Err(RoadieAppError::Unauthorized))
+                    }
+                }
+                _ => {
+                    response.set_status(StatusCode::NOT_FOUND);
+                    Ok(Err(RoadieAppError::NotFound))
+                }
+            }
+        }
+
+        /// Turns the result of a draw transition into a response, where `None` means the draw
+        /// was already over
+        fn finished_or(
+            tbi: Option<TakenBagItem>,
+            response: &ResponseOptions,
+        ) -> Result<RoadieResult<TakenBagItem>, ServerFnError> {
+            match tbi {
+                Some(tbi) => Ok(Ok(tbi)),
+                None => {
+                    response.set_status(StatusCode::CONFLICT);
+                    Ok(Err(RoadieAppError::DrawAlreadyFinished))
+                }
+            }
+        }
+
+        /// Loads an item from the bag's trash for someone who's allowed to change it
+        async fn trashed_item(
+            auth: &AuthSession,
+            bag_id: i64,
+            id: i64,
+            pool: &SqlitePool,
+            response: &ResponseOptions,
+        ) -> Result<RoadieResult<BagItem>, ServerFnError> {
+            if let Err(e) = authorize(auth, bag_id, Role::Editor, pool, response).await? {
+                return Ok(Err(e));
+            }
+            let item = BagItem::by_id(id, pool).await?;
+            match item.filter(|bi| bi.deleted_at.is_some()) {
+                Some(bi) if bi.bag_id == bag_id && !can_modify(auth, &bi, pool).await => {
+                    tracing::warn!("User {:?} can't touch trashed item {}", auth.current_user, id);
+                    response.set_status(StatusCode::FORBIDDEN);
+                    Ok(Err(RoadieAppError::Unauthorized))
+                }
+                Some(bi) if bi.bag_id == bag_id => Ok(Ok(bi)),
+                _ => {
+                    response.set_status(StatusCode::NOT_FOUND);
+                    Ok(Err(RoadieAppError::NotFound))
+                }
+            }
+        }
+
+        /// True when taking the owner role away from the user would leave the bag without one
+        async fn is_last_owner(bag: &Bag, user_id: i64, pool: &SqlitePool) -> Result<bool, sqlx::Error> {
+            let owners: Vec<i64> = bag.members(pool)
+                .await?
+                .into_iter()
+                .filter(|m| m.role == Role::Owner)
+                .map(|m| m.user.id)
+                .collect();
+            Ok(owners == vec![user_id])
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BagForm {
+    pub(crate) id: i64,
+    pub(crate) name: String,
+    pub(crate) description: String,
+    pub(crate) small_weight: u32,
+    pub(crate) medium_weight: u32,
+    pub(crate) large_weight: u32,
+    pub(crate) default_rounds: String,
+    pub(crate) undo_window_secs: u32,
+}
+
+impl BagForm {
+    pub fn validate(&self) -> Option<RoadieAppError> {
+        let mut error_map = HashMap::new();
+        if self.name.trim().is_empty() {
+            error_map.insert(
+                "name".to_string(),
+                RoadieAppError::BagNameNonEmpty.to_string(),
+            );
+        }
+        if self.small_weight == 0 && self.medium_weight == 0 && self.large_weight == 0 {
+            error_map.insert(
+                "weights".to_string(),
+                RoadieAppError::SizeWeightsAllZero.to_string(),
+            );
+        }
+        if let Err(e) = self.default_rounds.parse::<DiceExpr>() {
+            error_map.insert("default_rounds".to_string(), e.to_string());
+        }
+        if !error_map.is_empty() {
+            Some(RoadieAppError::MultipleErrors(error_map))
+        } else {
+            None
+        }
+    }
+}
+
+impl BagForm {
+    pub fn size_weights(&self) -> SizeWeights {
+        SizeWeights {
+            small: self.small_weight,
+            medium: self.medium_weight,
+            large: self.large_weight,
+        }
+    }
+
+    pub fn default_rounds(&self) -> DiceExpr {
+        self.default_rounds.parse().unwrap_or_default()
+    }
+}
+
+impl Default for BagForm {
+    fn default() -> Self {
+        let weights = SizeWeights::default();
+        BagForm {
+            id: -1,
+            name: "".to_string(),
+            description: "".to_string(),
+            small_weight: weights.small,
+            medium_weight: weights.medium,
+            large_weight: weights.large,
+            default_rounds: DiceExpr::default().to_string(),
+            undo_window_secs: 60,
+        }
+    }
+}
+
+impl From<Bag> for BagForm {
+    fn from(value: Bag) -> Self {
+        BagForm {
+            id: value.id,
+            name: value.name,
+            description: value.description,
+            small_weight: value.size_weights.small,
+            medium_weight: value.size_weights.medium,
+            large_weight: value.size_weights.large,
+            default_rounds: value.default_rounds.to_string(),
+            undo_window_secs: value.undo_window_secs,
+        }
     }
 }
 
@@ -24,10 +230,26 @@ pub struct BagItemForm {
     pub(crate) quantity: i32,
     pub(crate) size: Option<ItemSize>,
     pub(crate) infinite: Option<bool>,
+    pub(crate) weight: Option<u32>,
+    /// Dice expression for how many rounds a draw lasts. Blank uses the bag's default.
+    pub(crate) rounds: Option<String>,
+    pub(crate) par_level: Option<i32>,
+    /// Comma separated, see [`parse_tags`]
+    #[serde(default)]
+    pub(crate) tags: String,
 }
 
 impl BagItemForm {
     pub fn validate(&self) -> Option<RoadieAppError> {
+        self.validate_quantity(self.quantity <= 0, RoadieAppError::ItemQntGtZero)
+    }
+
+    /// Like [`Self::validate`], but lets in items that have run out since exports list those too
+    pub fn validate_import(&self) -> Option<RoadieAppError> {
+        self.validate_quantity(self.quantity < 0, RoadieAppError::ItemQntNegative)
+    }
+
+    fn validate_quantity(&self, bad_quantity: bool, quantity_error: RoadieAppError) -> Option<RoadieAppError> {
         let mut error_map = HashMap::new();
         if self.name.trim().is_empty() {
             error_map.insert(
@@ -48,12 +270,21 @@ impl BagItemForm {
             );
         }
 
-        if self.quantity <= 0 {
+        if bad_quantity {
+            error_map.insert("quantity".to_string(), quantity_error.to_string());
+        }
+        if matches!(self.par_level, Some(par_level) if par_level <= 0) {
             error_map.insert(
-                "quantity".to_string(),
-                RoadieAppError::ItemQntGtZero.to_string(),
+                "par_level".to_string(),
+                RoadieAppError::ItemParLevelGtZero.to_string(),
             );
         }
+        if let Err(e) = self.parsed_rounds() {
+            error_map.insert("rounds".to_string(), e.to_string());
+        }
+        if let Some(tag) = self.parsed_tags().into_iter().find(|t| t.chars().count() > 32) {
+            error_map.insert("tags".to_string(), RoadieAppError::TagTooLong(tag).to_string());
+        }
         if !error_map.is_empty() {
             Some(RoadieAppError::MultipleErrors(error_map))
         } else {
@@ -62,6 +293,19 @@ impl BagItemForm {
     }
 }
 
+impl BagItemForm {
+    pub fn parsed_rounds(&self) -> RoadieResult<Option<DiceExpr>> {
+        match self.rounds.as_deref().map(str::trim) {
+            None | Some("") => Ok(None),
+            Some(rounds) => rounds.parse().map(Some),
+        }
+    }
+
+    pub fn parsed_tags(&self) -> Vec<String> {
+        parse_tags(&self.tags)
+    }
+}
+
 impl Default for BagItemForm {
     fn default() -> Self {
         BagItemForm {
@@ -71,6 +315,10 @@ impl Default for BagItemForm {
             description: "".to_string(),
             size: None,
             infinite: None,
+            weight: None,
+            rounds: None,
+            par_level: None,
+            tags: "".to_string(),
         }
     }
 }
@@ -84,6 +332,135 @@ impl From<BagItem> for BagItemForm {
             quantity: value.quantity,
             size: Some(value.size),
             infinite: Some(value.infinite),
+            weight: value.weight,
+            rounds: value.rounds.map(|r| r.to_string()),
+            par_level: value.par_level,
+            tags: value.tags.join(", "),
+        }
+    }
+}
+
+#[tracing::instrument(level = "info", fields(error), ret, err)]
+#[server(ListBags, "/api", "Url", "list_bags")]
+pub async fn list_bags() -> Result<RoadieResult<Vec<Bag>>, ServerFnError> {
+    let pool = db_pool()?;
+    let auth = auth_session()?;
+    let response = expect_context::<ResponseOptions>();
+
+    if auth.is_anonymous() {
+        response.set_status(StatusCode::UNAUTHORIZED);
+        leptos_axum::redirect("/auth");
+        Ok(Err(RoadieAppError::Unauthorized))
+    } else if is_admin(&auth, &pool).await {
+        Ok(Ok(Bag::all(&pool).await?))
+    } else {
+        let user = auth.current_user.unwrap();
+        Ok(Ok(Bag::for_user(user.id, &pool).await?))
+    }
+}
+
+#[tracing::instrument(level = "info", fields(error), ret, err)]
+#[server(GetBag, "/api", "Url", "get_bag")]
+pub async fn get_bag(bag_id: i64) -> Result<RoadieResult<Bag>, ServerFnError> {
+    let pool = db_pool()?;
+    let auth = auth_session()?;
+    let response = expect_context::<ResponseOptions>();
+
+    if auth.is_anonymous() {
+        response.set_status(StatusCode::UNAUTHORIZED);
+        leptos_axum::redirect("/auth");
+        Ok(Err(RoadieAppError::Unauthorized))
+    } else {
+        authorize(&auth, bag_id, Role::Viewer, &pool, &response).await
+    }
+}
+
+/// The form a new bag starts from, filled in with the instance's draw defaults
+#[tracing::instrument(level = "info", fields(error), ret, err)]
+#[server(NewBagForm, "/api", "Url", "new_bag_form")]
+pub async fn new_bag_form() -> Result<RoadieResult<BagForm>, ServerFnError> {
+    let auth = auth_session()?;
+    let response = expect_context::<ResponseOptions>();
+
+    if auth.is_anonymous() {
+        response.set_status(StatusCode::UNAUTHORIZED);
+        leptos_axum::redirect("/auth");
+        Ok(Err(RoadieAppError::Unauthorized))
+    } else {
+        let config = use_context::<Config>().unwrap_or_default();
+        Ok(Ok(config.draw.bag_form()))
+    }
+}
+
+#[tracing::instrument(level = "info", fields(error), ret, err)]
+#[server(CreateUpdateBag, "/api", "Url", "create_update_bag")]
+pub async fn create_update_bag(bag: BagForm) -> Result<RoadieResult<BagForm>, ServerFnError> {
+    let pool = db_pool()?;
+    let auth = auth_session()?;
+    let response = expect_context::<ResponseOptions>();
+    let mut bag = bag;
+
+    if auth.is_anonymous() {
+        response.set_status(StatusCode::UNAUTHORIZED);
+        Ok(Err(RoadieAppError::Unauthorized))
+    } else if let Some(errors) = bag.validate() {
+        response.set_status(StatusCode::BAD_REQUEST);
+        Ok(Err(errors))
+    } else if bag.id == -1 {
+        let new_bag = Bag {
+            id: -1,
+            created_by: auth.current_user.unwrap(),
+            name: bag.name.clone(),
+            description: bag.description.clone(),
+            size_weights: bag.size_weights(),
+            default_rounds: bag.default_rounds(),
+            undo_window_secs: bag.undo_window_secs,
+            created_at: Utc::now(),
+        }
+        .insert(&pool)
+        .await?;
+        bag.id = new_bag.id;
+        tracing::info!("Bag with ID {} added", &bag.id);
+        Ok(Ok(bag))
+    } else {
+        match authorize(&auth, bag.id, Role::Owner, &pool, &response).await? {
+            Ok(mut e) => {
+                tracing::info!("Updating bag ID {}", bag.id);
+                e.name = bag.name.clone();
+                e.description = bag.description.clone();
+                e.size_weights = bag.size_weights();
+                e.default_rounds = bag.default_rounds();
+                e.undo_window_secs = bag.undo_window_secs;
+                e.update(&pool).await?;
+                Ok(Ok(bag))
+            }
+            Err(e) => {
+                tracing::error!("Unable to update bag {}: {}", bag.id, e);
+                Ok(Err(e))
+            }
+        }
+    }
+}
+
+#[tracing::instrument(level = "info", fields(error), ret, err)]
+#[server(DeleteBag, "/api", "Url", "delete_bag")]
+pub async fn delete_bag(bag_id: i64) -> Result<RoadieResult<()>, ServerFnError> {
+    let pool = db_pool()?;
+    let auth = auth_session()?;
+    let response = expect_context::<ResponseOptions>();
+
+    if auth.is_anonymous() {
+        response.set_status(StatusCode::UNAUTHORIZED);
+        leptos_axum::redirect("/auth");
+        Ok(Err(RoadieAppError::Unauthorized))
+    } else {
+        match authorize(&auth, bag_id, Role::Owner, &pool, &response).await? {
+            Ok(bag) => {
+                bag.delete(&pool).await?;
+                response.set_status(StatusCode::OK);
+                Ok(Ok(()))
+            }
+            Err(e) => Ok(Err(e)),
         }
     }
 }
@@ -91,6 +468,7 @@ impl From<BagItem> for BagItemForm {
 #[tracing::instrument(level = "info", fields(error), ret, err)]
 #[server(CreateUpdateBagItem, "/api", "Url", "create_update_bag_item")]
 pub async fn create_update_bag_item(
+    bag_id: i64,
     item: BagItemForm,
 ) -> Result<RoadieResult<BagItemForm>, ServerFnError> {
     let pool = db_pool()?;
@@ -101,6 +479,8 @@ pub async fn create_update_bag_item(
         response.set_status(StatusCode::UNAUTHORIZED);
         //leptos_axum::redirect("/auth");
         Ok(Err(RoadieAppError::Unauthorized))
+    } else if let Err(e) = authorize(&auth, bag_id, Role::Editor, &pool, &response).await? {
+        Ok(Err(e))
     } else {
         let errors = item.validate();
         if errors.is_some() {
@@ -110,32 +490,50 @@ pub async fn create_update_bag_item(
             if item.id == -1 {
                 let bi = BagItem {
                     id: -1,
+                    bag_id,
                     name: item.name.clone(),
                     added_by: auth.current_user.unwrap(),
                     description: item.description.clone(),
                     infinite: item.infinite.unwrap_or_default(),
                     quantity: item.quantity,
                     size: item.size.unwrap(),
+                    weight: item.weight,
+                    rounds: item.parsed_rounds().unwrap_or_default(),
+                    par_level: item.par_level,
+                    tags: item.parsed_tags(),
                     created_at: Utc::now(),
+                    deleted_at: None,
                 };
                 let insert_item = bi.insert(&pool).await?;
                 item.id = insert_item.id;
-                tracing::info!("Item with ID {} added", &item.id);
+                tracing::info!("Item with ID {} added to bag {}", &item.id, bag_id);
                 Ok(Ok(item))
             } else {
-                match BagItem::by_id(item.id, &pool).await? {
-                    Some(mut e) => {
+                let existing = BagItem::by_id(item.id, &pool)
+                    .await?
+                    .filter(|e| e.deleted_at.is_none());
+                match existing {
+                    Some(e) if e.bag_id == bag_id && !can_modify(&auth, &e, &pool).await => {
+                        tracing::warn!("User {:?} can't edit item {}", auth.current_user, item.id);
+                        response.set_status(StatusCode::FORBIDDEN);
+                        Ok(Err(RoadieAppError::Unauthorized))
+                    }
+                    Some(mut e) if e.bag_id == bag_id => {
                         tracing::info!("Updating item ID {}", item.id);
                         e.name = item.name.clone();
                         e.description = item.description.clone();
                         e.infinite = item.infinite.unwrap_or_default();
                         e.quantity = item.quantity;
                         e.size = item.size.unwrap();
+                        e.weight = item.weight;
+                        e.rounds = item.parsed_rounds().unwrap_or_default();
+                        e.par_level = item.par_level;
+                        e.tags = item.parsed_tags();
                         e.update(&pool).await?;
                         Ok(Ok(item))
                     }
-                    None => {
-                        tracing::error!("Unable to find item {}", item.id);
+                    _ => {
+                        tracing::error!("Unable to find item {} in bag {}", item.id, bag_id);
                         response.set_status(StatusCode::NOT_FOUND);
                         Ok(Err(RoadieAppError::NotFound))
                     }
@@ -145,9 +543,44 @@ pub async fn create_update_bag_item(
     }
 }
 
+/// Adds every item in a CSV or JSON file to the bag. Nothing is added unless every row is
+/// valid, and the errors come back keyed by row number, counting from 1.
+#[tracing::instrument(level = "info", skip(contents), fields(error), ret, err)]
+#[server(ImportBagItems, "/api", "Url", "import_bag_items")]
+pub async fn import_bag_items(
+    bag_id: i64,
+    format: ImportFormat,
+    contents: String,
+    dry_run: Option<bool>,
+    skip_duplicates: Option<bool>,
+) -> Result<RoadieResult<ImportReport>, ServerFnError> {
+    let pool = db_pool()?;
+    let auth = auth_session()?;
+    let response = expect_context::<ResponseOptions>();
+
+    if auth.is_anonymous() {
+        response.set_status(StatusCode::UNAUTHORIZED);
+        leptos_axum::redirect("/auth");
+        return Ok(Err(RoadieAppError::Unauthorized));
+    }
+    if let Err(e) = authorize(&auth, bag_id, Role::Editor, &pool, &response).await? {
+        return Ok(Err(e));
+    }
+
+    let added_by = auth.current_user.unwrap();
+    let dry_run = dry_run.unwrap_or(false);
+    match import_items(bag_id, format, &contents, dry_run, skip_duplicates.unwrap_or(false), added_by, &pool).await? {
+        Ok(report) => Ok(Ok(report)),
+        Err(errors) => {
+            response.set_status(StatusCode::EXPECTATION_FAILED);
+            Ok(Err(RoadieAppError::MultipleErrors(errors)))
+        }
+    }
+}
+
 #[tracing::instrument(level = "info", fields(error), ret, err)]
 #[server(GetBagItem, "/api", "Url", "get_bag_item")]
-pub async fn get_bag_item(item_id: i64) -> Result<RoadieResult<BagItem>, ServerFnError> {
+pub async fn get_bag_item(bag_id: i64, item_id: i64) -> Result<RoadieResult<BagItem>, ServerFnError> {
     let pool = db_pool()?;
     let auth = auth_session()?;
     let response = expect_context::<ResponseOptions>();
@@ -156,14 +589,16 @@ pub async fn get_bag_item(item_id: i64) -> Result<RoadieResult<BagItem>, ServerF
         response.set_status(StatusCode::UNAUTHORIZED);
         leptos_axum::redirect("/auth");
         Ok(Err(RoadieAppError::Unauthorized))
+    } else if let Err(e) = authorize(&auth, bag_id, Role::Viewer, &pool, &response).await? {
+        Ok(Err(e))
     } else {
         let item = BagItem::by_id(item_id, &pool).await?;
-        match item {
-            Some(bi) => {
+        match item.filter(|bi| bi.deleted_at.is_none()) {
+            Some(bi) if bi.bag_id == bag_id => {
                 response.set_status(StatusCode::OK);
                 Ok(Ok(bi))
             }
-            None => {
+            _ => {
                 response.set_status(StatusCode::NOT_FOUND);
                 Ok(Err(RoadieAppError::NotFound))
             }
@@ -173,7 +608,7 @@ pub async fn get_bag_item(item_id: i64) -> Result<RoadieResult<BagItem>, ServerF
 
 #[tracing::instrument(level = "info", fields(error), ret, err)]
 #[server(DeleteBagItem, "/api", "Url", "delete_bag_item")]
-pub async fn delete_bag_item(id: i64) -> Result<RoadieResult<()>, ServerFnError> {
+pub async fn delete_bag_item(bag_id: i64, id: i64) -> Result<RoadieResult<()>, ServerFnError> {
     let pool = db_pool()?;
     let auth = auth_session()?;
     let response = expect_context::<ResponseOptions>();
@@ -182,15 +617,22 @@ pub async fn delete_bag_item(id: i64) -> Result<RoadieResult<()>, ServerFnError>
         response.set_status(StatusCode::UNAUTHORIZED);
         leptos_axum::redirect("/auth");
         Ok(Err(RoadieAppError::Unauthorized))
+    } else if let Err(e) = authorize(&auth, bag_id, Role::Editor, &pool, &response).await? {
+        Ok(Err(e))
     } else {
         let item = BagItem::by_id(id, &pool).await?;
-        match item {
-            Some(bi) => {
+        match item.filter(|bi| bi.deleted_at.is_none()) {
+            Some(bi) if bi.bag_id == bag_id && !can_modify(&auth, &bi, &pool).await => {
+                tracing::warn!("User {:?} can't delete item {}", auth.current_user, id);
+                response.set_status(StatusCode::FORBIDDEN);
+                Ok(Err(RoadieAppError::Unauthorized))
+            }
+            Some(bi) if bi.bag_id == bag_id => {
                 bi.delete(&pool).await?;
                 response.set_status(StatusCode::OK);
                 Ok(Ok(()))
             }
-            None => {
+            _ => {
                 response.set_status(StatusCode::NOT_FOUND);
                 Ok(Err(RoadieAppError::NotFound))
             }
@@ -198,9 +640,93 @@ pub async fn delete_bag_item(id: i64) -> Result<RoadieResult<()>, ServerFnError>
     }
 }
 
+/// Every tag used by an item in the bag
+#[tracing::instrument(level = "info", fields(error), err)]
+#[server(ListTags, "/api", "Url", "list_tags")]
+pub async fn list_tags(bag_id: i64) -> Result<RoadieResult<Vec<String>>, ServerFnError> {
+    let pool = db_pool()?;
+    let auth = auth_session()?;
+    let response = expect_context::<ResponseOptions>();
+
+    if auth.is_anonymous() {
+        response.set_status(StatusCode::UNAUTHORIZED);
+        leptos_axum::redirect("/auth");
+        Ok(Err(RoadieAppError::Unauthorized))
+    } else if let Err(e) = authorize(&auth, bag_id, Role::Viewer, &pool, &response).await? {
+        Ok(Err(e))
+    } else {
+        Ok(Ok(Tag::for_bag(bag_id, &pool).await?))
+    }
+}
+
+/// Items in the bag's trash
+#[tracing::instrument(level = "info", fields(error), err)]
+#[server(ListTrash, "/api", "Url", "list_trash")]
+pub async fn list_trash(bag_id: i64) -> Result<RoadieResult<Vec<BagItem>>, ServerFnError> {
+    let pool = db_pool()?;
+    let auth = auth_session()?;
+    let response = expect_context::<ResponseOptions>();
+
+    if auth.is_anonymous() {
+        response.set_status(StatusCode::UNAUTHORIZED);
+        leptos_axum::redirect("/auth");
+        Ok(Err(RoadieAppError::Unauthorized))
+    } else if let Err(e) = authorize(&auth, bag_id, Role::Editor, &pool, &response).await? {
+        Ok(Err(e))
+    } else {
+        Ok(Ok(BagItem::trash(bag_id, &pool).await?))
+    }
+}
+
+#[tracing::instrument(level = "info", fields(error), ret, err)]
+#[server(RestoreBagItem, "/api", "Url", "restore_bag_item")]
+pub async fn restore_bag_item(bag_id: i64, id: i64) -> Result<RoadieResult<()>, ServerFnError> {
+    let pool = db_pool()?;
+    let auth = auth_session()?;
+    let response = expect_context::<ResponseOptions>();
+
+    if auth.is_anonymous() {
+        response.set_status(StatusCode::UNAUTHORIZED);
+        leptos_axum::redirect("/auth");
+        Ok(Err(RoadieAppError::Unauthorized))
+    } else {
+        match trashed_item(&auth, bag_id, id, &pool, &response).await? {
+            Ok(bi) => {
+                bi.restore(&pool).await?;
+                Ok(Ok(()))
+            }
+            Err(e) => Ok(Err(e)),
+        }
+    }
+}
+
+/// Deletes an item in the trash for good, along with its draw history
+#[tracing::instrument(level = "info", fields(error), ret, err)]
+#[server(PurgeBagItem, "/api", "Url", "purge_bag_item")]
+pub async fn purge_bag_item(bag_id: i64, id: i64) -> Result<RoadieResult<()>, ServerFnError> {
+    let pool = db_pool()?;
+    let auth = auth_session()?;
+    let response = expect_context::<ResponseOptions>();
+
+    if auth.is_anonymous() {
+        response.set_status(StatusCode::UNAUTHORIZED);
+        leptos_axum::redirect("/auth");
+        Ok(Err(RoadieAppError::Unauthorized))
+    } else {
+        match trashed_item(&auth, bag_id, id, &pool, &response).await? {
+            Ok(bi) => {
+                bi.purge(&pool).await?;
+                Ok(Ok(()))
+            }
+            Err(e) => Ok(Err(e)),
+        }
+    }
+}
+
 #[tracing::instrument(level = "info", fields(error), err)]
 #[server(ListBagItems, "/api", "Url", "list_bag_items")]
 pub async fn list_bag_items(
+    bag_id: i64,
     filter: Option<BagItemFilter>,
 ) -> Result<RoadieResult<BagItemPage>, ServerFnError> {
     let pool = db_pool()?;
@@ -212,15 +738,208 @@ pub async fn list_bag_items(
         response.set_status(StatusCode::UNAUTHORIZED);
         leptos_axum::redirect("/auth");
         Ok(Err(RoadieAppError::Unauthorized))
+    } else if let Err(e) = authorize(&auth, bag_id, Role::Viewer, &pool, &response).await? {
+        Ok(Err(e))
+    } else {
+        let filter = BagItemFilter {
+            bag_id: Some(bag_id),
+            ..filter.unwrap_or_default()
+        };
+        let page = BagItem::filter(filter, &pool).await?;
+        Ok(Ok(page))
+    }
+}
+
+#[tracing::instrument(level = "info", fields(error), err)]
+#[server(ListTakenItems, "/api", "Url", "list_taken_items")]
+pub async fn list_taken_items(
+    bag_id: i64,
+    filter: Option<TakenItemFilter>,
+) -> Result<RoadieResult<TakenBagItemPage>, ServerFnError> {
+    let pool = db_pool()?;
+    let auth = auth_session()?;
+    let response = expect_context::<ResponseOptions>();
+
+    if auth.is_anonymous() {
+        response.set_status(StatusCode::UNAUTHORIZED);
+        leptos_axum::redirect("/auth");
+        Ok(Err(RoadieAppError::Unauthorized))
+    } else if let Err(e) = authorize(&auth, bag_id, Role::Viewer, &pool, &response).await? {
+        Ok(Err(e))
     } else {
-        let page = BagItem::filter(filter.unwrap_or_default(), &pool).await?;
+        let filter = TakenItemFilter {
+            bag_id: Some(bag_id),
+            ..filter.unwrap_or_default()
+        };
+        let page = TakenBagItem::filter(filter, &pool).await?;
         Ok(Ok(page))
     }
 }
 
+/// Fills finite items back up to their par levels: just `item_id` when it's given, otherwise
+/// everything matching `filter`, or the whole bag without one
+#[tracing::instrument(level = "info", fields(error), ret, err)]
+#[server(RestockItems, "/api", "Url", "restock_items")]
+pub async fn restock_items(
+    bag_id: i64,
+    item_id: Option<i64>,
+    filter: Option<BagItemFilter>,
+) -> Result<RoadieResult<Vec<Restock>>, ServerFnError> {
+    let pool = db_pool()?;
+    let auth = auth_session()?;
+    let response = expect_context::<ResponseOptions>();
+
+    if auth.is_anonymous() {
+        response.set_status(StatusCode::UNAUTHORIZED);
+        leptos_axum::redirect("/auth");
+        Ok(Err(RoadieAppError::Unauthorized))
+    } else if let Err(e) = authorize(&auth, bag_id, Role::Editor, &pool, &response).await? {
+        Ok(Err(e))
+    } else {
+        let filter = BagItemFilter {
+            bag_id: Some(bag_id),
+            ..filter.unwrap_or_default()
+        };
+        let user = auth.current_user.unwrap();
+        let restocks = Restock::refill(&filter, item_id, user.id, &pool).await?;
+        tracing::info!("Restocked {} items in bag {}", restocks.len(), bag_id);
+        Ok(Ok(restocks))
+    }
+}
+
+#[tracing::instrument(level = "info", fields(error), err)]
+#[server(ListRestocks, "/api", "Url", "list_restocks")]
+pub async fn list_restocks(bag_id: i64) -> Result<RoadieResult<Vec<Restock>>, ServerFnError> {
+    let pool = db_pool()?;
+    let auth = auth_session()?;
+    let response = expect_context::<ResponseOptions>();
+
+    if auth.is_anonymous() {
+        response.set_status(StatusCode::UNAUTHORIZED);
+        leptos_axum::redirect("/auth");
+        Ok(Err(RoadieAppError::Unauthorized))
+    } else if let Err(e) = authorize(&auth, bag_id, Role::Viewer, &pool, &response).await? {
+        Ok(Err(e))
+    } else {
+        Ok(Ok(Restock::for_bag(bag_id, 50, &pool).await?))
+    }
+}
+
 #[tracing::instrument(level = "info", fields(error), ret, err)]
 #[server(TakeRandom, "/api", "Url", "take_random")]
-pub async fn take_random() -> Result<RoadieResult<Option<TakenBagItem>>, ServerFnError> {
+pub async fn take_random(
+    bag_id: i64,
+    tags: Option<DrawTags>,
+) -> Result<RoadieResult<Option<TakenBagItem>>, ServerFnError> {
+    let pool = db_pool()?;
+    let auth = auth_session()?;
+    let response = expect_context::<ResponseOptions>();
+
+    if auth.is_anonymous() {
+        response.set_status(StatusCode::UNAUTHORIZED);
+        leptos_axum::redirect("/auth");
+        Ok(Err(RoadieAppError::Unauthorized))
+    } else if let Err(e) = authorize(&auth, bag_id, Role::Player, &pool, &response).await? {
+        Ok(Err(e))
+    } else {
+        let user = auth.current_user.unwrap();
+        if TakenBagItem::active_for(bag_id, user.id, &pool).await?.is_some() {
+            response.set_status(StatusCode::CONFLICT);
+            Ok(Err(RoadieAppError::DrawInProgress))
+        } else {
+            let tags = tags.unwrap_or_default();
+            let drawn = TakenBagItem::get_random(bag_id, user.id, &tags, &pool).await?;
+            if let Err(e) = &drawn {
+                response.set_status(e.status_code());
+            }
+            Ok(drawn)
+        }
+    }
+}
+
+#[tracing::instrument(level = "info", fields(error), ret, err)]
+#[server(CompleteDraw, "/api", "Url", "complete_draw")]
+pub async fn complete_draw(
+    bag_id: i64,
+    draw_id: i64,
+) -> Result<RoadieResult<TakenBagItem>, ServerFnError> {
+    let pool = db_pool()?;
+    let auth = auth_session()?;
+    let response = expect_context::<ResponseOptions>();
+
+    if auth.is_anonymous() {
+        response.set_status(StatusCode::UNAUTHORIZED);
+        leptos_axum::redirect("/auth");
+        Ok(Err(RoadieAppError::Unauthorized))
+    } else {
+        match load_draw(&auth, bag_id, draw_id, &pool, &response).await? {
+            Ok(tbi) => {
+                let user = auth.current_user.unwrap();
+                finished_or(tbi.complete(user.id, &pool).await?, &response)
+            }
+            Err(e) => Ok(Err(e)),
+        }
+    }
+}
+
+#[tracing::instrument(level = "info", fields(error), ret, err)]
+#[server(AdvanceRound, "/api", "Url", "advance_round")]
+pub async fn advance_round(
+    bag_id: i64,
+    draw_id: i64,
+) -> Result<RoadieResult<TakenBagItem>, ServerFnError> {
+    let pool = db_pool()?;
+    let auth = auth_session()?;
+    let response = expect_context::<ResponseOptions>();
+
+    if auth.is_anonymous() {
+        response.set_status(StatusCode::UNAUTHORIZED);
+        leptos_axum::redirect("/auth");
+        Ok(Err(RoadieAppError::Unauthorized))
+    } else {
+        match load_draw(&auth, bag_id, draw_id, &pool, &response).await? {
+            Ok(tbi) if !tbi.done && tbi.rounds_remaining == 0 => {
+                response.set_status(StatusCode::CONFLICT);
+                Ok(Err(RoadieAppError::NoRoundsLeft))
+            }
+            Ok(tbi) => {
+                let user = auth.current_user.unwrap();
+                finished_or(tbi.advance_round(user.id, &pool).await?, &response)
+            }
+            Err(e) => Ok(Err(e)),
+        }
+    }
+}
+
+#[tracing::instrument(level = "info", fields(error), ret, err)]
+#[server(AbandonDraw, "/api", "Url", "abandon_draw")]
+pub async fn abandon_draw(
+    bag_id: i64,
+    draw_id: i64,
+) -> Result<RoadieResult<TakenBagItem>, ServerFnError> {
+    let pool = db_pool()?;
+    let auth = auth_session()?;
+    let response = expect_context::<ResponseOptions>();
+
+    if auth.is_anonymous() {
+        response.set_status(StatusCode::UNAUTHORIZED);
+        leptos_axum::redirect("/auth");
+        Ok(Err(RoadieAppError::Unauthorized))
+    } else {
+        match load_draw(&auth, bag_id, draw_id, &pool, &response).await? {
+            Ok(tbi) => {
+                let user = auth.current_user.unwrap();
+                finished_or(tbi.abandon(user.id, &pool).await?, &response)
+            }
+            Err(e) => Ok(Err(e)),
+        }
+    }
+}
+
+/// Takes back the caller's latest draw, as long as the bag's undo window hasn't passed
+#[tracing::instrument(level = "info", fields(error), ret, err)]
+#[server(UndoDraw, "/api", "Url", "undo_draw")]
+pub async fn undo_draw(bag_id: i64, draw_id: i64) -> Result<RoadieResult<()>, ServerFnError> {
     let pool = db_pool()?;
     let auth = auth_session()?;
     let response = expect_context::<ResponseOptions>();
@@ -230,13 +949,42 @@ pub async fn take_random() -> Result<RoadieResult<Option<TakenBagItem>>, ServerF
         leptos_axum::redirect("/auth");
         Ok(Err(RoadieAppError::Unauthorized))
     } else {
-        Ok(Ok(TakenBagItem::get_random(&pool).await?))
+        let bag = match authorize(&auth, bag_id, Role::Player, &pool, &response).await? {
+            Ok(bag) => bag,
+            Err(e) => return Ok(Err(e)),
+        };
+        let user = auth.current_user.unwrap();
+        match TakenBagItem::by_id(draw_id, &pool).await? {
+            Some(tbi) if tbi.bag_id == bag_id => {
+                let window = chrono::Duration::seconds(bag.undo_window_secs.into());
+                if tbi.drawn_by.as_ref().map(|u| u.id) != Some(user.id) {
+                    response.set_status(StatusCode::FORBIDDEN);
+                    Ok(Err(RoadieAppError::Unauthorized))
+                } else if Utc::now() - tbi.extraction_time > window {
+                    response.set_status(StatusCode::CONFLICT);
+                    Ok(Err(RoadieAppError::UndoWindowPassed))
+                } else if !tbi.undo(&pool).await? {
+                    response.set_status(StatusCode::CONFLICT);
+                    Ok(Err(RoadieAppError::NotLatestDraw))
+                } else {
+                    tracing::info!("User {} undid draw {} in bag {}", user.id, draw_id, bag_id);
+                    Ok(Ok(()))
+                }
+            }
+            _ => {
+                response.set_status(StatusCode::NOT_FOUND);
+                Ok(Err(RoadieAppError::NotFound))
+            }
+        }
     }
 }
 
 #[tracing::instrument(level = "info", fields(error), ret, err)]
-#[server(UpdateTaken, "/api", "Url", "update_taken")]
-pub async fn update_taken(taken_item: TakenBagItem) -> Result<RoadieResult<()>, ServerFnError> {
+#[server(ReplayDraw, "/api", "Url", "replay_draw")]
+pub async fn replay_draw(
+    bag_id: i64,
+    draw_id: i64,
+) -> Result<RoadieResult<DrawOutcome>, ServerFnError> {
     let pool = db_pool()?;
     let auth = auth_session()?;
     let response = expect_context::<ResponseOptions>();
@@ -245,14 +993,28 @@ pub async fn update_taken(taken_item: TakenBagItem) -> Result<RoadieResult<()>,
         response.set_status(StatusCode::UNAUTHORIZED);
         leptos_axum::redirect("/auth");
         Ok(Err(RoadieAppError::Unauthorized))
+    } else if let Err(e) = authorize(&auth, bag_id, Role::Viewer, &pool, &response).await? {
+        Ok(Err(e))
     } else {
-        Ok(Ok(taken_item.update(&pool).await?))
+        match TakenBagItem::by_id(draw_id, &pool).await? {
+            Some(tbi) if tbi.bag_id == bag_id => match tbi.replay(&pool).await? {
+                Some(outcome) => Ok(Ok(outcome)),
+                None => {
+                    response.set_status(StatusCode::NOT_FOUND);
+                    Ok(Err(RoadieAppError::DrawNotReplayable))
+                }
+            },
+            _ => {
+                response.set_status(StatusCode::NOT_FOUND);
+                Ok(Err(RoadieAppError::NotFound))
+            }
+        }
     }
 }
 
 #[tracing::instrument(level = "info", fields(error), ret, err)]
 #[server(LastTaken, "/api", "Url", "last_taken")]
-pub async fn last_taken() -> Result<RoadieResult<Option<TakenBagItem>>, ServerFnError> {
+pub async fn last_taken(bag_id: i64) -> Result<RoadieResult<Option<TakenBagItem>>, ServerFnError> {
     let pool = db_pool()?;
     let auth = auth_session()?;
     let response = expect_context::<ResponseOptions>();
@@ -261,15 +1023,40 @@ pub async fn last_taken() -> Result<RoadieResult<Option<TakenBagItem>>, ServerFn
         response.set_status(StatusCode::UNAUTHORIZED);
         leptos_axum::redirect("/auth");
         Ok(Err(RoadieAppError::Unauthorized))
+    } else if let Err(e) = authorize(&auth, bag_id, Role::Viewer, &pool, &response).await? {
+        Ok(Err(e))
     } else {
-        let item = TakenBagItem::last(&pool).await?;
+        let user = auth.current_user.unwrap();
+        let item = TakenBagItem::active_for(bag_id, user.id, &pool).await?;
         Ok(Ok(item))
     }
 }
 
+#[tracing::instrument(level = "info", fields(error), ret, err)]
+#[server(OthersTaken, "/api", "Url", "others_taken")]
+pub async fn others_taken(bag_id: i64) -> Result<RoadieResult<Vec<TakenBagItem>>, ServerFnError> {
+    let pool = db_pool()?;
+    let auth = auth_session()?;
+    let response = expect_context::<ResponseOptions>();
+
+    if auth.is_anonymous() {
+        response.set_status(StatusCode::UNAUTHORIZED);
+        leptos_axum::redirect("/auth");
+        Ok(Err(RoadieAppError::Unauthorized))
+    } else if let Err(e) = authorize(&auth, bag_id, Role::Viewer, &pool, &response).await? {
+        Ok(Err(e))
+    } else {
+        let user = auth.current_user.unwrap();
+        Ok(Ok(TakenBagItem::active_for_others(bag_id, user.id, &pool).await?))
+    }
+}
+
 #[tracing::instrument(level = "info", fields(error), ret, err)]
 #[server(ForItem, "/api", "Url", "for_item")]
-pub async fn for_item(item_id: i64) -> Result<RoadieResult<Vec<TakenBagItem>>, ServerFnError> {
+pub async fn for_item(
+    bag_id: i64,
+    item_id: i64,
+) -> Result<RoadieResult<Vec<TakenBagItem>>, ServerFnError> {
     let pool = db_pool()?;
     let auth = auth_session()?;
     let response = expect_context::<ResponseOptions>();
@@ -278,8 +1065,101 @@ pub async fn for_item(item_id: i64) -> Result<RoadieResult<Vec<TakenBagItem>>, S
         response.set_status(StatusCode::UNAUTHORIZED);
         leptos_axum::redirect("/auth");
         Ok(Err(RoadieAppError::Unauthorized))
+    } else if let Err(e) = authorize(&auth, bag_id, Role::Viewer, &pool, &response).await? {
+        Ok(Err(e))
     } else {
-        let items = TakenBagItem::for_item(item_id, &pool).await?;
-        Ok(Ok(items))
+        match BagItem::by_id(item_id, &pool).await? {
+            Some(bi) if bi.bag_id == bag_id => {
+                let items = TakenBagItem::for_item(item_id, &pool).await?;
+                Ok(Ok(items))
+            }
+            _ => {
+                response.set_status(StatusCode::NOT_FOUND);
+                Ok(Err(RoadieAppError::NotFound))
+            }
+        }
+    }
+}
+
+#[tracing::instrument(level = "info", fields(error), ret, err)]
+#[server(ListBagMembers, "/api", "Url", "list_bag_members")]
+pub async fn list_bag_members(bag_id: i64) -> Result<RoadieResult<Vec<BagMember>>, ServerFnError> {
+    let pool = db_pool()?;
+    let auth = auth_session()?;
+    let response = expect_context::<ResponseOptions>();
+
+    if auth.is_anonymous() {
+        response.set_status(StatusCode::UNAUTHORIZED);
+        leptos_axum::redirect("/auth");
+        Ok(Err(RoadieAppError::Unauthorized))
+    } else {
+        match authorize(&auth, bag_id, Role::Owner, &pool, &response).await? {
+            Ok(bag) => Ok(Ok(bag.members(&pool).await?)),
+            Err(e) => Ok(Err(e)),
+        }
+    }
+}
+
+#[tracing::instrument(level = "info", fields(error), ret, err)]
+#[server(GrantRole, "/api", "Url", "grant_role")]
+pub async fn grant_role(
+    bag_id: i64,
+    username: String,
+    role: Role,
+) -> Result<RoadieResult<()>, ServerFnError> {
+    let pool = db_pool()?;
+    let auth = auth_session()?;
+    let response = expect_context::<ResponseOptions>();
+
+    if auth.is_anonymous() {
+        response.set_status(StatusCode::UNAUTHORIZED);
+        Ok(Err(RoadieAppError::Unauthorized))
+    } else {
+        let bag = match authorize(&auth, bag_id, Role::Owner, &pool, &response).await? {
+            Ok(bag) => bag,
+            Err(e) => return Ok(Err(e)),
+        };
+        let user = match SQLUser::by_username(username, &pool).await? {
+            Some(user) => user,
+            None => {
+                response.set_status(StatusCode::BAD_REQUEST);
+                return Ok(Err(RoadieAppError::ValidationFailedForField(
+                    "username".into(),
+                )));
+            }
+        };
+        if role != Role::Owner && is_last_owner(&bag, user.id, &pool).await? {
+            response.set_status(StatusCode::BAD_REQUEST);
+            return Ok(Err(RoadieAppError::LastBagOwner));
+        }
+        tracing::info!("Giving user {} the {} role in bag {}", user.id, role, bag_id);
+        bag.set_role(user.id, role, &pool).await?;
+        Ok(Ok(()))
+    }
+}
+
+#[tracing::instrument(level = "info", fields(error), ret, err)]
+#[server(RevokeRole, "/api", "Url", "revoke_role")]
+pub async fn revoke_role(bag_id: i64, user_id: i64) -> Result<RoadieResult<()>, ServerFnError> {
+    let pool = db_pool()?;
+    let auth = auth_session()?;
+    let response = expect_context::<ResponseOptions>();
+
+    if auth.is_anonymous() {
+        response.set_status(StatusCode::UNAUTHORIZED);
+        Ok(Err(RoadieAppError::Unauthorized))
+    } else {
+        match authorize(&auth, bag_id, Role::Owner, &pool, &response).await? {
+            Ok(bag) if is_last_owner(&bag, user_id, &pool).await? => {
+                response.set_status(StatusCode::BAD_REQUEST);
+                Ok(Err(RoadieAppError::LastBagOwner))
+            }
+            Ok(bag) => {
+                tracing::info!("Removing user {} from bag {}", user_id, bag_id);
+                bag.remove_member(user_id, &pool).await?;
+                Ok(Ok(()))
+            }
+            Err(e) => Ok(Err(e)),
+        }
     }
 }
@@ -0,0 +1,141 @@
+use cfg_if::cfg_if;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::errors::RoadieAppError;
+
+/// Caps on what a dice expression can ask for, so a typo can't make a draw last forever
+const MAX_DICE: u32 = 100;
+const MAX_SIDES: u32 = 1000;
+const MAX_MODIFIER: i32 = 1000;
+
+/// How many rounds a draw lasts, written like `1d6`, `2d4+1`, `d8-1` or just `3`.
+///
+/// A fixed number is stored as zero dice with a modifier. Every expression has to roll at
+/// least 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct DiceExpr {
+    pub count: u32,
+    pub sides: u32,
+    pub modifier: i32,
+}
+
+impl Default for DiceExpr {
+    fn default() -> Self {
+        DiceExpr {
+            count: 1,
+            sides: 6,
+            modifier: 0,
+        }
+    }
+}
+
+impl DiceExpr {
+    pub fn fixed(rounds: u32) -> Self {
+        DiceExpr {
+            count: 0,
+            sides: 0,
+            modifier: rounds as i32,
+        }
+    }
+
+    pub fn min(&self) -> i32 {
+        self.count as i32 + self.modifier
+    }
+
+    pub fn max(&self) -> i32 {
+        (self.count * self.sides) as i32 + self.modifier
+    }
+}
+
+impl FromStr for DiceExpr {
+    type Err = RoadieAppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || RoadieAppError::InvalidDiceExpr(s.to_string());
+        let expr: String = s
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+            .to_lowercase();
+        let number = |n: &str| n.parse::<u32>().map_err(|_| invalid());
+
+        let parsed = match expr.split_once('d') {
+            None => DiceExpr::fixed(number(&expr)?),
+            Some((count, rest)) => {
+                let count = if count.is_empty() { 1 } else { number(count)? };
+                let (sides, modifier) = match rest.find(['+', '-']) {
+                    Some(i) => {
+                        let modifier = number(&rest[i + 1..])? as i32;
+                        let modifier = if rest[i..].starts_with('-') {
+                            -modifier
+                        } else {
+                            modifier
+                        };
+                        (number(&rest[..i])?, modifier)
+                    }
+                    None => (number(rest)?, 0),
+                };
+                DiceExpr {
+                    count,
+                    sides,
+                    modifier,
+                }
+            }
+        };
+
+        let dice_ok = parsed.count == 0 || (1..=MAX_DICE).contains(&parsed.count);
+        let sides_ok = parsed.count == 0 || (1..=MAX_SIDES).contains(&parsed.sides);
+        if !dice_ok || !sides_ok || parsed.modifier.abs() > MAX_MODIFIER || parsed.min() < 1 {
+            Err(invalid())
+        } else {
+            Ok(parsed)
+        }
+    }
+}
+
+impl fmt::Display for DiceExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.count == 0 {
+            return write!(f, "{}", self.modifier);
+        }
+        write!(f, "{}d{}", self.count, self.sides)?;
+        match self.modifier {
+            0 => Ok(()),
+            m if m > 0 => write!(f, "+{}", m),
+            m => write!(f, "{}", m),
+        }
+    }
+}
+
+impl TryFrom<String> for DiceExpr {
+    type Error = RoadieAppError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<DiceExpr> for String {
+    fn from(value: DiceExpr) -> Self {
+        value.to_string()
+    }
+}
+
+cfg_if! {
+    if #[cfg(feature = "ssr")] {
+        use rand::Rng;
+
+        impl DiceExpr {
+            /// Rolls every die once and adds the modifier
+            pub fn roll<R: Rng>(&self, rng: &mut R) -> u32 {
+                let rolled: i32 = (0..self.count)
+                    .map(|_| rng.gen_range(1..=self.sides) as i32)
+                    .sum();
+                (rolled + self.modifier).max(1) as u32
+            }
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use cfg_if::cfg_if;
+use serde::{Deserialize, Serialize};
+
+use super::dice::DiceExpr;
+
+/// An item that can come out of the bag, along with how likely it is to be picked and how
+/// long it lasts. Candidates stored before round durations were configurable roll a d6.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DrawCandidate {
+    pub item_id: i64,
+    pub weight: u32,
+    #[serde(default)]
+    pub rounds: DiceExpr,
+}
+
+/// Everything a draw decided. Replaying `seed` against the same candidates always gives the
+/// same item and number of rounds
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DrawOutcome {
+    pub seed: u64,
+    pub item_id: i64,
+    pub num_rounds: u32,
+}
+
+cfg_if! {
+    if #[cfg(feature = "ssr")] {
+        use rand::{RngCore, SeedableRng};
+        use rand::distributions::{Distribution, WeightedIndex};
+        use rand_chacha::ChaCha8Rng;
+
+        /// Picks items out of a bag.
+        ///
+        /// The injected RNG is only used to pick a seed for each draw. The draw itself runs on a
+        /// [`ChaCha8Rng`] seeded from it, so storing the seed is enough to reproduce the draw later
+        /// with [`replay`].
+        pub struct DrawEngine<R: RngCore> {
+            rng: R,
+        }
+
+        impl DrawEngine<ChaCha8Rng> {
+            pub fn from_entropy() -> Self {
+                Self::new(ChaCha8Rng::from_entropy())
+            }
+        }
+
+        impl<R: RngCore> DrawEngine<R> {
+            pub fn new(rng: R) -> Self {
+                DrawEngine { rng }
+            }
+
+            /// Draws one of the candidates with a fresh seed. Returns `None` when there's nothing
+            /// with a weight above zero to draw.
+            pub fn draw(&mut self, candidates: &[DrawCandidate]) -> Option<DrawOutcome> {
+                let seed = self.rng.next_u64();
+                replay(seed, candidates)
+            }
+        }
+
+        pub fn replay(seed: u64, candidates: &[DrawCandidate]) -> Option<DrawOutcome> {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let distribution = WeightedIndex::new(candidates.iter().map(|c| c.weight)).ok()?;
+            let candidate = &candidates[distribution.sample(&mut rng)];
+            let item_id = candidate.item_id;
+            let num_rounds = candidate.rounds.roll(&mut rng);
+            Some(DrawOutcome {
+                seed,
+                item_id,
+                num_rounds,
+            })
+        }
+    }
+}
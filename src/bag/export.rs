@@ -0,0 +1,386 @@
+use cfg_if::cfg_if;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_qs as qs;
+use strum::*;
+
+use super::model::*;
+
+/// Formats bag contents and draw history can be exported as
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Display, EnumIter)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[strum(serialize = "CSV")]
+    Csv,
+    #[strum(serialize = "JSON")]
+    Json,
+    #[strum(serialize = "NDJSON")]
+    Ndjson,
+}
+
+impl ExportFormat {
+    /// How the format is written in export links, also used as the file extension
+    pub fn key(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            ExportFormat::Ndjson => "ndjson",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Json => "application/json",
+            ExportFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+}
+
+/// Link to download every item matching `filter`. Paging is left out since the export always
+/// has all of them.
+pub fn items_export_href(bag_id: i64, format: ExportFormat, filter: &BagItemFilter) -> String {
+    let filter = BagItemFilter {
+        bag_id: None,
+        page_num: None,
+        page_size: None,
+        paging: None,
+        after: None,
+        before: None,
+        ..filter.clone()
+    };
+    format!(
+        "/export/bag/{}/items/{}?{}",
+        bag_id,
+        format.key(),
+        qs::to_string(&filter).expect("Couldn't serialize query string")
+    )
+}
+
+/// Link to download every draw matching `filter`
+pub fn history_export_href(bag_id: i64, format: ExportFormat, filter: &TakenItemFilter) -> String {
+    let filter = TakenItemFilter {
+        bag_id: None,
+        page_num: None,
+        page_size: None,
+        ..filter.clone()
+    };
+    format!(
+        "/export/bag/{}/history/{}?{}",
+        bag_id,
+        format.key(),
+        qs::to_string(&filter).expect("Couldn't serialize query string")
+    )
+}
+
+/// Something that can be written out as one row of an export
+pub trait ExportRecord: Serialize {
+    /// The CSV header, which has to be written even when there aren't any rows to take it from
+    const COLUMNS: &'static [&'static str];
+}
+
+/// An exported item. The columns line up with [`super::import::ImportRow`], so an export can
+/// be imported into another bag.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemRecord {
+    pub id: i64,
+    pub name: String,
+    pub description: String,
+    pub quantity: i32,
+    pub size: String,
+    pub infinite: bool,
+    pub weight: Option<u32>,
+    pub rounds: Option<String>,
+    pub par_level: Option<i32>,
+    /// Comma separated
+    pub tags: String,
+    pub added_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ExportRecord for ItemRecord {
+    const COLUMNS: &'static [&'static str] = &[
+        "id", "name", "description", "quantity", "size", "infinite", "weight", "rounds", "par_level",
+        "tags", "added_by", "created_at",
+    ];
+}
+
+impl From<BagItem> for ItemRecord {
+    fn from(value: BagItem) -> Self {
+        ItemRecord {
+            id: value.id,
+            name: value.name,
+            description: value.description,
+            quantity: value.quantity,
+            size: value.size.to_string(),
+            infinite: value.infinite,
+            weight: value.weight,
+            rounds: value.rounds.map(|r| r.to_string()),
+            par_level: value.par_level,
+            tags: value.tags.join(", "),
+            added_by: value.added_by.username,
+            created_at: value.created_at,
+        }
+    }
+}
+
+/// An exported draw
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DrawRecord {
+    pub id: i64,
+    pub item_id: i64,
+    pub item: String,
+    pub drawn_at: DateTime<Utc>,
+    pub drawn_by: Option<String>,
+    pub rounds: u32,
+    pub rounds_remaining: u32,
+    pub done: bool,
+    pub abandoned: bool,
+    pub completed_by: Option<String>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub seed: Option<u64>,
+}
+
+impl ExportRecord for DrawRecord {
+    const COLUMNS: &'static [&'static str] = &[
+        "id", "item_id", "item", "drawn_at", "drawn_by", "rounds", "rounds_remaining", "done",
+        "abandoned", "completed_by", "completed_at", "seed",
+    ];
+}
+
+impl From<TakenBagItem> for DrawRecord {
+    fn from(value: TakenBagItem) -> Self {
+        DrawRecord {
+            id: value.id,
+            item_id: value.item.id,
+            item: value.item.name,
+            drawn_at: value.extraction_time,
+            drawn_by: value.drawn_by.map(|u| u.username),
+            rounds: value.rounds,
+            rounds_remaining: value.rounds_remaining,
+            done: value.done,
+            abandoned: value.abandoned,
+            completed_by: value.completed_by.map(|u| u.username),
+            completed_at: value.completed_at,
+            seed: value.seed,
+        }
+    }
+}
+
+cfg_if! {
+    if #[cfg(feature = "ssr")] {
+        use axum::{
+            body::StreamBody,
+            extract::{Path, RawQuery, State},
+            http::{header, StatusCode},
+            response::{IntoResponse, Response},
+            routing::get,
+            Router,
+        };
+        use futures::stream::{self, BoxStream, Stream, StreamExt, TryStreamExt};
+        use sqlx::SqlitePool;
+
+        use crate::auth::{has_role, AuthSession, Role};
+        use crate::state::AppState;
+
+        pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+        /// How many rows are loaded from the database at a time while streaming an export
+        const EXPORT_PAGE_SIZE: u64 = 500;
+
+        pub fn routes() -> Router<AppState> {
+            Router::new()
+                .route("/export/bag/:bag_id/items/:format", get(export_items))
+                .route("/export/bag/:bag_id/history/:format", get(export_history))
+        }
+
+        /// Exports are open to anyone who can view the bag
+        async fn check_access(auth: &AuthSession, bag_id: i64, pool: &SqlitePool) -> Result<(), StatusCode> {
+            if auth.is_anonymous() {
+                return Err(StatusCode::UNAUTHORIZED);
+            }
+            match Bag::by_id(bag_id, pool).await {
+                Err(e) => {
+                    tracing::error!("Unable to load bag {}: {}", bag_id, e);
+                    Err(StatusCode::INTERNAL_SERVER_ERROR)
+                }
+                Ok(None) => Err(StatusCode::NOT_FOUND),
+                Ok(Some(_)) if !has_role(auth, bag_id, Role::Viewer, pool).await => {
+                    tracing::warn!("User {:?} can't export bag {}", auth.current_user, bag_id);
+                    Err(StatusCode::FORBIDDEN)
+                }
+                Ok(Some(_)) => Ok(()),
+            }
+        }
+
+        #[tracing::instrument(level = "info", skip(auth, pool))]
+        async fn export_items(
+            auth: AuthSession,
+            State(pool): State<SqlitePool>,
+            Path((bag_id, format)): Path<(i64, ExportFormat)>,
+            RawQuery(query): RawQuery,
+        ) -> Response {
+            if let Err(status) = check_access(&auth, bag_id, &pool).await {
+                return status.into_response();
+            }
+            let filter = match qs::from_str::<BagItemFilter>(&query.unwrap_or_default()) {
+                Ok(filter) => filter,
+                Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+            };
+            let pages = item_pages(bag_id, filter, pool);
+            attachment(format, format!("bag-{}-items", bag_id), pages)
+        }
+
+        #[tracing::instrument(level = "info", skip(auth, pool))]
+        async fn export_history(
+            auth: AuthSession,
+            State(pool): State<SqlitePool>,
+            Path((bag_id, format)): Path<(i64, ExportFormat)>,
+            RawQuery(query): RawQuery,
+        ) -> Response {
+            if let Err(status) = check_access(&auth, bag_id, &pool).await {
+                return status.into_response();
+            }
+            let filter = match qs::from_str::<TakenItemFilter>(&query.unwrap_or_default()) {
+                Ok(filter) => filter,
+                Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+            };
+            let pages = draw_pages(bag_id, filter, pool);
+            attachment(format, format!("bag-{}-history", bag_id), pages)
+        }
+
+        /// Every item in the bag that matches `filter`, a page at a time
+        pub fn item_pages(
+            bag_id: i64,
+            filter: BagItemFilter,
+            pool: SqlitePool,
+        ) -> impl Stream<Item = Result<Vec<ItemRecord>, sqlx::Error>> + Send + 'static {
+            // Walk the items with cursors so items added during the export don't repeat any
+            let filter = BagItemFilter {
+                bag_id: Some(bag_id),
+                paging: Some(PagingMode::Cursor),
+                page_size: Some(EXPORT_PAGE_SIZE),
+                page_num: None,
+                ..filter.with_cursors(None, None)
+            };
+            stream::try_unfold(Some(filter), move |filter| {
+                let pool = pool.clone();
+                async move {
+                    let filter = match filter {
+                        Some(filter) => filter,
+                        None => return Ok(None),
+                    };
+                    let page = BagItem::filter(filter.clone(), &pool).await?;
+                    let next = page.next_cursor.map(|c| filter.with_cursors(Some(c), None));
+                    let records = page.items.into_iter().map(ItemRecord::from).collect::<Vec<_>>();
+                    Ok::<_, sqlx::Error>(Some((records, next)))
+                }
+            })
+        }
+
+        /// Every draw from the bag that matches `filter`, newest first, a page at a time
+        pub fn draw_pages(
+            bag_id: i64,
+            filter: TakenItemFilter,
+            pool: SqlitePool,
+        ) -> impl Stream<Item = Result<Vec<DrawRecord>, sqlx::Error>> + Send + 'static {
+            let filter = TakenItemFilter {
+                bag_id: Some(bag_id),
+                page_size: Some(EXPORT_PAGE_SIZE),
+                ..filter.with_page(1)
+            };
+            stream::try_unfold((Some(filter), None), move |(filter, last_id): (Option<TakenItemFilter>, Option<i64>)| {
+                let pool = pool.clone();
+                async move {
+                    let filter = match filter {
+                        Some(filter) => filter,
+                        None => return Ok(None),
+                    };
+                    let page = TakenBagItem::filter(filter.clone(), &pool).await?;
+                    let next = (page.page_num < page.total_pages).then(|| filter.with_page(page.page_num + 1));
+                    // Newest draws come first, so draws made during the export push ones that
+                    // were already sent onto the next page
+                    let records = page.items
+                        .into_iter()
+                        .filter(|d| last_id.map_or(true, |last| d.id < last))
+                        .map(DrawRecord::from)
+                        .collect::<Vec<_>>();
+                    let last_id = records.last().map(|d| d.id).or(last_id);
+                    Ok::<_, sqlx::Error>(Some((records, (next, last_id))))
+                }
+            })
+        }
+
+        /// Streams the pages back as a download, one record per chunk
+        fn attachment<T, S>(format: ExportFormat, name: String, pages: S) -> Response
+        where
+            T: ExportRecord + Send + 'static,
+            S: Stream<Item = Result<Vec<T>, sqlx::Error>> + Send + 'static,
+        {
+            (
+                [
+                    (header::CONTENT_TYPE, format.content_type().to_string()),
+                    (
+                        header::CONTENT_DISPOSITION,
+                        format!("attachment; filename=\"{}.{}\"", name, format.key()),
+                    ),
+                ],
+                StreamBody::new(encode_pages(format, pages)),
+            )
+                .into_response()
+        }
+
+        /// Writes out the records in the pages one at a time, along with whatever the format
+        /// needs around them
+        pub fn encode_pages<T, S>(format: ExportFormat, pages: S) -> BoxStream<'static, Result<String, BoxError>>
+        where
+            T: ExportRecord + Send + 'static,
+            S: Stream<Item = Result<Vec<T>, sqlx::Error>> + Send + 'static,
+        {
+            let records = pages
+                .map_ok(|records| stream::iter(records.into_iter().map(Ok)))
+                .try_flatten()
+                .enumerate()
+                .map(move |(i, record)| {
+                    record
+                        .map_err(BoxError::from)
+                        .and_then(|record| encode(format, i == 0, &record))
+                });
+            match format {
+                ExportFormat::Json => {
+                    let open = stream::once(async { Ok::<_, BoxError>("[".to_string()) });
+                    let close = stream::once(async { Ok("]".to_string()) });
+                    open.chain(records).chain(close).boxed()
+                }
+                ExportFormat::Csv => {
+                    let header = stream::once(async { csv_row(T::COLUMNS) });
+                    header.chain(records).boxed()
+                }
+                ExportFormat::Ndjson => records.boxed(),
+            }
+        }
+
+        fn csv_row(columns: &[&str]) -> Result<String, BoxError> {
+            let mut writer = csv::Writer::from_writer(vec![]);
+            writer.write_record(columns)?;
+            let bytes = writer.into_inner().map_err(|e| e.into_error())?;
+            Ok(String::from_utf8(bytes)?)
+        }
+
+        fn encode<T: Serialize>(format: ExportFormat, first: bool, record: &T) -> Result<String, BoxError> {
+            match format {
+                ExportFormat::Csv => {
+                    let mut writer = csv::WriterBuilder::new()
+                        .has_headers(false)
+                        .from_writer(vec![]);
+                    writer.serialize(record)?;
+                    let bytes = writer.into_inner().map_err(|e| e.into_error())?;
+                    Ok(String::from_utf8(bytes)?)
+                }
+                ExportFormat::Json if first => Ok(serde_json::to_string(record)?),
+                ExportFormat::Json => Ok(format!(",{}", serde_json::to_string(record)?)),
+                ExportFormat::Ndjson => Ok(format!("{}\n", serde_json::to_string(record)?)),
+            }
+        }
+    }
+}
@@ -6,6 +6,7 @@ use leptos_router::*;
 use std::collections::HashMap;
 use strum::*;
 
+use super::use_bag_id;
 use crate::bag::api::*;
 use crate::bag::model::*;
 use crate::errors::RoadieAppError;
@@ -23,15 +24,16 @@ pub fn AddEditItem() -> impl IntoView {
 #[component]
 pub fn ItemForm(_editable: bool) -> impl IntoView {
     let params = use_params::<AddEditParams>();
+    let bag_id = use_bag_id();
     let (submit_error, set_submit_error) = create_signal(HashMap::new());
     let action = create_server_action::<CreateUpdateBagItem>();
 
     create_resource(
-        move || params.get(),
-        move |p| async move {
+        move || (bag_id(), params.get()),
+        move |(bag_id, p)| async move {
             if let Ok(p) = p {
                 if let Some(id) = p.id {
-                    match get_bag_item(id).await {
+                    match get_bag_item(bag_id, id).await {
                         Ok(Err(RoadieAppError::MultipleErrors(e))) => set_submit_error(e),
                         Err(e) => {
                             set_submit_error.update(|m| {
@@ -55,7 +57,7 @@ pub fn ItemForm(_editable: bool) -> impl IntoView {
                 }
             } else {
                 logging::error!("Unable to parse params");
-                use_navigate()("/items", Default::default());
+                use_navigate()(&format!("/bag/{}/items", bag_id), Default::default());
             }
         },
     );
@@ -228,6 +230,23 @@ pub fn ItemForm(_editable: bool) -> impl IntoView {
     let infinite_error =
         Signal::derive(move || submit_error.with(|em| em.get("infinite").cloned()));
 
+    let weight = create_memo(move |_| {
+        result.with(|bif| bif.weight.map(|w| w.to_string()).unwrap_or_default())
+    });
+    let weight_error = Signal::derive(move || submit_error.with(|em| em.get("weight").cloned()));
+
+    let par_level = create_memo(move |_| {
+        result.with(|bif| bif.par_level.map(|p| p.to_string()).unwrap_or_default())
+    });
+    let par_level_error =
+        Signal::derive(move || submit_error.with(|em| em.get("par_level").cloned()));
+
+    let rounds = create_memo(move |_| result.with(|bif| bif.rounds.clone().unwrap_or_default()));
+    let rounds_error = Signal::derive(move || submit_error.with(|em| em.get("rounds").cloned()));
+
+    let tags = create_memo(move |_| result.with(|bif| bif.tags.clone()));
+    let tags_error = Signal::derive(move || submit_error.with(|em| em.get("tags").cloned()));
+
     let other_error = Signal::derive(move || submit_error.with(|em| em.get("other").cloned()));
 
     let on_submit = move |ev: SubmitEvent| {
@@ -269,6 +288,7 @@ pub fn ItemForm(_editable: bool) -> impl IntoView {
                     <div class="py-24 px-10 w-full">
                         <ActionForm action=action on:submit=on_submit>
                             <h2 class="text-2xl font-semibold mb-2 text-center">{submit_text}</h2>
+                            <input type="hidden" name="bag_id" prop:value=move || bag_id().to_string()/>
                             <InputText
                                 field_label="Id"
                                 container_style_base="invisible"
@@ -315,6 +335,38 @@ pub fn ItemForm(_editable: bool) -> impl IntoView {
                                 field_name="item[infinite]"
                             />
                             <Alert alert_type="Error".into() msg=infinite_error/>
+
+                            <InputText
+                                input_type="number"
+                                field_label="Par level (what a restock fills it back up to, leave empty to never restock)"
+                                field_value=par_level
+                                field_name="item[par_level]"
+                            />
+                            <Alert alert_type="Error".into() msg=par_level_error/>
+
+                            <InputText
+                                input_type="number"
+                                field_label="Draw weight (leave empty to use the bag's weight for this size)"
+                                field_value=weight
+                                field_name="item[weight]"
+                            />
+                            <Alert alert_type="Error".into() msg=weight_error/>
+
+                            <InputText
+                                field_label="Rounds, like 1d6 or 2d4+1 (leave empty to use the bag's default)"
+                                field_value=rounds
+                                placeholder="1d6"
+                                field_name="item[rounds]"
+                            />
+                            <Alert alert_type="Error".into() msg=rounds_error/>
+
+                            <InputText
+                                field_label="Tags, separated by commas"
+                                field_value=tags
+                                placeholder="dungeon, treasure"
+                                field_name="item[tags]"
+                            />
+                            <Alert alert_type="Error".into() msg=tags_error/>
                             <button type="submit" class="btn mt-2 w-full btn-primary">
                                 {submit_text}
                             </button>
@@ -0,0 +1,276 @@
+use crate::common::components::input::*;
+use crate::common::components::Alert;
+use leptos::ev::SubmitEvent;
+use leptos::*;
+use leptos_router::*;
+use std::collections::HashMap;
+
+use super::{use_bag_id, BagContext};
+use crate::bag::api::*;
+use crate::errors::RoadieAppError;
+
+#[component]
+pub fn BagPicker() -> impl IntoView {
+    let bag_context = use_context::<BagContext>().expect("Failed to get BagContext");
+
+    let options = Signal::derive(move || {
+        bag_context
+            .bags
+            .get()
+            .and_then(|r| r.ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|bag| (bag.id, bag.name))
+            .collect::<Vec<(i64, String)>>()
+    });
+
+    view! {
+        <select
+            class="select select-bordered select-sm w-full max-w-xs"
+            on:change=move |ev| {
+                if let Ok(id) = event_target_value(&ev).parse::<i64>() {
+                    use_navigate()(&format!("/bag/{}", id), Default::default());
+                }
+            }
+        >
+
+            <option disabled selected=move || bag_context.bag_id.get().is_none()>
+                "Pick a bag"
+            </option>
+            <For
+                each=options
+                key=|option| option.0
+                children=move |(id, name)| {
+                    view! {
+                        <option
+                            value=id.to_string()
+                            selected=move || bag_context.bag_id.get() == Some(id)
+                        >
+                            {name}
+                        </option>
+                    }
+                }
+            />
+
+        </select>
+    }
+}
+
+#[component]
+pub fn BagList() -> impl IntoView {
+    let bag_context = use_context::<BagContext>().expect("Failed to get BagContext");
+
+    let bags = Signal::derive(move || {
+        bag_context
+            .bags
+            .get()
+            .and_then(|r| r.ok())
+            .unwrap_or_default()
+    });
+
+    view! {
+        <div class="min-h-screen bg-base-200 flex items-center">
+            <div class="card mx-auto w-full max-w-5xl  shadow-xl">
+                <div class="bg-base-100 rounded-xl">
+                    <div class="py-24 px-10 w-full">
+                        <h2 class="text-2xl font-semibold mb-2 text-center">"Your bags"</h2>
+                        <ul class="menu bg-base-200 rounded-box">
+                            <For
+                                each=bags
+                                key=|bag| bag.id
+                                children=move |bag| {
+                                    view! {
+                                        <li>
+                                            <A href=format!("/bag/{}", bag.id)>
+                                                <span class="font-semibold">{bag.name}</span>
+                                                <span class="text-sm">{bag.description}</span>
+                                            </A>
+                                        </li>
+                                    }
+                                }
+                            />
+
+                        </ul>
+                        <A href="/bags/add" class="btn mt-2 w-full btn-primary">
+                            "Create Bag"
+                        </A>
+                    </div>
+                </div>
+            </div>
+        </div>
+    }
+}
+
+#[component]
+pub fn AddEditBag() -> impl IntoView {
+    let bag_id = use_bag_id();
+    let (submit_error, set_submit_error) = create_signal(HashMap::new());
+    let action = create_server_action::<CreateUpdateBag>();
+
+    create_resource(
+        move || bag_id(),
+        move |id| async move {
+            if id == -1 {
+                match new_bag_form().await {
+                    Ok(Ok(form)) => action.value().set(Some(Ok(Ok(form)))),
+                    Ok(Err(e)) => set_submit_error.update(|m| {
+                        m.insert("other".to_string(), e.to_string());
+                    }),
+                    Err(e) => set_submit_error.update(|m| {
+                        m.insert("other".to_string(), e.to_string());
+                    }),
+                }
+            } else {
+                match get_bag(id).await {
+                    Ok(Ok(bag)) => action.value().set(Some(Ok(Ok(bag.into())))),
+                    Ok(Err(e)) => set_submit_error.update(|m| {
+                        m.insert("other".to_string(), e.to_string());
+                    }),
+                    Err(e) => set_submit_error.update(|m| {
+                        m.insert("other".to_string(), e.to_string());
+                    }),
+                }
+            }
+        },
+    );
+
+    create_effect(move |_| {
+        if let Some(Ok(Ok(bag))) = action.value().get() {
+            if action.version().get() > 0 {
+                use_navigate()(&format!("/bag/{}", bag.id), Default::default());
+            }
+        }
+    });
+
+    let submit_text = move || {
+        action.pending().track();
+        if let Some(Ok(Ok(r))) = action.value().get() {
+            if r.id == -1 {
+                "Create Bag".to_string()
+            } else {
+                "Update Bag".to_string()
+            }
+        } else {
+            "".to_string()
+        }
+    };
+
+    let result = create_memo(move |_| {
+        action.value().with(|f| {
+            f.clone()
+                .map(|g| g.map(|r1| r1.unwrap_or_default()).unwrap_or_default())
+                .unwrap_or_default()
+        })
+    });
+
+    let id = create_memo(move |_| result.with(|bf| bf.id.to_string()));
+    let name = create_memo(move |_| result.with(|bf| bf.name.clone()));
+    let name_error = Signal::derive(move || submit_error.with(|em| em.get("name").cloned()));
+    let desc = create_memo(move |_| result.with(|bf| bf.description.clone()));
+    let small_weight = create_memo(move |_| result.with(|bf| bf.small_weight.to_string()));
+    let medium_weight = create_memo(move |_| result.with(|bf| bf.medium_weight.to_string()));
+    let large_weight = create_memo(move |_| result.with(|bf| bf.large_weight.to_string()));
+    let weights_error = Signal::derive(move || submit_error.with(|em| em.get("weights").cloned()));
+    let undo_window_secs = create_memo(move |_| result.with(|bf| bf.undo_window_secs.to_string()));
+    let default_rounds = create_memo(move |_| result.with(|bf| bf.default_rounds.clone()));
+    let default_rounds_error =
+        Signal::derive(move || submit_error.with(|em| em.get("default_rounds").cloned()));
+    let other_error = Signal::derive(move || submit_error.with(|em| em.get("other").cloned()));
+
+    let on_submit = move |ev: SubmitEvent| {
+        let data = CreateUpdateBag::from_event(&ev).map(|it| it.bag.validate());
+        set_submit_error(HashMap::new());
+
+        match data {
+            Ok(Some(RoadieAppError::MultipleErrors(e))) => {
+                set_submit_error(e);
+                ev.prevent_default();
+            }
+            Ok(Some(e)) => {
+                set_submit_error.update(|em| {
+                    em.insert("other".to_string(), e.to_string());
+                });
+                ev.prevent_default();
+            }
+            Err(e) => {
+                set_submit_error.update(|em| {
+                    em.insert("other".to_string(), e.to_string());
+                });
+                ev.prevent_default();
+            }
+            _ => (),
+        };
+    };
+
+    view! {
+        <div class="min-h-screen bg-base-200 flex items-center">
+            <div class="card mx-auto w-full max-w-5xl  shadow-xl">
+                <div class="bg-base-100 rounded-xl">
+                    <div class="py-24 px-10 w-full">
+                        <ActionForm action=action on:submit=on_submit>
+                            <h2 class="text-2xl font-semibold mb-2 text-center">{submit_text}</h2>
+                            <InputText
+                                field_label="Id"
+                                container_style_base="invisible"
+                                input_type="hidden"
+                                field_name="bag[id]"
+                                field_value=id
+                            />
+                            <InputText
+                                field_label="Name"
+                                field_value=name
+                                placeholder="Bag Name"
+                                field_name="bag[name]"
+                            />
+                            <Alert alert_type="Error".into() msg=name_error/>
+
+                            <TextArea
+                                field_label="Bag Description"
+                                field_value=desc
+                                placeholder="Bag Description"
+                                field_name="bag[description]"
+                            />
+
+                            <InputText
+                                input_type="number"
+                                field_label="Draw weight for small items"
+                                field_value=small_weight
+                                field_name="bag[small_weight]"
+                            />
+                            <InputText
+                                input_type="number"
+                                field_label="Draw weight for medium items"
+                                field_value=medium_weight
+                                field_name="bag[medium_weight]"
+                            />
+                            <InputText
+                                input_type="number"
+                                field_label="Draw weight for large items"
+                                field_value=large_weight
+                                field_name="bag[large_weight]"
+                            />
+                            <Alert alert_type="Error".into() msg=weights_error/>
+                            <InputText
+                                field_label="Rounds a draw lasts, like 1d6 or 2d4+1"
+                                field_value=default_rounds
+                                placeholder="1d6"
+                                field_name="bag[default_rounds]"
+                            />
+                            <Alert alert_type="Error".into() msg=default_rounds_error/>
+                            <InputText
+                                input_type="number"
+                                field_label="Seconds a player has to undo a draw (0 turns undo off)"
+                                field_value=undo_window_secs
+                                field_name="bag[undo_window_secs]"
+                            />
+                            <button type="submit" class="btn mt-2 w-full btn-primary">
+                                {submit_text}
+                            </button>
+                            <Alert alert_type="Error".into() msg=other_error/>
+                        </ActionForm>
+                    </div>
+                </div>
+            </div>
+        </div>
+    }
+}
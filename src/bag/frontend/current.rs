@@ -1,16 +1,20 @@
+use super::use_bag_id;
 use crate::bag::api::*;
-use crate::bag::model::TakenBagItem;
+use crate::bag::model::{parse_tags, DrawTags, TakenBagItem};
 
+use chrono::Utc;
 use leptos::*;
+use crate::common::components::Alert;
 use crate::errors::{RoadieResult, NestedResult};
 
 #[component]
 pub fn CurrentItem() -> impl IntoView {
+    let bag_id = use_bag_id();
     let (tbi, set_tbi) = create_signal(Ok(None));
     let taken_item = create_resource(
-        || (),
-        move |_| async move {
-            let to_set = NestedResult::from(last_taken().await);
+        move || bag_id(),
+        move |bag_id| async move {
+            let to_set = NestedResult::from(last_taken(bag_id).await);
 
             /*let to_set = match last_taken().await {
                 Err(e) => Err(RoadieAppError::ServerError(e)),
@@ -22,20 +26,90 @@ pub fn CurrentItem() -> impl IntoView {
         },
     );
 
+    let others = create_resource(
+        move || (bag_id(), tbi.with(|t| t.as_ref().ok().flatten().map(|t| t.id))),
+        |(bag_id, _)| async move {
+            NestedResult::from(others_taken(bag_id).await).unwrap_or_default()
+        },
+    );
+
+    let (with_tags, set_with_tags) = create_signal(String::new());
+    let (without_tags, set_without_tags) = create_signal(String::new());
+    let (draw_error, set_draw_error) = create_signal(None);
     let take_item = create_action(move |()| async move {
-        let _item = take_random().await.expect("server error");
-        taken_item.refetch();
+        let tags = DrawTags {
+            with_tags: parse_tags(&with_tags.get_untracked()),
+            without_tags: parse_tags(&without_tags.get_untracked()),
+        };
+        let filtered = !tags.with_tags.is_empty() || !tags.without_tags.is_empty();
+        match NestedResult::from(take_random(bag_id.get_untracked(), Some(tags)).await) {
+            Ok(Some(_)) => {
+                set_draw_error(None);
+                taken_item.refetch();
+            }
+            Ok(None) if filtered => set_draw_error(Some("Nothing left in the bag matches those tags".to_string())),
+            Ok(None) => set_draw_error(Some("The bag is empty".to_string())),
+            Err(e) => set_draw_error(Some(e.to_string())),
+        }
     });
 
     let done_with_item = create_action(move |()| async move {
-        if let Ok(Some(mut current_item)) = tbi() {
-            current_item.done = true;
-            let to_set = NestedResult::from(update_taken(current_item).await);
+        if let Ok(Some(current_item)) = tbi() {
+            let to_set =
+                NestedResult::from(complete_draw(bag_id.get_untracked(), current_item.id).await);
             let to_set = to_set.map(|_v| None);
             set_tbi(to_set);
         }
     });
 
+    let next_round = create_action(move |()| async move {
+        if let Ok(Some(current_item)) = tbi() {
+            let to_set =
+                NestedResult::from(advance_round(bag_id.get_untracked(), current_item.id).await);
+            // Advancing past the last round finishes the draw
+            let to_set = to_set.map(|t| Some(t).filter(|t| !t.done));
+            set_tbi(to_set);
+        }
+    });
+
+    let give_up = create_action(move |()| async move {
+        if let Ok(Some(current_item)) = tbi() {
+            let to_set =
+                NestedResult::from(abandon_draw(bag_id.get_untracked(), current_item.id).await);
+            let to_set = to_set.map(|_v| None);
+            set_tbi(to_set);
+        }
+    });
+
+    let bag = create_resource(
+        move || bag_id(),
+        |bag_id| async move { NestedResult::from(get_bag(bag_id).await).ok() },
+    );
+    let (undo_error, set_undo_error) = create_signal(None);
+    let undo = create_action(move |()| async move {
+        if let Ok(Some(current_item)) = tbi() {
+            match NestedResult::from(undo_draw(bag_id.get_untracked(), current_item.id).await) {
+                Ok(()) => {
+                    set_undo_error(None);
+                    set_tbi(Ok(None));
+                }
+                Err(e) => set_undo_error(Some(e.to_string())),
+            }
+        }
+    });
+    // Checked when the item is shown; the server has the final say on late clicks
+    let can_undo = Signal::derive(move || {
+        let window = bag.get().flatten().map(|b| b.undo_window_secs).unwrap_or(0);
+        matches!(
+            tbi(),
+            Ok(Some(t)) if (Utc::now() - t.extraction_time).num_seconds() < window as i64
+        )
+    });
+
+    let rounds_left = Signal::derive(move || {
+        matches!(tbi(), Ok(Some(t)) if t.rounds_remaining > 0)
+    });
+
     let has_current_item = Signal::derive(move || {
         matches!(tbi(), Ok(Some(_)))
     });
@@ -49,6 +123,22 @@ pub fn CurrentItem() -> impl IntoView {
                             <Transition fallback=move || view! {}>
                                 <div class="relative flex flex-col items-center col-span-12">
                                     <Show when=move || !has_current_item()>
+                                        <div class="flex gap-2 mb-4">
+                                            <input
+                                                type="text"
+                                                class="input input-bordered input-sm"
+                                                placeholder="Only tags"
+                                                prop:value=with_tags
+                                                on:input=move |ev| set_with_tags(event_target_value(&ev))
+                                            />
+                                            <input
+                                                type="text"
+                                                class="input input-bordered input-sm"
+                                                placeholder="Skip tags"
+                                                prop:value=without_tags
+                                                on:input=move |ev| set_without_tags(event_target_value(&ev))
+                                            />
+                                        </div>
                                         <button
                                             class="btn btn-primary"
                                             on:click=move |_| take_item.dispatch(())
@@ -57,17 +147,46 @@ pub fn CurrentItem() -> impl IntoView {
                                         </button>
                                     </Show>
                                     <Show when=has_current_item>
-                                        <button
-                                            class="btn btn-primary"
-                                            on:click=move |_| done_with_item.dispatch(())
-                                        >
-                                            Done
-                                        </button>
+                                        <div class="join">
+                                            <Show when=rounds_left>
+                                                <button
+                                                    class="btn join-item"
+                                                    on:click=move |_| next_round.dispatch(())
+                                                >
+                                                    Next Round
+                                                </button>
+                                            </Show>
+                                            <button
+                                                class="btn btn-primary join-item"
+                                                on:click=move |_| done_with_item.dispatch(())
+                                            >
+                                                Done
+                                            </button>
+                                            <button
+                                                class="btn btn-ghost join-item"
+                                                on:click=move |_| give_up.dispatch(())
+                                            >
+                                                Give Up
+                                            </button>
+                                            <Show when=can_undo>
+                                                <button
+                                                    class="btn btn-warning join-item"
+                                                    on:click=move |_| undo.dispatch(())
+                                                >
+                                                    Undo
+                                                </button>
+                                            </Show>
+                                        </div>
                                     </Show>
+                                    <Alert alert_type="Error".into() msg=draw_error.into_signal()/>
+                                    <Alert alert_type="Error".into() msg=undo_error.into_signal()/>
                                 </div>
                                 <Show when=has_current_item>
                                     <ItemDisplay item=tbi/>
                                 </Show>
+                                <div class="relative flex flex-col col-span-12">
+                                    <OthersTaken items=Signal::derive(move || others.get().unwrap_or_default())/>
+                                </div>
                             </Transition>
                         </div>
                     </div>
@@ -84,11 +203,17 @@ pub fn ItemDisplay(#[prop(into)] item: Signal<RoadieResult<Option<TakenBagItem>>
         <div class="relative flex flex-col items-center justify-between col-span-6 px-8 py-12 space-y-4 overflow-hidden sm:rounded-xl">
             <div class="p-2 bg-neutral rounded-box text-neutral-content">
                 <span class="font-mono text-5xl">
-                    {move || item.get().map(|tbi| tbi.unwrap().rounds)}
+                    {move || item.get().map(|tbi| tbi.unwrap().rounds_remaining)}
                 </span>
             </div>
             <div>
-                <span class="text-sm">"rounds"</span>
+                <span class="text-sm">
+                    {move || {
+                        item.get()
+                            .map(|tbi| format!("of {} rounds left", tbi.unwrap().rounds))
+                    }}
+
+                </span>
             </div>
         </div>
         <div class="relative flex flex-col items-center justify-between col-span-6 px-8 py-12 space-y-4 overflow-hidden sm:rounded-xl">
@@ -96,9 +221,80 @@ pub fn ItemDisplay(#[prop(into)] item: Signal<RoadieResult<Option<TakenBagItem>>
             <h3>{move || item.get().map(|tbi| tbi.unwrap().item.size.to_string())}</h3>
 
         </div>
-        <div class="relative flex flex-col items-center justify-between col-span-6 px-8 py-12 space-y-4 overflow-hidden sm:rounded-xl"></div>
+        <div class="relative flex flex-col items-center justify-between col-span-6 px-8 py-12 space-y-4 overflow-hidden sm:rounded-xl">
+            <span class="text-sm">
+                {move || {
+                    item.get()
+                        .ok()
+                        .flatten()
+                        .map(|tbi| {
+                            let who = tbi
+                                .drawn_by
+                                .map(|u| u.username)
+                                .unwrap_or("someone".to_string());
+                            format!(
+                                "Drawn by {} at {}",
+                                who,
+                                tbi.extraction_time.format("%Y-%m-%d %H:%M"),
+                            )
+                        })
+                }}
+
+            </span>
+            <span class="text-sm">
+                {move || {
+                    item.get()
+                        .ok()
+                        .flatten()
+                        .and_then(|tbi| match (tbi.completed_by, tbi.completed_at) {
+                            (Some(u), Some(at)) => {
+                                Some(
+                                    format!(
+                                        "Marked done by {} at {}",
+                                        u.username,
+                                        at.format("%Y-%m-%d %H:%M"),
+                                    ),
+                                )
+                            }
+                            _ => None,
+                        })
+                }}
+
+            </span>
+        </div>
         <div class="relative flex flex-col items-center justify-between col-span-6 px-8 py-12 space-y-4 overflow-hidden sm:rounded-xl">
             <h4>{move || item.get().map(|tbi| tbi.unwrap().item.description)}</h4>
         </div>
     }
 }
+
+/// What everyone else at the table is working on
+#[component]
+pub fn OthersTaken(#[prop(into)] items: Signal<Vec<TakenBagItem>>) -> impl IntoView {
+    view! {
+        <Show when=move || !items.with(|i| i.is_empty())>
+            <h2 class="text-xl font-semibold mb-2">"Other players"</h2>
+            <ul class="menu bg-base-200 rounded-box">
+                <For
+                    each=items
+                    key=|tbi| tbi.id
+                    children=move |tbi| {
+                        let who = tbi
+                            .drawn_by
+                            .map(|u| u.username)
+                            .unwrap_or("Someone".to_string());
+                        view! {
+                            <li>
+                                <span>
+                                    <span class="font-semibold">{who}</span>
+                                    {format!(": {} ({} rounds)", tbi.item.name, tbi.rounds)}
+                                </span>
+                            </li>
+                        }
+                    }
+                />
+
+            </ul>
+        </Show>
+    }
+}
@@ -0,0 +1,272 @@
+use leptos::*;
+use leptos_router::*;
+use leptos_struct_table::*;
+use serde::{Deserialize, Serialize};
+use serde_qs as qs;
+use std::cmp::min;
+
+use super::list::RoadiebagClassesPreset;
+use super::use_bag_id;
+use crate::bag::api::*;
+use crate::bag::model::*;
+
+#[derive(TableComponent, Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[table(classes_provider = "RoadiebagClassesPreset")]
+pub struct HistoryItem {
+    #[table(key, skip)]
+    pub id: i64,
+    pub item: String,
+    pub drawn_at: String,
+    pub drawn_by: String,
+    pub rounds: u32,
+    pub done: bool,
+    pub completed_by: String,
+}
+
+impl From<TakenBagItem> for HistoryItem {
+    fn from(value: TakenBagItem) -> Self {
+        HistoryItem {
+            id: value.id,
+            item: value.item.name,
+            drawn_at: value.extraction_time.format("%Y-%m-%d %H:%M").to_string(),
+            drawn_by: value.drawn_by.map(|u| u.username).unwrap_or_default(),
+            rounds: value.rounds,
+            done: value.done,
+            completed_by: value.completed_by.map(|u| u.username).unwrap_or_default(),
+        }
+    }
+}
+
+#[component]
+pub fn HistoryPagination(
+    query: Memo<TakenItemFilter>,
+    page: Resource<(i64, TakenItemFilter), Option<TakenBagItemPage>>,
+) -> impl IntoView {
+    let page_link = move |page_num: u64| {
+        let new_qs =
+            qs::to_string(&query().with_page(page_num)).expect("Couldn't serialize query string");
+        format!("?{}", new_qs)
+    };
+
+    let total_pages = Signal::derive(move || {
+        page()
+            .flatten()
+            .map(|p| p.total_pages.max(1))
+            .unwrap_or(1)
+    });
+
+    let pages = Signal::derive(move || {
+        let page_num = page().flatten().map(|p| p.page_num).unwrap_or(1);
+        let page_min = page_num.saturating_sub(2).max(1);
+        let page_max = min(page_num + 2, total_pages());
+
+        (page_min..=page_max)
+            .map(|i| {
+                let class = if page_num == i {
+                    "join-item btn btn-disabled"
+                } else {
+                    "join-item btn"
+                };
+                (i, page_link(i), class)
+            })
+            .collect::<Vec<_>>()
+    });
+
+    view! {
+        <div class="pt-4 w-full flex justify-center">
+            <div class="join">
+                <a class="join-item btn" href=move || page_link(1)>
+                    "«"
+                </a>
+                <For
+                    each=pages
+                    key=|link| link.0
+                    children=move |(id, href, class)| {
+                        view! {
+                            <a class=class href=href>
+                                {id}
+                            </a>
+                        }
+                    }
+                />
+
+                <a class="join-item btn" href=move || page_link(total_pages())>
+                    "»"
+                </a>
+            </div>
+        </div>
+    }
+}
+
+#[component]
+pub fn RestockHistory(#[prop(into)] restocks: Signal<Vec<Restock>>) -> impl IntoView {
+    view! {
+        <div class="overflow-x-auto p-4">
+            <h3 class="text-lg font-semibold mb-2">"Restocks"</h3>
+            <table class="table table-zebra">
+                <thead>
+                    <tr>
+                        <th>"Item"</th>
+                        <th>"Restocked at"</th>
+                        <th>"Restocked by"</th>
+                        <th>"Quantity"</th>
+                    </tr>
+                </thead>
+                <tbody>
+                    <For
+                        each=restocks
+                        key=|restock| restock.id
+                        children=move |restock| {
+                            view! {
+                                <tr>
+                                    <td>{restock.item.name}</td>
+                                    <td>{restock.restocked_at.format("%Y-%m-%d %H:%M").to_string()}</td>
+                                    <td>
+                                        {restock.restocked_by.map(|u| u.username).unwrap_or_default()}
+                                    </td>
+                                    <td>
+                                        {format!(
+                                            "{} → {}",
+                                            restock.quantity_before,
+                                            restock.quantity_after,
+                                        )}
+                                    </td>
+                                </tr>
+                            }
+                        }
+                    />
+
+                </tbody>
+            </table>
+        </div>
+    }
+}
+
+#[component]
+pub fn History() -> impl IntoView {
+    let bag_id = use_bag_id();
+    let location = use_location();
+    let query = create_memo(move |_| {
+        location.search.with(|m| {
+            let qs_config = qs::Config::new(0, false);
+            qs_config
+                .deserialize_str::<TakenItemFilter>(m)
+                .unwrap_or(TakenItemFilter {
+                    page_num: Some(1),
+                    page_size: Some(50),
+                    ..Default::default()
+                })
+        })
+    });
+
+    let page = create_resource(
+        move || (bag_id(), query()),
+        |(bag_id, filter)| async move {
+            match list_taken_items(bag_id, Some(filter)).await {
+                Ok(Ok(page)) => Some(page),
+                _ => None,
+            }
+        },
+    );
+
+    let restocks = create_resource(
+        move || bag_id(),
+        |bag_id| async move {
+            match list_restocks(bag_id).await {
+                Ok(Ok(restocks)) => restocks,
+                _ => vec![],
+            }
+        },
+    );
+
+    // Everything in the bag, for the item filter
+    let items = create_resource(
+        move || bag_id(),
+        |bag_id| async move {
+            let filter = BagItemFilter {
+                page_size: Some(1000),
+                ..Default::default()
+            };
+            match list_bag_items(bag_id, Some(filter)).await {
+                Ok(Ok(page)) => page.items,
+                _ => vec![],
+            }
+        },
+    );
+    let item_options = Signal::derive(move || {
+        items
+            .get()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|bi| (bi.id, bi.name))
+            .collect::<Vec<(i64, String)>>()
+    });
+
+    let history_items = create_rw_signal(Vec::<HistoryItem>::new());
+    create_effect(move |_| {
+        if let Some(Some(pg)) = page() {
+            history_items.set(pg.items.into_iter().map(|tbi| tbi.into()).collect());
+        }
+    });
+
+    let from = move || query().from.map(|d| d.to_string()).unwrap_or_default();
+    let to = move || query().to.map(|d| d.to_string()).unwrap_or_default();
+
+    view! {
+        <div class="mt-0 mr-8 mb-0 ml-0 w-full h-full flex flex-col bg-base-100 shadow-xl">
+            <div class="h-full w-full pb-6 bg-base-100">
+                <Form
+                    method="GET"
+                    action=move || format!("/bag/{}/history", bag_id())
+                    class="flex flex-wrap gap-2 items-end p-4"
+                >
+                    <select name="item_id" class="select select-bordered select-sm">
+                        <option value="">"Any item"</option>
+                        <For
+                            each=item_options
+                            key=|option| option.0
+                            children=move |(id, name)| {
+                                view! {
+                                    <option
+                                        value=id.to_string()
+                                        selected=move || query().item_id == Some(id)
+                                    >
+                                        {name}
+                                    </option>
+                                }
+                            }
+                        />
+
+                    </select>
+                    <input
+                        type="date"
+                        name="from"
+                        class="input input-bordered input-sm"
+                        prop:value=from
+                    />
+                    <input type="date" name="to" class="input input-bordered input-sm" prop:value=to/>
+                    <select name="done" class="select select-bordered select-sm">
+                        <option value="" selected=move || query().done.is_none()>
+                            "Done or not"
+                        </option>
+                        <option value="true" selected=move || query().done == Some(true)>
+                            "Done"
+                        </option>
+                        <option value="false" selected=move || query().done == Some(false)>
+                            "Not done"
+                        </option>
+                    </select>
+                    <input type="hidden" name="page_num" value="1"/>
+                    <button type="submit" class="btn btn-sm btn-primary">
+                        "Filter"
+                    </button>
+                </Form>
+                <div class="overflow-x-auto">
+                    <HistoryItemTable items=history_items/>
+                </div>
+                <HistoryPagination query=query page=page/>
+                <RestockHistory restocks=Signal::derive(move || restocks.get().unwrap_or_default())/>
+            </div>
+        </div>
+    }
+}
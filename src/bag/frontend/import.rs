@@ -0,0 +1,180 @@
+use cfg_if::cfg_if;
+use crate::common::components::input::*;
+use crate::common::components::Alert;
+use leptos::*;
+use leptos_router::*;
+use strum::IntoEnumIterator;
+
+use super::use_bag_id;
+use crate::bag::api::*;
+use crate::bag::import::*;
+use crate::errors::{NestedResult, RoadieAppError};
+
+#[component]
+pub fn ImportItems() -> impl IntoView {
+    let bag_id = use_bag_id();
+    let action = create_server_action::<ImportBagItems>();
+    let (contents, set_contents) = create_signal(String::new());
+    let (format, set_format) = create_signal(ImportFormat::Csv);
+    let (file_error, set_file_error) = create_signal(None::<String>);
+
+    // Reads the chosen file into the text box, so it can be checked over before it's sent.
+    // Files only ever get picked in the browser.
+    let on_file = move |ev: ev::Event| {
+        cfg_if! {
+            if #[cfg(feature = "hydrate")] {
+                use wasm_bindgen_futures::JsFuture;
+
+                let input = event_target::<web_sys::HtmlInputElement>(&ev);
+                if let Some(file) = input.files().and_then(|files| files.get(0)) {
+                    if file.name().to_lowercase().ends_with(".json") {
+                        set_format(ImportFormat::Json);
+                    } else {
+                        set_format(ImportFormat::Csv);
+                    }
+                    spawn_local(async move {
+                        match JsFuture::from(file.text()).await {
+                            Ok(text) => {
+                                set_file_error(None);
+                                set_contents(text.as_string().unwrap_or_default());
+                            }
+                            Err(e) => set_file_error(Some(format!("Couldn't read {}: {:?}", file.name(), e))),
+                        }
+                    });
+                }
+            } else {
+                let _ = (ev, set_format, set_contents, set_file_error);
+            }
+        }
+    };
+
+    let result = Signal::derive(move || action.value().get().map(NestedResult::from));
+    let report = Signal::derive(move || result().and_then(|r| r.ok()));
+    let summary = Signal::derive(move || {
+        report().map(|r| {
+            let verb = if r.dry_run { "Would import" } else { "Imported" };
+            let mut summary = format!("{} {} items", verb, r.imported.len());
+            if !r.skipped.is_empty() {
+                summary.push_str(&format!(
+                    ", skipped {} already in the bag: {}",
+                    r.skipped.len(),
+                    r.skipped.join(", ")
+                ));
+            }
+            summary
+        })
+    });
+    // Problems with the whole file come first, then each row in order
+    let row_errors = Signal::derive(move || match result() {
+        Some(Err(RoadieAppError::MultipleErrors(errors))) => {
+            let mut errors: Vec<(String, String)> = errors.into_iter().collect();
+            errors.sort_by_key(|(key, _)| {
+                key.strip_prefix("row ")
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .unwrap_or(0)
+            });
+            errors
+        }
+        _ => vec![],
+    });
+    let other_error = Signal::derive(move || match result() {
+        Some(Err(RoadieAppError::MultipleErrors(_))) | Some(Ok(_)) | None => None,
+        Some(Err(e)) => Some(e.to_string()),
+    });
+
+    view! {
+        <div class="min-h-screen bg-base-200 flex items-center">
+            <div class="card mx-auto w-full max-w-5xl  shadow-xl">
+                <div class="bg-base-100 rounded-xl">
+                    <div class="py-24 px-10 w-full">
+                        <ActionForm action=action>
+                            <h2 class="text-2xl font-semibold mb-2 text-center">"Import Items"</h2>
+                            <p class="mb-4">
+                                "Upload a CSV file with a header row, or a JSON array of objects. "
+                                "Each item needs a name, quantity and size (small, medium or large), "
+                                "and can also have a description, infinite, weight, rounds, par_level and tags."
+                            </p>
+                            <input type="hidden" name="bag_id" prop:value=move || bag_id().to_string()/>
+                            <div class="form-control w-full mt-4">
+                                <label class="label">
+                                    <span class="label-text text-base-content">"File"</span>
+                                </label>
+                                <input
+                                    type="file"
+                                    accept=".csv,.json,text/csv,application/json"
+                                    class="file-input file-input-bordered w-full"
+                                    on:change=on_file
+                                />
+                            </div>
+                            <Alert alert_type="Error".into() msg=file_error.into_signal()/>
+                            <div class="form-control w-full mt-4">
+                                <label class="label">
+                                    <span class="label-text text-base-content">"Format"</span>
+                                </label>
+                                <select name="format" class="select select-bordered w-full max-w-xs">
+                                    {ImportFormat::iter()
+                                        .map(|f| {
+                                            view! {
+                                                <option value=f.key() prop:selected=move || format() == f>
+                                                    {f.to_string()}
+                                                </option>
+                                            }
+                                        })
+                                        .collect_view()}
+                                </select>
+                            </div>
+                            <TextArea
+                                field_label="Contents"
+                                field_value=contents
+                                placeholder="name,description,quantity,size,infinite"
+                                field_name="contents"
+                                field_style="h-64 font-mono"
+                            />
+                            <label class="label cursor-pointer justify-start gap-2 mt-4">
+                                <input type="checkbox" name="skip_duplicates" value="true" class="checkbox"/>
+                                <span class="label-text">"Skip items with the same name as one already in the bag"</span>
+                            </label>
+                            <label class="label cursor-pointer justify-start gap-2">
+                                <input type="checkbox" name="dry_run" value="true" class="checkbox"/>
+                                <span class="label-text">"Dry run, only check the file"</span>
+                            </label>
+                            <button type="submit" class="btn mt-2 w-full btn-primary">
+                                "Import"
+                            </button>
+                        </ActionForm>
+                        <Alert alert_type="Success".into() msg=summary/>
+                        <Alert alert_type="Error".into() msg=other_error/>
+                        <Show when=move || !row_errors().is_empty() fallback=|| ()>
+                            <table class="table mt-4">
+                                <thead>
+                                    <tr>
+                                        <th>"Row"</th>
+                                        <th>"Problem"</th>
+                                    </tr>
+                                </thead>
+                                <tbody>
+                                    <For
+                                        each=row_errors
+                                        key=|(row, _)| row.clone()
+                                        children=move |(row, error)| {
+                                            view! {
+                                                <tr>
+                                                    <td>{row}</td>
+                                                    <td>{error}</td>
+                                                </tr>
+                                            }
+                                        }
+                                    />
+
+                                </tbody>
+                            </table>
+                        </Show>
+                        <A href=move || format!("/bag/{}/items", bag_id()) class="btn btn-ghost mt-4">
+                            "Back to items"
+                        </A>
+                    </div>
+                </div>
+            </div>
+        </div>
+    }
+}
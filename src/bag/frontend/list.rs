@@ -5,8 +5,11 @@ use leptos_struct_table::*;
 use serde::{Deserialize, Serialize};
 use serde_qs as qs;
 use std::cmp::min;
+use strum::IntoEnumIterator;
 
+use super::use_bag_id;
 use crate::bag::api::*;
+use crate::bag::export::*;
 use crate::bag::model::*;
 
 #[derive(Clone, Copy)]
@@ -81,9 +84,11 @@ where
     K: Clone + 'static,
 {
     let delete_action = create_server_action::<DeleteBagItem>();
+    let restock_action = create_server_action::<RestockItems>();
     let (enable_delete, set_enable_delete) = create_signal(false);
 
     let id_signal = Signal::derive(move || row_value().id);
+    let bag_id_signal = Signal::derive(move || row_value().bag_id);
 
     view! {
         <tr
@@ -96,9 +101,24 @@ where
         >
 
             {children()}
+            <td>
+                <ActionForm action=restock_action>
+                    <input type="hidden" name="bag_id" prop:value=bag_id_signal/>
+                    <input type="hidden" name="item_id" prop:value=id_signal/>
+                    <button
+                        type="submit"
+                        class="btn btn-xs"
+                        disabled=move || row_value().par_level.is_empty()
+                    >
+                        "Restock"
+                    </button>
+                </ActionForm>
+            </td>
             <td>
                 <div class="inline-flex item-baseline self-center">
-                    <A href=move || format!("/items/edit/{}", row_value().id)>
+                    <A href=move || {
+                        format!("/bag/{}/items/edit/{}", row_value().bag_id, row_value().id)
+                    }>
                         <svg
                             xmlns="http://www.w3.org/2000/svg"
                             fill="none"
@@ -144,7 +164,8 @@ where
 
                     <Show when=enable_delete>
                         <ActionForm action=delete_action>
-                            <input type="hidden" prop:value=id_signal/>
+                            <input type="hidden" name="bag_id" prop:value=bag_id_signal/>
+                            <input type="hidden" name="id" prop:value=id_signal/>
                             <button type="submit" class="btn btn-xs btn-error mx-1">
                                 "Delete"
                             </button>
@@ -161,15 +182,21 @@ where
 #[table(
     sortable,
     classes_provider = "RoadiebagClassesPreset",
+    head_cell_renderer = "ItemSortHeader",
     row_renderer = "EditDeleteRowValueRenderer"
 )]
 pub struct ListItem {
     #[table(key, skip)]
     pub id: i64,
+    #[table(skip)]
+    pub bag_id: i64,
     pub name: String,
     pub description: String,
     pub quantity: i32,
+    pub par_level: String,
     pub size: String,
+    #[table(skip_sort)]
+    pub tags: String,
     pub infinite: bool,
     pub added_by: String,
 }
@@ -178,101 +205,219 @@ impl From<BagItem> for ListItem {
     fn from(value: BagItem) -> Self {
         ListItem {
             id: value.id,
+            bag_id: value.bag_id,
             added_by: value.added_by.username,
             name: value.name,
             description: value.description,
             quantity: value.quantity,
+            par_level: value.par_level.map(|p| p.to_string()).unwrap_or_default(),
             size: value.size.to_string(),
+            tags: value.tags.join(", "),
             infinite: value.infinite,
         }
     }
 }
 
-#[component]
-pub fn ItemListPagination(
-    current_page: Resource<BagItemFilter, Option<BagItemPage>>,
-) -> impl IntoView {
-    let page_max = Signal::derive(move || {
-        let page = use_context::<Resource<BagItemFilter, Option<BagItemPage>>>()
-            .expect("Unable to fetch page");
+/// The server side sort key behind a column of the item table
+fn item_sort_column(column: ListItemColumnName) -> Option<ItemSortColumn> {
+    match column {
+        ListItemColumnName::Name => Some(ItemSortColumn::Name),
+        ListItemColumnName::Description => Some(ItemSortColumn::Description),
+        ListItemColumnName::Quantity => Some(ItemSortColumn::Quantity),
+        ListItemColumnName::ParLevel => Some(ItemSortColumn::ParLevel),
+        ListItemColumnName::Size => Some(ItemSortColumn::Size),
+        ListItemColumnName::Infinite => Some(ItemSortColumn::Infinite),
+        ListItemColumnName::AddedBy => Some(ItemSortColumn::AddedBy),
+        _ => None,
+    }
+}
 
-        let page_num = if let Some(Some(pn)) = page() {
-            pn.page_num
-        } else {
-            1
+/// Header cell for the item list. Clicks go through the query string instead of sorting the
+/// rows in place, so the server sorts every page and the order survives reloads and shared
+/// links. The sort shown comes from the query for the same reason.
+#[allow(unused_variables)]
+#[component]
+pub fn ItemSortHeader<F>(
+    /// The class attribute for the head element. Generated by the classes provider.
+    #[prop(into)]
+    class: Signal<String>,
+    /// The class attribute for the inner element. Generated by the classes provider.
+    #[prop(into)]
+    inner_class: String,
+    /// The index of the column. Starts at 0 for the first column.
+    index: usize,
+    /// The column enum variant. It is auto generated from the struct.
+    column: ListItemColumnName,
+    /// The sort priority the table keeps itself. Unused, see `query`.
+    #[prop(into)]
+    sort_priority: Signal<Option<usize>>,
+    /// The sort direction the table keeps itself. Unused, see `query`.
+    #[prop(into)]
+    sort_direction: Signal<ColumnSort>,
+    /// The table's own click handler, which would only sort the rows already loaded
+    on_click: F,
+    children: Children,
+) -> impl IntoView
+where
+    F: Fn(TableHeadEvent<ListItemColumnName>) + 'static,
+{
+    let bag_id = use_bag_id();
+    let query = use_context::<Memo<BagItemFilter>>().expect("Unable to fetch query");
+    let navigate = use_navigate();
+    let sort_column = item_sort_column(column);
+    let class_provider = RoadiebagClassesPreset::new();
+
+    let direction = move || match sort_column.and_then(|c| query().sort_direction(c)) {
+        Some(SortDirection::Asc) => ColumnSort::Ascending,
+        Some(SortDirection::Desc) => ColumnSort::Descending,
+        None => ColumnSort::None,
+    };
+    let style = move || {
+        let sort = match direction() {
+            ColumnSort::Ascending => "--sort-icon: '▲';",
+            ColumnSort::Descending => "--sort-icon: '▼';",
+            ColumnSort::None => "--sort-icon: '';",
         };
+        let sort_keys = query().sort.unwrap_or_default();
+        let priority = sort_keys
+            .iter()
+            .position(|s| Some(s.column) == sort_column)
+            .filter(|_| sort_keys.len() > 1)
+            .map(|p| format!("--sort-priority: '{}';", p + 1))
+            .unwrap_or_else(|| "--sort-priority: '';".to_string());
+        format!("{} {}", sort, priority)
+    };
+    let on_header_click = move |_| {
+        if let Some(sort_column) = sort_column {
+            let new_qs = qs::to_string(&query().with_sort_toggled(sort_column))
+                .expect("Couldn't serialize query string");
+            navigate(&format!("/bag/{}/items?{}", bag_id(), new_qs), Default::default());
+        }
+    };
 
-        let total_pages = if let Some(Some(page)) = current_page() {
-            page.total_pages
-        } else {
-            1
-        };
+    view! {
+        <th
+            class=move || class_provider.head_cell(direction(), "")
+            on:click=on_header_click
+            style=style
+        >
+            <span class=inner_class>{children()}</span>
+        </th>
+    }
+}
 
-        min(page_num + 2, total_pages)
-    });
+/// Page links for the item list. Numbered pages in offset mode, previous and next in cursor
+/// mode. Every link keeps the rest of the current query.
+#[component]
+pub fn ItemListPagination(
+    current_page: Resource<(i64, BagItemFilter), Option<BagItemPage>>,
+) -> impl IntoView {
+    let bag_id = use_bag_id();
+    let query = use_context::<Memo<BagItemFilter>>().expect("Unable to fetch query");
+    let href = move |filter: BagItemFilter| {
+        format!(
+            "/bag/{}/items?{}",
+            bag_id(),
+            qs::to_string(&filter).expect("Couldn't serialize query string")
+        )
+    };
+
+    let page = Signal::derive(move || current_page.get().flatten());
+    let page_num = move || page().map(|p| p.page_num).unwrap_or(1).max(1);
+    let total_pages = move || page().map(|p| p.total_pages).unwrap_or(1).max(1);
 
     let pages = Signal::derive(move || {
-        let query = use_context::<Memo<BagItemFilter>>().expect("Unable to fetch query");
-        let page = use_context::<Resource<BagItemFilter, Option<BagItemPage>>>()
-            .expect("Unable to fetch page");
+        let page_num = page_num();
+        let page_min = page_num.saturating_sub(2).max(1);
+        let page_max = min(page_num + 2, total_pages());
+        (page_min..=page_max)
+            .map(|i| {
+                let class = if page_num == i {
+                    "join-item btn btn-disabled"
+                } else {
+                    "join-item btn"
+                };
+                (i, href(query().with_page(i)), class)
+            })
+            .collect::<Vec<_>>()
+    });
 
-        let page_num = if let Some(Some(pn)) = page() {
-            pn.page_num
+    let prev_href = move || {
+        page()
+            .and_then(|p| p.prev_cursor)
+            .map(|c| href(query().with_cursors(None, Some(c))))
+    };
+    let next_href = move || {
+        page()
+            .and_then(|p| p.next_cursor)
+            .map(|c| href(query().with_cursors(Some(c), None)))
+    };
+    let cursor_class = |enabled: bool| {
+        if enabled {
+            "join-item btn"
         } else {
-            1
-        };
-
-        let page_min = page_num.checked_sub(2).unwrap_or(page_num);
+            "join-item btn btn-disabled"
+        }
+    };
 
-        let total_pages = if let Some(Some(page)) = current_page() {
-            page.total_pages
+    let is_cursor = move || query().paging() == PagingMode::Cursor;
+    let switch_mode = move || {
+        let paging = if is_cursor() {
+            PagingMode::Offset
         } else {
-            1
+            PagingMode::Cursor
         };
-
-        let page_max = min(page_num + 2, total_pages);
-
-        let mut pages = vec![];
-        for i in page_min..=page_max {
-            let new_qs =
-                qs::to_string(&query().with_page(i)).expect("Couldn't serialize query string");
-            let class = if page_num == i {
-                "join-item btn btn-disable"
-            } else {
-                "join-item btn"
-            };
-            pages.push((i, format!("/list?{}", new_qs), class));
-        }
-        pages
-    });
+        href(BagItemFilter {
+            paging: Some(paging),
+            ..query().with_page(1)
+        })
+    };
 
     view! {
-        <div class="pt-4 w-full flex justify-center">
-            <div class="join">
-
-                <a class="join-item btn" exact=true href="/list?page_num=1">
-                    "«"
-                </a>
-                <For
-                    each=pages
-                    key=|link| link.0
-                    children=move |(id, query, class)| {
-                        view! {
-                            <a class=class href=query>
-                                {id}
+        <div class="pt-4 w-full flex justify-center items-center gap-4">
+            <Show
+                when=is_cursor
+                fallback=move || {
+                    view! {
+                        <div class="join">
+                            <a class="join-item btn" href=move || href(query().with_page(1))>
+                                "«"
                             </a>
-                        }
+                            <For
+                                each=pages
+                                key=|link| link.0
+                                children=move |(id, query, class)| {
+                                    view! {
+                                        <a class=class href=query>
+                                            {id}
+                                        </a>
+                                    }
+                                }
+                            />
+
+                            <a class="join-item btn" href=move || href(query().with_page(total_pages()))>
+                                "»"
+                            </a>
+                        </div>
                     }
-                />
-
-                <a
-                    class="join-item btn"
-                    exact=true
-                    href=move || { format!("/list?page_num={}", page_max()) }
-                >
-                    "»"
-                </a>
-            </div>
+                }
+            >
+
+                <div class="join">
+                    <a class="join-item btn" href=move || href(query().with_page(1))>
+                        "«"
+                    </a>
+                    <a class=move || cursor_class(prev_href().is_some()) href=prev_href>
+                        "‹ Previous"
+                    </a>
+                    <a class=move || cursor_class(next_href().is_some()) href=next_href>
+                        "Next ›"
+                    </a>
+                </div>
+            </Show>
+            <a class="btn btn-xs btn-ghost" href=switch_mode>
+                {move || if is_cursor() { "Numbered pages" } else { "Continuous pages" }}
+            </a>
         </div>
     }
 }
@@ -280,6 +425,7 @@ pub fn ItemListPagination(
 #[component]
 pub fn ItemList() -> impl IntoView {
     logging::log!("Item list time");
+    let bag_id = use_bag_id();
     let location = use_location();
     let query = create_memo(move |_| {
         location.search.with(|m| {
@@ -297,14 +443,54 @@ pub fn ItemList() -> impl IntoView {
     });
     provide_context(query);
     //let query = use_query::<BagItemFilter>();
-    let page = create_resource(query, |filter| async move {
-        match list_bag_items(Some(filter)).await {
+    let page = create_resource(move || (bag_id(), query()), |(bag_id, filter)| async move {
+        match list_bag_items(bag_id, Some(filter)).await {
             Ok(Ok(page)) => Some(page),
             _ => None,
         }
     });
     provide_context(page);
 
+    let restock = create_action(move |filter: &Option<BagItemFilter>| {
+        let filter = filter.clone();
+        async move {
+            let _restocked = restock_items(bag_id.get_untracked(), None, filter).await;
+            page.refetch();
+        }
+    });
+
+    let tags = create_resource(
+        move || bag_id(),
+        |bag_id| async move { list_tags(bag_id).await.ok().and_then(|r| r.ok()).unwrap_or_default() },
+    );
+    // Clicking a tag adds it to the filter, clicking it again takes it back out
+    let tag_href = move |tag: &str| {
+        let mut filter = query().with_page(1);
+        let mut selected = filter.tags.take().unwrap_or_default();
+        if selected.iter().any(|t| t == tag) {
+            selected.retain(|t| t != tag);
+        } else {
+            selected.push(tag.to_string());
+        }
+        filter.tags = Some(selected).filter(|t| !t.is_empty());
+        format!(
+            "/bag/{}/items?{}",
+            bag_id(),
+            qs::to_string(&filter).expect("Couldn't serialize query string")
+        )
+    };
+
+    let navigate = use_navigate();
+    let search = move |ev| {
+        let q = event_target_value(&ev);
+        let filter = BagItemFilter {
+            q: Some(q).filter(|q| !q.trim().is_empty()),
+            ..query().with_page(1)
+        };
+        let new_qs = qs::to_string(&filter).expect("Couldn't serialize query string");
+        navigate(&format!("/bag/{}/items?{}", bag_id(), new_qs), Default::default());
+    };
+
     let page_items = create_rw_signal(Vec::<ListItem>::new());
 
     create_effect(move |_| {
@@ -320,6 +506,92 @@ pub fn ItemList() -> impl IntoView {
     view! {
         <div class="mt-0 mr-8 mb-0 ml-0 w-full h-full flex flex-col bg-base-100 shadow-xl">
             <div class="h-full w-full pb-6 bg-base-100">
+                <div class="flex justify-end gap-2 p-4">
+                    <input
+                        type="search"
+                        class="input input-bordered input-sm mr-auto"
+                        placeholder="Search items"
+                        prop:value=move || query().q.unwrap_or_default()
+                        on:change=search
+                    />
+                    <div class="dropdown dropdown-end">
+                        <label tabindex="0" class="btn btn-sm btn-ghost">
+                            "Export"
+                        </label>
+                        <ul tabindex="0" class="dropdown-content z-[1] menu p-2 shadow bg-base-100 rounded-box w-56">
+                            {ExportFormat::iter()
+                                .map(|format| {
+                                    view! {
+                                        <li>
+                                            <a
+                                                rel="external"
+                                                download
+                                                href=move || items_export_href(bag_id(), format, &query())
+                                            >
+                                                {format!("These items as {}", format)}
+                                            </a>
+                                        </li>
+                                    }
+                                })
+                                .collect_view()}
+                            {ExportFormat::iter()
+                                .map(|format| {
+                                    view! {
+                                        <li>
+                                            <a
+                                                rel="external"
+                                                download
+                                                href=move || {
+                                                    history_export_href(bag_id(), format, &TakenItemFilter::default())
+                                                }
+                                            >
+                                                {format!("Draw history as {}", format)}
+                                            </a>
+                                        </li>
+                                    }
+                                })
+                                .collect_view()}
+                        </ul>
+                    </div>
+                    <A href=move || format!("/bag/{}/items/import", bag_id()) class="btn btn-sm btn-ghost">
+                        "Import"
+                    </A>
+                    <A href=move || format!("/bag/{}/items/trash", bag_id()) class="btn btn-sm btn-ghost">
+                        "Trash"
+                    </A>
+                    <button class="btn btn-sm" on:click=move |_| restock.dispatch(Some(query()))>
+                        "Restock these"
+                    </button>
+                    <button class="btn btn-sm btn-primary" on:click=move |_| restock.dispatch(None)>
+                        "Restock bag"
+                    </button>
+                </div>
+                <div class="flex flex-wrap gap-2 px-4">
+                    <For
+                        each=move || tags.get().unwrap_or_default()
+                        key=|tag| tag.clone()
+                        children=move |tag| {
+                            let selected = {
+                                let tag = tag.clone();
+                                move || query().tags.unwrap_or_default().contains(&tag)
+                            };
+                            let href = {
+                                let tag = tag.clone();
+                                move || tag_href(&tag)
+                            };
+                            view! {
+                                <A
+                                    href=href
+                                    class=move || {
+                                        if selected() { "badge badge-primary" } else { "badge badge-outline" }
+                                    }
+                                >
+                                    {tag}
+                                </A>
+                            }
+                        }
+                    />
+                </div>
                 <div class="overflow-x-auto">
                     <ListItemTable items=page_items/>
                 </div>
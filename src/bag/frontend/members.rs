@@ -0,0 +1,124 @@
+use crate::common::components::input::*;
+use crate::common::components::Alert;
+use leptos::*;
+use leptos_router::*;
+use strum::IntoEnumIterator;
+
+use super::use_bag_id;
+use crate::auth::Role;
+use crate::bag::api::*;
+use crate::errors::NestedResult;
+
+#[component]
+pub fn BagMembers() -> impl IntoView {
+    let bag_id = use_bag_id();
+    let grant = create_server_action::<GrantRole>();
+    let revoke = create_server_action::<RevokeRole>();
+    let (action_error, set_action_error) = create_signal(None);
+
+    let members = create_resource(
+        move || (bag_id(), grant.version().get(), revoke.version().get()),
+        |(bag_id, _, _)| async move { NestedResult::from(list_bag_members(bag_id).await) },
+    );
+
+    create_effect(move |_| {
+        let grant_result = grant.value().get().map(NestedResult::from);
+        let revoke_result = revoke.value().get().map(NestedResult::from);
+        match (grant_result, revoke_result) {
+            (Some(Err(e)), _) | (_, Some(Err(e))) => set_action_error(Some(e.to_string())),
+            _ => set_action_error(None),
+        }
+    });
+
+    let member_list = Signal::derive(move || {
+        members
+            .get()
+            .and_then(|r| r.ok())
+            .unwrap_or_default()
+    });
+    let load_error = Signal::derive(move || {
+        members
+            .get()
+            .and_then(|r| r.err())
+            .map(|e| e.to_string())
+    });
+    let role_options = Role::iter()
+        .map(|r| (r.to_string(), r.to_string()))
+        .collect::<Vec<(String, String)>>();
+
+    view! {
+        <div class="min-h-screen bg-base-200 flex items-center">
+            <div class="card mx-auto w-full max-w-5xl  shadow-xl">
+                <div class="bg-base-100 rounded-xl">
+                    <div class="py-24 px-10 w-full">
+                        <h2 class="text-2xl font-semibold mb-2 text-center">"Bag members"</h2>
+                        <Alert alert_type="Error".into() msg=load_error/>
+                        <table class="table">
+                            <thead>
+                                <tr>
+                                    <th>"User"</th>
+                                    <th>"Role"</th>
+                                    <th></th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                <For
+                                    each=member_list
+                                    key=|member| (member.user.id, member.role)
+                                    children=move |member| {
+                                        view! {
+                                            <tr>
+                                                <td>{member.user.username}</td>
+                                                <td>{member.role.to_string()}</td>
+                                                <td>
+                                                    <ActionForm action=revoke>
+                                                        <input
+                                                            type="hidden"
+                                                            name="bag_id"
+                                                            value=move || bag_id().to_string()
+                                                        />
+                                                        <input
+                                                            type="hidden"
+                                                            name="user_id"
+                                                            value=member.user.id.to_string()
+                                                        />
+                                                        <button type="submit" class="btn btn-xs btn-error">
+                                                            "Remove"
+                                                        </button>
+                                                    </ActionForm>
+                                                </td>
+                                            </tr>
+                                        }
+                                    }
+                                />
+
+                            </tbody>
+                        </table>
+                        <ActionForm action=grant>
+                            <input
+                                type="hidden"
+                                name="bag_id"
+                                value=move || bag_id().to_string()
+                            />
+                            <InputText
+                                field_label="Username"
+                                field_name="username"
+                                placeholder="Who should get access?"
+                            />
+                            <SelectBox
+                                field_label="Role"
+                                field_name="role"
+                                field_value=Signal::derive(|| Role::Player.to_string())
+                                options=role_options
+                            />
+                            <button type="submit" class="btn mt-2 w-full btn-primary">
+                                "Grant Role"
+                            </button>
+                        </ActionForm>
+                        <Alert alert_type="Error".into() msg=action_error.into_signal()/>
+                    </div>
+                </div>
+            </div>
+        </div>
+    }
+}
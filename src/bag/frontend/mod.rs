@@ -1,16 +1,85 @@
 mod addedit;
+mod bags;
 mod current;
+mod history;
+mod import;
 mod list;
+mod members;
+mod trash;
 
 use crate::auth::frontend::AuthContext;
+use crate::bag::api::list_bags;
+use crate::bag::model::Bag;
+use crate::errors::{NestedResult, RoadieResult};
 use leptos::*;
 use leptos_router::*;
 
+pub use bags::BagPicker;
+
+/// The bag the user is currently looking at, shared with components that live outside of
+/// the bag routes (like the header)
+#[derive(Clone, Copy)]
+pub struct BagContext {
+    pub bag_id: RwSignal<Option<i64>>,
+    pub bags: Resource<String, RoadieResult<Vec<Bag>>>,
+}
+
+impl BagContext {
+    /// Builds a link relative to the currently selected bag
+    pub fn href(&self, path: &str) -> String {
+        match self.bag_id.get() {
+            Some(id) => format!("/bag/{}{}", id, path),
+            None => "/".to_string(),
+        }
+    }
+}
+
+pub fn provide_bag_context() {
+    let location = use_location();
+    let bags = create_resource(
+        move || location.pathname.get(),
+        |_| async move { NestedResult::from(list_bags().await) },
+    );
+    provide_context(BagContext {
+        bag_id: create_rw_signal(None),
+        bags,
+    });
+}
+
+#[derive(Params, Default, PartialOrd, PartialEq, Debug, Copy, Clone)]
+pub struct BagParams {
+    bag_id: Option<i64>,
+}
+
+/// The `:bag_id` of the current route, or -1 if it's missing
+pub fn use_bag_id() -> Signal<i64> {
+    let params = use_params::<BagParams>();
+    Signal::derive(move || {
+        params
+            .get()
+            .ok()
+            .and_then(|p| p.bag_id)
+            .unwrap_or(-1)
+    })
+}
+
 #[component]
 pub fn BagRoutesOutlet() -> impl IntoView {
     view! { <Outlet/> }
 }
 
+#[component]
+pub fn BagOutlet() -> impl IntoView {
+    let bag_context = use_context::<BagContext>().expect("Failed to get BagContext");
+    let bag_id = use_bag_id();
+    create_effect(move |_| {
+        bag_context.bag_id.set(Some(bag_id()).filter(|id| *id != -1));
+    });
+    on_cleanup(move || bag_context.bag_id.set(None));
+
+    view! { <Outlet/> }
+}
+
 #[component(transparent)]
 pub fn BagRoutes() -> impl IntoView {
 
@@ -27,28 +96,77 @@ pub fn BagRoutes() -> impl IntoView {
         <ProtectedRoute path="/" view=BagRoutesOutlet condition=is_authed redirect_path="/auth">
             <ProtectedRoute
                 path=""
-                view=current::CurrentItem
+                view=bags::BagList
                 condition=is_authed
                 redirect_path="/auth"
             />
             <ProtectedRoute
-                path="items"
-                view=list::ItemList
+                path="bags/add"
+                view=bags::AddEditBag
                 condition=is_authed
                 redirect_path="/auth"
             />
             <ProtectedRoute
-                path="items/add"
-                view=addedit::AddEditItem
+                path="bags/edit/:bag_id"
+                view=bags::AddEditBag
                 condition=is_authed
                 redirect_path="/auth"
             />
             <ProtectedRoute
-                path="items/edit/:id"
-                view=addedit::AddEditItem
+                path="bag/:bag_id"
+                view=BagOutlet
                 condition=is_authed
                 redirect_path="/auth"
-            />
+            >
+                <ProtectedRoute
+                    path=""
+                    view=current::CurrentItem
+                    condition=is_authed
+                    redirect_path="/auth"
+                />
+                <ProtectedRoute
+                    path="items"
+                    view=list::ItemList
+                    condition=is_authed
+                    redirect_path="/auth"
+                />
+                <ProtectedRoute
+                    path="items/add"
+                    view=addedit::AddEditItem
+                    condition=is_authed
+                    redirect_path="/auth"
+                />
+                <ProtectedRoute
+                    path="items/import"
+                    view=import::ImportItems
+                    condition=is_authed
+                    redirect_path="/auth"
+                />
+                <ProtectedRoute
+                    path="items/trash"
+                    view=trash::Trash
+                    condition=is_authed
+                    redirect_path="/auth"
+                />
+                <ProtectedRoute
+                    path="items/edit/:id"
+                    view=addedit::AddEditItem
+                    condition=is_authed
+                    redirect_path="/auth"
+                />
+                <ProtectedRoute
+                    path="history"
+                    view=history::History
+                    condition=is_authed
+                    redirect_path="/auth"
+                />
+                <ProtectedRoute
+                    path="members"
+                    view=members::BagMembers
+                    condition=is_authed
+                    redirect_path="/auth"
+                />
+            </ProtectedRoute>
         </ProtectedRoute>
     }
 }
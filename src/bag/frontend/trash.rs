@@ -0,0 +1,114 @@
+use crate::common::components::Alert;
+use leptos::*;
+use leptos_router::*;
+
+use super::use_bag_id;
+use crate::bag::api::*;
+use crate::errors::NestedResult;
+
+#[component]
+pub fn Trash() -> impl IntoView {
+    let bag_id = use_bag_id();
+    let restore = create_server_action::<RestoreBagItem>();
+    let purge = create_server_action::<PurgeBagItem>();
+    let (action_error, set_action_error) = create_signal(None);
+
+    let trash = create_resource(
+        move || (bag_id(), restore.version().get(), purge.version().get()),
+        |(bag_id, _, _)| async move { NestedResult::from(list_trash(bag_id).await) },
+    );
+
+    create_effect(move |_| {
+        let restore_result = restore.value().get().map(NestedResult::from);
+        let purge_result = purge.value().get().map(NestedResult::from);
+        match (restore_result, purge_result) {
+            (Some(Err(e)), _) | (_, Some(Err(e))) => set_action_error(Some(e.to_string())),
+            _ => set_action_error(None),
+        }
+    });
+
+    let items = Signal::derive(move || trash.get().and_then(|r| r.ok()).unwrap_or_default());
+    let load_error = Signal::derive(move || {
+        trash
+            .get()
+            .and_then(|r| r.err())
+            .map(|e| e.to_string())
+    });
+
+    view! {
+        <div class="min-h-screen bg-base-200 flex items-center">
+            <div class="card mx-auto w-full max-w-5xl  shadow-xl">
+                <div class="bg-base-100 rounded-xl">
+                    <div class="py-24 px-10 w-full">
+                        <h2 class="text-2xl font-semibold mb-2 text-center">"Trash"</h2>
+                        <Alert alert_type="Error".into() msg=load_error/>
+                        <table class="table">
+                            <thead>
+                                <tr>
+                                    <th>"Item"</th>
+                                    <th>"Deleted at"</th>
+                                    <th></th>
+                                    <th></th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                <For
+                                    each=items
+                                    key=|item| item.id
+                                    children=move |item| {
+                                        let deleted_at = item
+                                            .deleted_at
+                                            .map(|at| at.format("%Y-%m-%d %H:%M").to_string())
+                                            .unwrap_or_default();
+                                        view! {
+                                            <tr>
+                                                <td>{item.name}</td>
+                                                <td>{deleted_at}</td>
+                                                <td>
+                                                    <ActionForm action=restore>
+                                                        <input
+                                                            type="hidden"
+                                                            name="bag_id"
+                                                            value=move || bag_id().to_string()
+                                                        />
+                                                        <input
+                                                            type="hidden"
+                                                            name="id"
+                                                            value=item.id.to_string()
+                                                        />
+                                                        <button type="submit" class="btn btn-xs">
+                                                            "Restore"
+                                                        </button>
+                                                    </ActionForm>
+                                                </td>
+                                                <td>
+                                                    <ActionForm action=purge>
+                                                        <input
+                                                            type="hidden"
+                                                            name="bag_id"
+                                                            value=move || bag_id().to_string()
+                                                        />
+                                                        <input
+                                                            type="hidden"
+                                                            name="id"
+                                                            value=item.id.to_string()
+                                                        />
+                                                        <button type="submit" class="btn btn-xs btn-error">
+                                                            "Delete forever"
+                                                        </button>
+                                                    </ActionForm>
+                                                </td>
+                                            </tr>
+                                        }
+                                    }
+                                />
+
+                            </tbody>
+                        </table>
+                        <Alert alert_type="Error".into() msg=action_error.into_signal()/>
+                    </div>
+                </div>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,225 @@
+use cfg_if::cfg_if;
+use serde::{Deserialize, Serialize};
+use strum::*;
+
+use super::api::BagItemForm;
+use super::model::ItemSize;
+
+/// File formats items can be imported from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Display, EnumIter)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    #[default]
+    #[strum(serialize = "CSV")]
+    Csv,
+    #[strum(serialize = "JSON")]
+    Json,
+}
+
+impl ImportFormat {
+    /// How the format is written in forms and query strings
+    pub fn key(&self) -> &'static str {
+        match self {
+            ImportFormat::Csv => "csv",
+            ImportFormat::Json => "json",
+        }
+    }
+}
+
+/// What an import did, or would do on a dry run. Items are listed by name.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub imported: Vec<String>,
+    /// Rows left out because the bag, or an earlier row, already has an item with that name
+    pub skipped: Vec<String>,
+    pub dry_run: bool,
+}
+
+/// One row of an import file. Only `name`, `quantity` and `size` are required, everything else
+/// falls back to what a new item gets in the form.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportRow {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub quantity: i32,
+    /// Small, medium or large in any case
+    #[serde(default)]
+    pub size: String,
+    #[serde(default)]
+    pub infinite: Option<bool>,
+    #[serde(default)]
+    pub weight: Option<u32>,
+    #[serde(default)]
+    pub rounds: Option<String>,
+    #[serde(default)]
+    pub par_level: Option<i32>,
+    #[serde(default)]
+    pub tags: String,
+}
+
+impl From<ImportRow> for BagItemForm {
+    fn from(row: ImportRow) -> Self {
+        let size = ItemSize::iter()
+            .filter(|s| s != &ItemSize::Unknown)
+            .find(|s| s.to_string().eq_ignore_ascii_case(row.size.trim()));
+        BagItemForm {
+            name: row.name.trim().to_string(),
+            description: row.description,
+            quantity: row.quantity,
+            size,
+            infinite: row.infinite,
+            weight: row.weight,
+            rounds: row.rounds,
+            par_level: row.par_level,
+            tags: row.tags,
+            ..Default::default()
+        }
+    }
+}
+
+cfg_if! {
+    if #[cfg(feature = "ssr")] {
+        use chrono::Utc;
+        use sqlx::SqlitePool;
+        use std::collections::HashMap;
+
+        use super::model::BagItem;
+        use crate::auth::User;
+        use crate::errors::RoadieAppError;
+
+        /// Reads every row out of `contents`. Rows that don't have the right shape come back as
+        /// an error message so they can be reported along with the ones that fail validation.
+        /// Only a file that can't be read at all is an error.
+        pub fn parse_rows(format: ImportFormat, contents: &str) -> Result<Vec<Result<BagItemForm, String>>, String> {
+            match format {
+                ImportFormat::Csv => {
+                    let mut reader = csv::ReaderBuilder::new()
+                        .trim(csv::Trim::All)
+                        .from_reader(contents.as_bytes());
+                    let headers = reader
+                        .headers()
+                        .map_err(|e| e.to_string())?
+                        .iter()
+                        .map(|h| h.to_lowercase())
+                        .collect::<csv::StringRecord>();
+                    reader.set_headers(headers);
+                    Ok(reader
+                        .deserialize::<ImportRow>()
+                        .map(|row| row.map(BagItemForm::from).map_err(|e| e.to_string()))
+                        .collect())
+                }
+                ImportFormat::Json => {
+                    let rows = serde_json::from_str::<Vec<serde_json::Value>>(contents)
+                        .map_err(|e| e.to_string())?;
+                    Ok(rows
+                        .into_iter()
+                        .map(|row| {
+                            serde_json::from_value::<ImportRow>(row)
+                                .map(BagItemForm::from)
+                                .map_err(|e| e.to_string())
+                        })
+                        .collect())
+                }
+            }
+        }
+
+        /// Checks every row of an import and, unless it's a dry run, adds them all to the bag
+        /// as `added_by`. When anything's wrong nothing is added and the problems come back keyed
+        /// by `file` or `row N`.
+        pub async fn import_items(
+            bag_id: i64,
+            format: ImportFormat,
+            contents: &str,
+            dry_run: bool,
+            skip_duplicates: bool,
+            added_by: User,
+            pool: &SqlitePool,
+        ) -> Result<Result<ImportReport, HashMap<String, String>>, sqlx::Error> {
+            let rows = match parse_rows(format, contents) {
+                Ok(rows) if rows.is_empty() => Err(RoadieAppError::ImportEmpty),
+                Ok(rows) => Ok(rows),
+                Err(e) => Err(RoadieAppError::ImportUnreadable(e)),
+            };
+            let rows = match rows {
+                Ok(rows) => rows,
+                Err(e) => return Ok(Err(HashMap::from([("file".to_string(), e.to_string())]))),
+            };
+
+            let mut error_map = HashMap::new();
+            let mut forms = Vec::new();
+            for (i, row) in rows.into_iter().enumerate() {
+                let key = format!("row {}", i + 1);
+                match row.map(|form| (form.validate_import(), form)) {
+                    Err(e) => {
+                        error_map.insert(key, e);
+                    }
+                    Ok((Some(RoadieAppError::MultipleErrors(errors)), _)) => {
+                        let mut errors: Vec<_> = errors.into_iter().collect();
+                        errors.sort();
+                        let message = errors
+                            .into_iter()
+                            .map(|(field, e)| format!("{}: {}", field, e))
+                            .collect::<Vec<_>>()
+                            .join("; ");
+                        error_map.insert(key, message);
+                    }
+                    Ok((Some(e), _)) => {
+                        error_map.insert(key, e.to_string());
+                    }
+                    Ok((None, form)) => forms.push(form),
+                }
+            }
+            if !error_map.is_empty() {
+                return Ok(Err(error_map));
+            }
+
+            let mut seen: Vec<String> = if skip_duplicates {
+                BagItem::names(bag_id, pool)
+                    .await?
+                    .into_iter()
+                    .map(|name| name.trim().to_lowercase())
+                    .collect()
+            } else {
+                vec![]
+            };
+            let mut report = ImportReport {
+                dry_run,
+                ..Default::default()
+            };
+            let mut items = Vec::new();
+            let created_at = Utc::now();
+            for form in forms {
+                let name = form.name.trim().to_lowercase();
+                if skip_duplicates && seen.contains(&name) {
+                    report.skipped.push(form.name);
+                    continue;
+                }
+                seen.push(name);
+                report.imported.push(form.name.clone());
+                items.push(BagItem {
+                    id: -1,
+                    bag_id,
+                    name: form.name.clone(),
+                    added_by: added_by.clone(),
+                    description: form.description.clone(),
+                    infinite: form.infinite.unwrap_or_default(),
+                    quantity: form.quantity,
+                    size: form.size.unwrap(),
+                    weight: form.weight,
+                    rounds: form.parsed_rounds().unwrap_or_default(),
+                    par_level: form.par_level,
+                    tags: form.parsed_tags(),
+                    created_at,
+                    deleted_at: None,
+                });
+            }
+
+            if !dry_run {
+                BagItem::insert_many(items, pool).await?;
+                tracing::info!("Imported {} items into bag {}", report.imported.len(), bag_id);
+            }
+            Ok(Ok(report))
+        }
+    }
+}
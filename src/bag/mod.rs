@@ -1,4 +1,8 @@
 pub mod api;
+pub mod dice;
+pub mod draw;
+pub mod export;
 pub mod frontend;
+pub mod import;
 pub mod model;
-mod tests;
+pub(crate) mod tests;
@@ -1,9 +1,10 @@
 use cfg_if::cfg_if;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use strum::*;
 
-use crate::auth::User;
+use super::dice::DiceExpr;
+use crate::auth::{Role, User};
 use strum::Display;
 
 #[derive(
@@ -55,39 +56,234 @@ impl From<ItemSize> for u8 {
     }
 }
 
+/// How likely an item of each size is to be drawn when it doesn't have its own weight
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SizeWeights {
+    pub small: u32,
+    pub medium: u32,
+    pub large: u32,
+}
+
+impl Default for SizeWeights {
+    fn default() -> Self {
+        SizeWeights {
+            small: 1,
+            medium: 2,
+            large: 3,
+        }
+    }
+}
+
+impl SizeWeights {
+    pub fn weight_for(&self, size: ItemSize) -> u32 {
+        match size {
+            ItemSize::Small => self.small,
+            ItemSize::Medium | ItemSize::Unknown => self.medium,
+            ItemSize::Large => self.large,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bag {
+    pub id: i64,
+    pub created_by: User,
+    pub name: String,
+    pub description: String,
+    pub size_weights: SizeWeights,
+    /// How many rounds a draw lasts when the item doesn't say
+    pub default_rounds: DiceExpr,
+    /// How long after a draw the player who made it can still take it back
+    pub undo_window_secs: u32,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Someone who holds a role in a bag
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BagMember {
+    pub user: User,
+    pub role: Role,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct BagItem {
     pub(crate) id: i64,
+    pub(crate) bag_id: i64,
     pub(crate) added_by: User,
     pub(crate) name: String,
     pub(crate) description: String,
     pub(crate) quantity: i32,
     pub(crate) size: ItemSize,
     pub(crate) infinite: bool,
+    pub(crate) weight: Option<u32>,
+    pub(crate) rounds: Option<DiceExpr>,
+    /// What a restock fills a finite item back up to. Items without one are never restocked.
+    pub(crate) par_level: Option<i32>,
+    /// Lowercased and sorted, see [`parse_tags`]
+    pub(crate) tags: Vec<String>,
     pub(crate) created_at: DateTime<Utc>,
+    /// Set when the item is in the trash. Deleted items can't be drawn but stay in the history.
+    pub(crate) deleted_at: Option<DateTime<Utc>>,
+}
+
+impl BagItem {
+    /// The item's own weight, falling back to the bag's weight for its size
+    pub fn effective_weight(&self, size_weights: &SizeWeights) -> u32 {
+        self.weight
+            .unwrap_or_else(|| size_weights.weight_for(self.size))
+    }
+
+    /// The item's own round duration, falling back to the bag's default
+    pub fn effective_rounds(&self, default_rounds: &DiceExpr) -> DiceExpr {
+        self.rounds.unwrap_or(*default_rounds)
+    }
+}
+
+/// Splits a comma separated list of tags, trimming and lowercasing each one. Blank and
+/// repeated tags are dropped and the rest come back sorted.
+pub fn parse_tags(raw: &str) -> Vec<String> {
+    let mut tags = raw
+        .split(',')
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+/// Narrows a draw down to items that have every tag in `with_tags` and none of the tags in
+/// `without_tags`
+#[derive(Serialize, Default, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub struct DrawTags {
+    #[serde(default)]
+    pub with_tags: Vec<String>,
+    #[serde(default)]
+    pub without_tags: Vec<String>,
+}
+
+/// The item list columns that can be sorted on
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, EnumIter, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemSortColumn {
+    Name,
+    Description,
+    Quantity,
+    #[strum(to_string = "Par level")]
+    ParLevel,
+    Size,
+    Infinite,
+    #[strum(to_string = "Added by")]
+    AddedBy,
+    #[strum(to_string = "Added at")]
+    CreatedAt,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub struct ItemSort {
+    pub column: ItemSortColumn,
+    #[serde(default)]
+    pub direction: SortDirection,
+}
+
+/// How many sort keys a listing keeps. Clicking another column pushes the oldest one out.
+pub const MAX_SORT_KEYS: usize = 3;
+
+/// `Offset` pages are numbered and counted. `Cursor` pages continue from the `after` or
+/// `before` cursor of a page already seen, so items added in between don't shift what comes
+/// next. Cursor pages aren't counted and don't rank search results.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PagingMode {
+    #[default]
+    Offset,
+    Cursor,
 }
 
 #[derive(Serialize, Default, Deserialize, PartialEq, Eq, Clone, Debug)]
 pub struct BagItemFilter {
+    pub bag_id: Option<i64>,
     pub added_by: Option<Vec<i64>>,
     pub name: Option<String>,
     pub description: Option<String>,
     pub size: Option<Vec<u8>>,
     pub infinite: Option<bool>,
+    /// Words to look for in the name and description. Matches come back best first.
+    pub q: Option<String>,
+    /// Only items that have every one of these tags
+    pub tags: Option<Vec<String>>,
+    /// Sort keys, most significant first. Newest items come first when nothing else decides.
+    pub sort: Option<Vec<ItemSort>>,
+    pub paging: Option<PagingMode>,
+    /// In cursor mode, the page right after the one this cursor came from
+    pub after: Option<String>,
+    /// In cursor mode, the page right before the one this cursor came from
+    pub before: Option<String>,
     pub page_size: Option<u64>,
     pub page_num: Option<u64>,
 }
 
 impl BagItemFilter {
+    /// Goes to a numbered page, or back to the first page in cursor mode
     pub fn with_page(&self, page_num: u64) -> Self {
         BagItemFilter {
             page_num: Some(page_num),
-            added_by: self.added_by.clone(),
-            name: self.name.clone(),
-            description: self.description.clone(),
-            size: self.size.clone(),
-            infinite: self.infinite,
-            page_size: self.page_size,
+            after: None,
+            before: None,
+            ..self.clone()
+        }
+    }
+
+    pub fn with_cursors(&self, after: Option<String>, before: Option<String>) -> Self {
+        BagItemFilter {
+            after,
+            before,
+            ..self.clone()
+        }
+    }
+
+    pub fn paging(&self) -> PagingMode {
+        self.paging.unwrap_or_default()
+    }
+
+    /// How the listing is sorted on `column`, if at all
+    pub fn sort_direction(&self, column: ItemSortColumn) -> Option<SortDirection> {
+        self.sort
+            .iter()
+            .flatten()
+            .find(|s| s.column == column)
+            .map(|s| s.direction)
+    }
+
+    /// What clicking a column header does. A column that isn't the main sort key becomes it,
+    /// ascending. Clicking the main key again flips it to descending, and once more drops it.
+    pub fn with_sort_toggled(&self, column: ItemSortColumn) -> Self {
+        let mut sort = self.sort.clone().unwrap_or_default();
+        match sort.first().copied() {
+            Some(first) if first.column == column => {
+                if first.direction == SortDirection::Asc {
+                    sort[0].direction = SortDirection::Desc;
+                } else {
+                    sort.remove(0);
+                }
+            }
+            _ => {
+                sort.retain(|s| s.column != column);
+                sort.insert(0, ItemSort { column, direction: SortDirection::Asc });
+                sort.truncate(MAX_SORT_KEYS);
+            }
+        }
+        BagItemFilter {
+            sort: Some(sort).filter(|s| !s.is_empty()),
+            ..self.with_page(1)
         }
     }
 }
@@ -95,32 +291,332 @@ impl BagItemFilter {
 #[derive(Serialize, Default, Deserialize, PartialEq, Eq, Clone, Debug)]
 pub struct BagItemPage {
     pub items: Vec<BagItem>,
+    /// Page numbers and totals are left at 0 in cursor mode
     pub page_num: u64,
     pub total_pages: u64,
     pub page_size: u64,
     pub total_results: u64,
+    /// Cursor mode only. Set when there are more items after this page.
+    pub next_cursor: Option<String>,
+    /// Cursor mode only. Set when there are items before this page.
+    pub prev_cursor: Option<String>,
 }
 
 cfg_if! {
     if #[cfg(feature="ssr")] {
         use sqlx::prelude::*;
-        use sqlx::SqlitePool;
+        use sqlx::{Sqlite, SqliteConnection, SqlitePool};
         use futures::future::try_join_all;
 
         use sea_query_binder::SqlxBinder;
         #[cfg(feature="derive")]
         use sea_query::*;
-        use sea_query::{Query, Expr, IdenStatic,
-            Func, SqliteQueryBuilder, SelectStatement, Order, JoinType};
+        use sea_query::{Query, Expr, IdenStatic, Cond, OnConflict, SimpleExpr, Value,
+            Func, SqliteQueryBuilder, SelectStatement, UpdateStatement, Order};
         use sea_query::types::{Alias, Asterisk};
-        use crate::auth::model::UserTable;
-        use rand::Rng;
+        use crate::auth::model::{SQLUser, SQLUserPermission, UserTable};
+        use crate::bag::draw::{replay, DrawCandidate, DrawEngine, DrawOutcome};
+        use crate::db::is_busy;
+        use crate::errors::{RoadieAppError, RoadieResult};
+        use rand::RngCore;
+        use std::collections::HashMap;
+        use std::time::Duration;
+
+        #[derive(IdenStatic, EnumIter, Copy, Clone)]
+        #[iden="bags"]
+        pub enum BagsTable {
+            Table,
+            Id,
+            #[iden="created_by"]
+            CreatedBy,
+            Name,
+            Description,
+            #[iden="small_weight"]
+            SmallWeight,
+            #[iden="medium_weight"]
+            MediumWeight,
+            #[iden="large_weight"]
+            LargeWeight,
+            #[iden="default_rounds"]
+            DefaultRounds,
+            #[iden="undo_window_secs"]
+            UndoWindowSecs,
+            #[iden="created_at"]
+            CreatedAt
+        }
+
+        impl Bag {
+            /// Saves a new bag and makes its creator the owner
+            #[tracing::instrument(level = "info", skip_all, ret, err)]
+            pub async fn insert(self, pool: &SqlitePool) -> Result<Bag, sqlx::Error> {
+                let mut tx = pool.begin().await?;
+                let (insert_stmt, values) = Query::insert()
+                    .into_table(BagsTable::Table)
+                    .columns([
+                        BagsTable::CreatedBy,
+                        BagsTable::Name,
+                        BagsTable::Description,
+                        BagsTable::SmallWeight,
+                        BagsTable::MediumWeight,
+                        BagsTable::LargeWeight,
+                        BagsTable::DefaultRounds,
+                        BagsTable::UndoWindowSecs,
+                        BagsTable::CreatedAt
+                    ])
+                    .values_panic([
+                        self.created_by.id.into(),
+                        (&self.name).into(),
+                        (&self.description).into(),
+                        self.size_weights.small.into(),
+                        self.size_weights.medium.into(),
+                        self.size_weights.large.into(),
+                        self.default_rounds.to_string().into(),
+                        self.undo_window_secs.into(),
+                        self.created_at.into()
+                    ])
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+
+                let row_id = sqlx::query_with(&insert_stmt, values)
+                    .execute(&mut *tx)
+                    .await?
+                    .last_insert_rowid();
+                SQLUserPermission::grant(self.created_by.id, Role::Owner.token(row_id), &mut *tx).await?;
+                tx.commit().await?;
+
+                Ok(Bag {
+                    id: row_id,
+                    ..self
+                })
+            }
+
+            #[tracing::instrument(level = "info", skip_all, ret, err)]
+            pub async fn update(&self, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+                let (q, values) = Query::update()
+                    .table(BagsTable::Table)
+                    .values([
+                        (BagsTable::Name, (&self.name).into()),
+                        (BagsTable::Description, (&self.description).into()),
+                        (BagsTable::SmallWeight, self.size_weights.small.into()),
+                        (BagsTable::MediumWeight, self.size_weights.medium.into()),
+                        (BagsTable::LargeWeight, self.size_weights.large.into()),
+                        (BagsTable::DefaultRounds, self.default_rounds.to_string().into()),
+                        (BagsTable::UndoWindowSecs, self.undo_window_secs.into())
+                    ])
+                    .and_where(Expr::col(BagsTable::Id).eq(self.id))
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+
+                sqlx::query_with(&q, values)
+                    .execute(pool)
+                    .await?;
+                Ok(())
+            }
+
+            /// Deletes the bag along with every item and draw it owns
+            #[tracing::instrument(level = "info", skip_all, fields(error), ret, err)]
+            pub async fn delete(self, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+                let mut tx = pool.begin().await?;
+                Self::delete_with(self.id, &mut tx).await?;
+                tx.commit().await
+            }
+
+            pub(crate) async fn delete_with(bag_id: i64, conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+                let (q, values) = Query::delete()
+                    .from_table(RestocksTable::Table)
+                    .cond_where(Expr::col(RestocksTable::BagId).eq(bag_id))
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                sqlx::query_with(&q, values)
+                    .execute(&mut *conn)
+                    .await?;
+                let (q, values) = Query::delete()
+                    .from_table(TakenItemsTable::Table)
+                    .cond_where(Expr::col(TakenItemsTable::BagId).eq(bag_id))
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                sqlx::query_with(&q, values)
+                    .execute(&mut *conn)
+                    .await?;
+                let (q, values) = Query::delete()
+                    .from_table(ItemTagsTable::Table)
+                    .cond_where(
+                        Expr::col(ItemTagsTable::TagId).in_subquery(
+                            Query::select()
+                                .column(TagsTable::Id)
+                                .from(TagsTable::Table)
+                                .and_where(Expr::col(TagsTable::BagId).eq(bag_id))
+                                .to_owned()
+                        )
+                    )
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                sqlx::query_with(&q, values)
+                    .execute(&mut *conn)
+                    .await?;
+                let (q, values) = Query::delete()
+                    .from_table(TagsTable::Table)
+                    .cond_where(Expr::col(TagsTable::BagId).eq(bag_id))
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                sqlx::query_with(&q, values)
+                    .execute(&mut *conn)
+                    .await?;
+                let (q, values) = Query::delete()
+                    .from_table(BagItemsTable::Table)
+                    .cond_where(Expr::col(BagItemsTable::BagId).eq(bag_id))
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                sqlx::query_with(&q, values)
+                    .execute(&mut *conn)
+                    .await?;
+                let (q, values) = Query::delete()
+                    .from_table(BagsTable::Table)
+                    .cond_where(Expr::col(BagsTable::Id).eq(bag_id))
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                sqlx::query_with(&q, values)
+                    .execute(&mut *conn)
+                    .await?;
+                SQLUserPermission::revoke_prefix(None, Role::bag_prefix(bag_id), &mut *conn).await?;
+                Ok(())
+            }
+
+            async fn get_one<'e, E: Executor<'e, Database = Sqlite>>(mut query: SelectStatement, executor: E) -> Result<Option<Self>, sqlx::Error> {
+                let mut bag_vec = Self::get_many(query.limit(1).take(), executor).await?;
+                if bag_vec.len() >= 1 {
+                    Ok(Some(bag_vec.remove(0)))
+                } else {
+                    Ok(None)
+                }
+            }
+
+            async fn get_many<'e, E: Executor<'e, Database = Sqlite>>(mut query: SelectStatement, executor: E) -> Result<Vec<Self>, sqlx::Error> {
+                let (q, values) = query
+                    .from(BagsTable::Table)
+                    .column((BagsTable::Table, Asterisk))
+                    .expr_as(Expr::col((UserTable::Table, UserTable::Id)), Alias::new("user_id"))
+                    .column((UserTable::Table, UserTable::Username))
+                    .inner_join(
+                        UserTable::Table,
+                        Expr::col((BagsTable::Table, BagsTable::CreatedBy)).equals((UserTable::Table, UserTable::Id))
+                    )
+                    .order_by((BagsTable::Table, BagsTable::Name), Order::Asc)
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                let result = sqlx::query_with(&q, values)
+                    .fetch_all(executor)
+                    .await?;
+                Ok(result.iter().map(|row| {
+                    Bag {
+                        id: row.get(BagsTable::Id.as_str()),
+                        created_by: User {
+                            id: row.get("user_id"),
+                            username: row.get("username"),
+                            anonymous: false
+                        },
+                        name: row.get(BagsTable::Name.as_str()),
+                        description: row.get(BagsTable::Description.as_str()),
+                        size_weights: SizeWeights {
+                            small: row.get(BagsTable::SmallWeight.as_str()),
+                            medium: row.get(BagsTable::MediumWeight.as_str()),
+                            large: row.get(BagsTable::LargeWeight.as_str())
+                        },
+                        default_rounds: row.get::<String, _>(BagsTable::DefaultRounds.as_str())
+                            .parse()
+                            .unwrap_or_default(),
+                        undo_window_secs: row.get(BagsTable::UndoWindowSecs.as_str()),
+                        created_at: row.get::<DateTime<Utc>, _>(BagsTable::CreatedAt.as_str())
+                    }
+                }).collect())
+            }
+
+            #[tracing::instrument(level = "info", skip(executor), fields(error), ret, err)]
+            pub async fn by_id<'e, E: Executor<'e, Database = Sqlite>>(id: i64, executor: E) -> Result<Option<Bag>, sqlx::Error> {
+                Self::get_one(
+                    Query::select()
+                        .and_where(Expr::col((BagsTable::Table, BagsTable::Id)).eq(id))
+                        .to_owned(),
+                    executor
+                ).await
+            }
+
+            #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
+            pub async fn all(pool: &SqlitePool) -> Result<Vec<Bag>, sqlx::Error> {
+                Self::get_many(Query::select(), pool).await
+            }
+
+            /// Bags the user holds any role in
+            #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
+            pub async fn for_user(user_id: i64, pool: &SqlitePool) -> Result<Vec<Bag>, sqlx::Error> {
+                let bag_ids: Vec<i64> = SQLUserPermission::for_user(user_id, pool)
+                    .await?
+                    .iter()
+                    .filter_map(|p| Role::from_token(&p.token))
+                    .map(|(bag_id, _)| bag_id)
+                    .collect();
+                Self::get_many(
+                    Query::select()
+                        .and_where(Expr::col((BagsTable::Table, BagsTable::Id)).is_in(bag_ids))
+                        .to_owned(),
+                    pool
+                ).await
+            }
+
+            /// Everyone with a role in the bag, ordered by username
+            #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
+            pub async fn members(&self, pool: &SqlitePool) -> Result<Vec<BagMember>, sqlx::Error> {
+                let grants = SQLUserPermission::with_prefix(Role::bag_prefix(self.id), pool).await?;
+                let mut members = Vec::new();
+                for grant in grants {
+                    let role = match Role::from_token(&grant.token) {
+                        Some((_, role)) => role,
+                        None => continue
+                    };
+                    if let Some(user) = SQLUser::by_id(grant.user_id, pool).await? {
+                        members.push(BagMember {
+                            user: user.into(),
+                            role
+                        });
+                    }
+                }
+                members.sort_by(|a, b| a.user.username.cmp(&b.user.username));
+                Ok(members)
+            }
+
+            /// The user's role in the bag, if they have one
+            #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
+            pub async fn role_of(&self, user_id: i64, pool: &SqlitePool) -> Result<Option<Role>, sqlx::Error> {
+                Ok(self.members(pool)
+                    .await?
+                    .into_iter()
+                    .find(|m| m.user.id == user_id)
+                    .map(|m| m.role))
+            }
+
+            /// Gives the user `role` in the bag, replacing whatever role they had before
+            #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
+            pub async fn set_role(&self, user_id: i64, role: Role, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+                let mut tx = pool.begin().await?;
+                SQLUserPermission::revoke_prefix(Some(user_id), Role::bag_prefix(self.id), &mut *tx).await?;
+                SQLUserPermission::grant(user_id, role.token(self.id), &mut *tx).await?;
+                tx.commit().await
+            }
+
+            /// Takes away every role the user has in the bag
+            #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
+            pub async fn remove_member(&self, user_id: i64, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+                SQLUserPermission::revoke_prefix(Some(user_id), Role::bag_prefix(self.id), pool).await
+            }
+        }
 
         #[derive(IdenStatic, EnumIter, Copy, Clone)]
         #[iden="bagitems"]
         pub enum BagItemsTable {
             Table,
             Id,
+            #[iden="bag_id"]
+            BagId,
             #[iden="added_by"]
             AddedBy,
             Name,
@@ -128,40 +624,378 @@ cfg_if! {
             Quantity,
             Size,
             Infinite,
+            Weight,
+            Rounds,
+            #[iden="par_level"]
+            ParLevel,
             #[iden="created_at"]
-            CreatedAt
+            CreatedAt,
+            #[iden="deleted_at"]
+            DeletedAt
+        }
+
+        /// Full text index over item names and descriptions, kept up to date by triggers
+        #[derive(IdenStatic, EnumIter, Copy, Clone)]
+        #[iden="bagitems_fts"]
+        pub enum BagItemsFtsTable {
+            Table,
+            Rowid,
+            Rank
+        }
+
+        /// Turns what someone typed into an FTS5 query that matches items containing every word,
+        /// treating each word as a prefix. Returns `None` when there's nothing to search for.
+        fn fts_query(q: &str) -> Option<String> {
+            let words = q
+                .split_whitespace()
+                .filter(|w| w.chars().any(char::is_alphanumeric))
+                .map(|w| format!("\"{}\"*", w.replace('"', "\"\"")))
+                .collect::<Vec<_>>();
+            if words.is_empty() {
+                None
+            } else {
+                Some(words.join(" "))
+            }
+        }
+
+        /// Everything the item list can be ordered by
+        #[derive(Copy, Clone, Debug)]
+        enum SortKey {
+            Column(ItemSortColumn),
+            Rank,
+            Id
+        }
+
+        impl SortKey {
+            fn expr(&self) -> SimpleExpr {
+                let col = |c: BagItemsTable| Expr::col((BagItemsTable::Table, c)).into();
+                match self {
+                    SortKey::Column(ItemSortColumn::Name) => col(BagItemsTable::Name),
+                    SortKey::Column(ItemSortColumn::Description) => col(BagItemsTable::Description),
+                    SortKey::Column(ItemSortColumn::Quantity) => col(BagItemsTable::Quantity),
+                    // Items without a par level sort first, and cursors need a value to compare to
+                    SortKey::Column(ItemSortColumn::ParLevel) => Func::coalesce([
+                        Expr::col((BagItemsTable::Table, BagItemsTable::ParLevel)).into(),
+                        Expr::val(-1).into()
+                    ]).into(),
+                    SortKey::Column(ItemSortColumn::Size) => col(BagItemsTable::Size),
+                    SortKey::Column(ItemSortColumn::Infinite) => col(BagItemsTable::Infinite),
+                    SortKey::Column(ItemSortColumn::AddedBy) => Expr::col((UserTable::Table, UserTable::Username)).into(),
+                    SortKey::Column(ItemSortColumn::CreatedAt) => col(BagItemsTable::CreatedAt),
+                    SortKey::Rank => Expr::col((BagItemsFtsTable::Table, BagItemsFtsTable::Rank)).into(),
+                    SortKey::Id => col(BagItemsTable::Id)
+                }
+            }
+
+            fn cursor_value(&self, item: &BagItem) -> CursorValue {
+                match self {
+                    SortKey::Column(ItemSortColumn::Name) => CursorValue::Text(item.name.clone()),
+                    SortKey::Column(ItemSortColumn::Description) => CursorValue::Text(item.description.clone()),
+                    SortKey::Column(ItemSortColumn::Quantity) => CursorValue::Int(item.quantity.into()),
+                    SortKey::Column(ItemSortColumn::ParLevel) => CursorValue::Int(item.par_level.unwrap_or(-1).into()),
+                    SortKey::Column(ItemSortColumn::Size) => CursorValue::Int(Into::<u8>::into(item.size).into()),
+                    SortKey::Column(ItemSortColumn::Infinite) => CursorValue::Int(item.infinite.into()),
+                    SortKey::Column(ItemSortColumn::AddedBy) => CursorValue::Text(item.added_by.username.clone()),
+                    SortKey::Column(ItemSortColumn::CreatedAt) => CursorValue::Time(item.created_at),
+                    SortKey::Rank => unreachable!("Cursor pages aren't ranked"),
+                    SortKey::Id => CursorValue::Int(item.id)
+                }
+            }
+        }
+
+        #[derive(Serialize, Deserialize)]
+        enum CursorValue {
+            Int(i64),
+            Text(String),
+            Time(DateTime<Utc>)
+        }
+
+        impl From<CursorValue> for Value {
+            fn from(value: CursorValue) -> Self {
+                match value {
+                    CursorValue::Int(i) => i.into(),
+                    CursorValue::Text(t) => t.into(),
+                    CursorValue::Time(t) => t.into()
+                }
+            }
+        }
+
+        /// The sort values of the item at the edge of a page, hex encoded so they travel safely
+        /// in a query string
+        struct ItemCursor;
+
+        impl ItemCursor {
+            fn encode(keys: &[(SortKey, Order)], item: &BagItem) -> String {
+                let values = keys.iter().map(|(key, _)| key.cursor_value(item)).collect::<Vec<_>>();
+                serde_json::to_vec(&values)
+                    .expect("Cursor values always serialize")
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect()
+            }
+
+            /// `None` when the cursor is garbled or was made for a different sort order
+            fn decode(cursor: &str, keys: &[(SortKey, Order)]) -> Option<Vec<Value>> {
+                let bytes = (0..cursor.len())
+                    .step_by(2)
+                    .map(|i| cursor.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+                    .collect::<Option<Vec<u8>>>()?;
+                let values = serde_json::from_slice::<Vec<CursorValue>>(&bytes).ok()?;
+                if values.len() != keys.len() {
+                    return None;
+                }
+                Some(values.into_iter().map(Value::from).collect())
+            }
+
+            /// Items that come after the cursor's item in the sort order, or before it when going
+            /// `backwards`. That's any item that ties on the first few keys and then comes out
+            /// ahead on the next one.
+            fn condition(keys: &[(SortKey, Order)], values: &[Value], backwards: bool) -> Cond {
+                let mut any = Cond::any();
+                for i in 0..keys.len() {
+                    let mut all = Cond::all();
+                    for (j, (key, _)) in keys.iter().enumerate().take(i) {
+                        all = all.add(Expr::expr(key.expr()).eq(values[j].clone()));
+                    }
+                    let (key, order) = &keys[i];
+                    let ascending = matches!(order, Order::Asc) != backwards;
+                    all = all.add(if ascending {
+                        Expr::expr(key.expr()).gt(values[i].clone())
+                    } else {
+                        Expr::expr(key.expr()).lt(values[i].clone())
+                    });
+                    any = any.add(all);
+                }
+                any
+            }
+        }
+
+        #[derive(IdenStatic, EnumIter, Copy, Clone)]
+        #[iden="tags"]
+        pub enum TagsTable {
+            Table,
+            Id,
+            #[iden="bag_id"]
+            BagId,
+            Name
+        }
+
+        #[derive(IdenStatic, EnumIter, Copy, Clone)]
+        #[iden="item_tags"]
+        pub enum ItemTagsTable {
+            Table,
+            #[iden="item_id"]
+            ItemId,
+            #[iden="tag_id"]
+            TagId
+        }
+
+        /// Tags are kept per bag and shared by every item in it that carries them
+        pub struct Tag;
+
+        impl Tag {
+            /// Replaces the item's tags, creating any the bag doesn't have yet and dropping the
+            /// ones no item uses anymore
+            pub async fn set_for_item(item_id: i64, bag_id: i64, tags: &[String], conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+                let (q, v) = Query::delete()
+                    .from_table(ItemTagsTable::Table)
+                    .cond_where(Expr::col(ItemTagsTable::ItemId).eq(item_id))
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                sqlx::query_with(&q, v)
+                    .execute(&mut *conn)
+                    .await?;
+
+                for tag in tags {
+                    let (q, v) = Query::insert()
+                        .into_table(TagsTable::Table)
+                        .columns([TagsTable::BagId, TagsTable::Name])
+                        .values_panic([bag_id.into(), tag.into()])
+                        .on_conflict(OnConflict::columns([TagsTable::BagId, TagsTable::Name]).do_nothing().to_owned())
+                        .to_owned()
+                        .build_sqlx(SqliteQueryBuilder);
+                    sqlx::query_with(&q, v)
+                        .execute(&mut *conn)
+                        .await?;
+
+                    let (q, v) = Query::insert()
+                        .into_table(ItemTagsTable::Table)
+                        .columns([ItemTagsTable::ItemId, ItemTagsTable::TagId])
+                        .select_from(
+                            Query::select()
+                                .expr(Expr::val(item_id))
+                                .column(TagsTable::Id)
+                                .from(TagsTable::Table)
+                                .and_where(Expr::col(TagsTable::BagId).eq(bag_id))
+                                .and_where(Expr::col(TagsTable::Name).eq(tag))
+                                .to_owned()
+                        )
+                        .map_err(|e| sqlx::Error::Protocol(e.to_string()))?
+                        .to_owned()
+                        .build_sqlx(SqliteQueryBuilder);
+                    sqlx::query_with(&q, v)
+                        .execute(&mut *conn)
+                        .await?;
+                }
+
+                Self::remove_unused(bag_id, conn).await
+            }
+
+            /// Drops every tag the item had
+            pub async fn clear_for_item(item_id: i64, bag_id: i64, conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+                Self::set_for_item(item_id, bag_id, &[], conn).await
+            }
+
+            async fn remove_unused(bag_id: i64, conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+                let (q, v) = Query::delete()
+                    .from_table(TagsTable::Table)
+                    .cond_where(Expr::col(TagsTable::BagId).eq(bag_id))
+                    .cond_where(
+                        Expr::col(TagsTable::Id).not_in_subquery(
+                            Query::select()
+                                .column(ItemTagsTable::TagId)
+                                .from(ItemTagsTable::Table)
+                                .to_owned()
+                        )
+                    )
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                sqlx::query_with(&q, v)
+                    .execute(&mut *conn)
+                    .await?;
+                Ok(())
+            }
+
+            /// The tags of each of the items, keyed by item id
+            pub async fn for_items(item_ids: Vec<i64>, pool: &SqlitePool) -> Result<HashMap<i64, Vec<String>>, sqlx::Error> {
+                let (q, v) = Query::select()
+                    .column((ItemTagsTable::Table, ItemTagsTable::ItemId))
+                    .column((TagsTable::Table, TagsTable::Name))
+                    .from(ItemTagsTable::Table)
+                    .inner_join(
+                        TagsTable::Table,
+                        Expr::col((ItemTagsTable::Table, ItemTagsTable::TagId)).equals((TagsTable::Table, TagsTable::Id))
+                    )
+                    .and_where(Expr::col((ItemTagsTable::Table, ItemTagsTable::ItemId)).is_in(item_ids))
+                    .order_by((TagsTable::Table, TagsTable::Name), Order::Asc)
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                let mut tags: HashMap<i64, Vec<String>> = HashMap::new();
+                for row in sqlx::query_with(&q, v).fetch_all(pool).await? {
+                    tags.entry(row.get(ItemTagsTable::ItemId.as_str()))
+                        .or_default()
+                        .push(row.get(TagsTable::Name.as_str()));
+                }
+                Ok(tags)
+            }
+
+            /// Every tag used in the bag, in alphabetical order
+            #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
+            pub async fn for_bag(bag_id: i64, pool: &SqlitePool) -> Result<Vec<String>, sqlx::Error> {
+                let (q, v) = Query::select()
+                    .column(TagsTable::Name)
+                    .from(TagsTable::Table)
+                    .and_where(Expr::col(TagsTable::BagId).eq(bag_id))
+                    .order_by(TagsTable::Name, Order::Asc)
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                Ok(sqlx::query_with(&q, v)
+                    .fetch_all(pool)
+                    .await?
+                    .iter()
+                    .map(|row| row.get(TagsTable::Name.as_str()))
+                    .collect())
+            }
+
+            /// Ids of the items that carry any of the tags
+            fn tagged(tags: Vec<String>) -> SelectStatement {
+                Query::select()
+                    .column((ItemTagsTable::Table, ItemTagsTable::ItemId))
+                    .from(ItemTagsTable::Table)
+                    .inner_join(
+                        TagsTable::Table,
+                        Expr::col((ItemTagsTable::Table, ItemTagsTable::TagId)).equals((TagsTable::Table, TagsTable::Id))
+                    )
+                    .and_where(Expr::col((TagsTable::Table, TagsTable::Name)).is_in(tags))
+                    .to_owned()
+            }
+
+            /// Limits `query` on bag items to the ones with every tag in `with_tags` and none in
+            /// `without_tags`
+            fn restrict(query: &mut SelectStatement, with_tags: &[String], without_tags: &[String]) {
+                let with_tags = parse_tags(&with_tags.join(","));
+                let without_tags = parse_tags(&without_tags.join(","));
+                for tag in with_tags {
+                    query.and_where(
+                        Expr::col((BagItemsTable::Table, BagItemsTable::Id)).in_subquery(Self::tagged(vec![tag]))
+                    );
+                }
+                if !without_tags.is_empty() {
+                    query.and_where(
+                        Expr::col((BagItemsTable::Table, BagItemsTable::Id)).not_in_subquery(Self::tagged(without_tags))
+                    );
+                }
+            }
         }
 
         impl BagItem {
             #[tracing::instrument(level = "info", skip_all, ret, err)]
             pub async fn insert(self, pool:&SqlitePool) -> Result<BagItem, sqlx::Error> {
+                let mut tx = pool.begin().await?;
+                let item = self.insert_with(&mut tx).await?;
+                tx.commit().await?;
+                Ok(item)
+            }
+
+            /// Adds all of the items or none of them
+            #[tracing::instrument(level = "info", skip_all, err)]
+            pub async fn insert_many(items: Vec<BagItem>, pool: &SqlitePool) -> Result<Vec<BagItem>, sqlx::Error> {
+                let mut tx = pool.begin().await?;
+                let mut inserted = Vec::with_capacity(items.len());
+                for item in items {
+                    inserted.push(item.insert_with(&mut tx).await?);
+                }
+                tx.commit().await?;
+                Ok(inserted)
+            }
+
+            async fn insert_with(self, conn: &mut SqliteConnection) -> Result<BagItem, sqlx::Error> {
                 let (insert_stmt, values) = Query::insert()
                     .into_table(BagItemsTable::Table)
                     .columns([
+                        BagItemsTable::BagId,
                         BagItemsTable::AddedBy,
                         BagItemsTable::Name,
                         BagItemsTable::Description,
                         BagItemsTable::Quantity,
                         BagItemsTable::Size,
                         BagItemsTable::Infinite,
+                        BagItemsTable::Weight,
+                        BagItemsTable::Rounds,
+                        BagItemsTable::ParLevel,
                         BagItemsTable::CreatedAt
                     ])
                     .values_panic([
+                        self.bag_id.into(),
                         self.added_by.id.into(),
                         (&self.name).into(),
                         (&self.description).into(),
                         self.quantity.into(),
                         Into::<u8>::into(self.size).into(),
                         self.infinite.into(),
+                        self.weight.into(),
+                        self.rounds.map(|r| r.to_string()).into(),
+                        self.par_level.into(),
                         self.created_at.into()
                     ])
                     .to_owned()
                     .build_sqlx(SqliteQueryBuilder);
 
                 let row_id = sqlx::query_with(&insert_stmt, values)
-                    .execute(pool)
+                    .execute(&mut *conn)
                     .await?
                     .last_insert_rowid();
+                Tag::set_for_item(row_id, self.bag_id, &self.tags, conn).await?;
 
                 Ok(BagItem{
                     id: row_id,
@@ -174,35 +1008,117 @@ cfg_if! {
                 let (q, values) = Query::update()
                     .table(BagItemsTable::Table)
                     .values([
+                        (BagItemsTable::BagId, self.bag_id.into()),
                         (BagItemsTable::AddedBy, self.added_by.id.into()),
                         (BagItemsTable::Name, (&self.name).into()),
                         (BagItemsTable::Description,(&self.description).into()),
                         (BagItemsTable::Quantity, self.quantity.into()),
                         (BagItemsTable::Size, Into::<u8>::into(self.size).into()),
                         (BagItemsTable::Infinite, self.infinite.into()),
+                        (BagItemsTable::Weight, self.weight.into()),
+                        (BagItemsTable::Rounds, self.rounds.map(|r| r.to_string()).into()),
+                        (BagItemsTable::ParLevel, self.par_level.into()),
                         (BagItemsTable::CreatedAt, self.created_at.into())
                     ])
                     .and_where(Expr::col(BagItemsTable::Id).eq(self.id))
                     .to_owned()
                     .build_sqlx(SqliteQueryBuilder);
 
+                let mut tx = pool.begin().await?;
+                sqlx::query_with(&q, values)
+                    .execute(&mut *tx)
+                    .await?;
+                Tag::set_for_item(self.id, self.bag_id, &self.tags, &mut tx).await?;
+                tx.commit().await
+            }
+
+            /// Moves the item to the trash
+            #[tracing::instrument(level = "info", skip_all, fields(error), ret, err)]
+            pub async fn delete(self, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+                self.set_deleted_at(Some(Utc::now()), pool).await
+            }
+
+            /// Takes the item back out of the trash
+            #[tracing::instrument(level = "info", skip_all, fields(error), ret, err)]
+            pub async fn restore(self, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+                self.set_deleted_at(None, pool).await
+            }
+
+            async fn set_deleted_at(&self, deleted_at: Option<DateTime<Utc>>, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+                let (q, values) = Query::update()
+                    .table(BagItemsTable::Table)
+                    .value(BagItemsTable::DeletedAt, deleted_at)
+                    .and_where(Expr::col(BagItemsTable::Id).eq(self.id))
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
                 sqlx::query_with(&q, values)
                     .execute(pool)
                     .await?;
                 Ok(())
             }
 
+            /// Deletes the item for good, along with every draw and restock of it
             #[tracing::instrument(level = "info", skip_all, fields(error), ret, err)]
-            pub async fn delete(self, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+            pub async fn purge(self, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+                let mut tx = pool.begin().await?;
+                let (q, values) = Query::delete()
+                    .from_table(TakenItemsTable::Table)
+                    .cond_where(Expr::col(TakenItemsTable::ItemId).eq(self.id))
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                sqlx::query_with(&q, values)
+                    .execute(&mut *tx)
+                    .await?;
+                let (q, values) = Query::delete()
+                    .from_table(RestocksTable::Table)
+                    .cond_where(Expr::col(RestocksTable::ItemId).eq(self.id))
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                sqlx::query_with(&q, values)
+                    .execute(&mut *tx)
+                    .await?;
+                Tag::clear_for_item(self.id, self.bag_id, &mut tx).await?;
                 let (q, values) = Query::delete()
                     .from_table(BagItemsTable::Table)
                     .cond_where(Expr::col(BagItemsTable::Id).eq(self.id))
                     .to_owned()
                     .build_sqlx(SqliteQueryBuilder);
                 sqlx::query_with(&q, values)
-                    .execute(pool)
+                    .execute(&mut *tx)
                     .await?;
-                Ok(())
+                tx.commit().await
+            }
+
+            /// Names of every item in the bag that isn't in the trash
+            #[tracing::instrument(level = "info", skip(pool), err)]
+            pub async fn names(bag_id: i64, pool: &SqlitePool) -> Result<Vec<String>, sqlx::Error> {
+                let (q, v) = Query::select()
+                    .column(BagItemsTable::Name)
+                    .from(BagItemsTable::Table)
+                    .and_where(Expr::col(BagItemsTable::BagId).eq(bag_id))
+                    .and_where(Expr::col(BagItemsTable::DeletedAt).is_null())
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                Ok(sqlx::query_with(&q, v)
+                    .fetch_all(pool)
+                    .await?
+                    .iter()
+                    .map(|row| row.get(BagItemsTable::Name.as_str()))
+                    .collect())
+            }
+
+            /// Items in the bag's trash, most recently deleted first
+            #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
+            pub async fn trash(bag_id: i64, pool: &SqlitePool) -> Result<Vec<BagItem>, sqlx::Error> {
+                Self::get_many(
+                    Query::select()
+                        .and_where(Expr::col((BagItemsTable::Table, BagItemsTable::BagId)).eq(bag_id))
+                        .and_where(Expr::col((BagItemsTable::Table, BagItemsTable::DeletedAt)).is_not_null())
+                        .order_by((BagItemsTable::Table, BagItemsTable::DeletedAt), Order::Desc)
+                        .order_by((BagItemsTable::Table, BagItemsTable::Id), Order::Desc)
+                        .to_owned(),
+                    pool
+                ).await
             }
 
             async fn get_one(mut query: SelectStatement, pool: &SqlitePool) -> Result<Option<Self>, sqlx::Error> {
@@ -224,16 +1140,18 @@ cfg_if! {
                         UserTable::Table,
                         Expr::col((BagItemsTable::Table, BagItemsTable::AddedBy)).equals((UserTable::Table, UserTable::Id))
                     )
-
-                    .order_by((BagItemsTable::Table, BagItemsTable::Id), Order::Desc)
                     .to_owned()
                     .build_sqlx(SqliteQueryBuilder);
                 let result = sqlx::query_with(&q, values)
                     .fetch_all(pool)
                     .await?;
+                let ids = result.iter().map(|row| row.get(BagItemsTable::Id.as_str())).collect();
+                let mut tags = Tag::for_items(ids, pool).await?;
                     Ok(result.iter().map(|row| {
+                        let id: i64 = row.get(BagItemsTable::Id.as_str());
                         BagItem {
-                            id: row.get(BagItemsTable::Id.as_str()),
+                            id,
+                            bag_id: row.get(BagItemsTable::BagId.as_str()),
 
                             added_by: User {
                                 id: row.get("user_id"),
@@ -245,7 +1163,13 @@ cfg_if! {
                             quantity: row.get(BagItemsTable::Quantity.as_str()),
                             size: row.get::<u8, _>(BagItemsTable::Size.as_str()).into(),
                             infinite: row.get(BagItemsTable::Infinite.as_str()),
-                            created_at: row.get::<DateTime<Utc>, _>(BagItemsTable::CreatedAt.as_str())
+                            weight: row.get(BagItemsTable::Weight.as_str()),
+                            rounds: row.get::<Option<String>, _>(BagItemsTable::Rounds.as_str())
+                                .and_then(|r| r.parse().ok()),
+                            par_level: row.get(BagItemsTable::ParLevel.as_str()),
+                            tags: tags.remove(&id).unwrap_or_default(),
+                            created_at: row.get::<DateTime<Utc>, _>(BagItemsTable::CreatedAt.as_str()),
+                            deleted_at: row.get::<Option<DateTime<Utc>>, _>(BagItemsTable::DeletedAt.as_str())
                         }
                     }).collect())
             }
@@ -278,27 +1202,76 @@ cfg_if! {
 
             }
 
-            #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
-            pub async fn filter(filter: BagItemFilter, pool: &SqlitePool) -> Result<BagItemPage, sqlx::Error>{
-                let mut query = Query::select();
-                if let Some(added_by) = filter.added_by {
-                    query = query.and_where(Expr::col(BagItemsTable::AddedBy).is_in(added_by)).take();
+            /// The conditions in `filter`, leaving out paging. Items in the trash never match.
+            fn filter_query(filter: &BagItemFilter) -> SelectStatement {
+                let mut query = Query::select()
+                    .and_where(Expr::col((BagItemsTable::Table, BagItemsTable::DeletedAt)).is_null())
+                    .to_owned();
+                if let Some(bag_id) = filter.bag_id {
+                    query = query.and_where(Expr::col((BagItemsTable::Table, BagItemsTable::BagId)).eq(bag_id)).take();
+                }
+                if let Some(added_by) = filter.added_by.clone() {
+                    query = query.and_where(Expr::col((BagItemsTable::Table, BagItemsTable::AddedBy)).is_in(added_by)).take();
                 }
-                if let Some(name) = filter.name {
-                    query = query.and_where(Expr::col(BagItemsTable::Name).like(name)).take();
+                if let Some(name) = filter.name.clone() {
+                    query = query.and_where(Expr::col((BagItemsTable::Table, BagItemsTable::Name)).like(name)).take();
                 }
-                if let Some(description) = filter.description {
-                    query = query.and_where(Expr::col(BagItemsTable::Description).like(description)).take();
+                if let Some(description) = filter.description.clone() {
+                    query = query.and_where(Expr::col((BagItemsTable::Table, BagItemsTable::Description)).like(description)).take();
                 }
-                if let Some(size) = filter.size {
-                    query = query.and_where(Expr::col(BagItemsTable::Size).is_in(size)).take();
+                if let Some(size) = filter.size.clone() {
+                    query = query.and_where(Expr::col((BagItemsTable::Table, BagItemsTable::Size)).is_in(size)).take();
                 }
                 if let Some(infinite) = filter.infinite {
-                    query = query.and_where(Expr::col(BagItemsTable::Infinite).eq(infinite)).take();
+                    query = query.and_where(Expr::col((BagItemsTable::Table, BagItemsTable::Infinite)).eq(infinite)).take();
+                }
+                if let Some(q) = filter.q.as_deref().and_then(fts_query) {
+                    query = query
+                        .inner_join(
+                            BagItemsFtsTable::Table,
+                            Expr::col((BagItemsFtsTable::Table, BagItemsFtsTable::Rowid))
+                                .equals((BagItemsTable::Table, BagItemsTable::Id))
+                        )
+                        .and_where(Expr::cust_with_values("\"bagitems_fts\" MATCH ?", [q]))
+                        .take();
+                }
+                if let Some(tags) = &filter.tags {
+                    Tag::restrict(&mut query, tags, &[]);
+                }
+                query
+            }
+
+            /// What the listing is ordered by, most significant first. Ties always go to the newest
+            /// item, so every item has a place of its own.
+            fn sort_keys(filter: &BagItemFilter, by_rank: bool) -> Vec<(SortKey, Order)> {
+                let mut keys = filter.sort
+                    .iter()
+                    .flatten()
+                    .map(|sort| (SortKey::Column(sort.column), match sort.direction {
+                        SortDirection::Asc => Order::Asc,
+                        SortDirection::Desc => Order::Desc
+                    }))
+                    .collect::<Vec<_>>();
+                if by_rank && filter.q.as_deref().and_then(fts_query).is_some() {
+                    keys.push((SortKey::Rank, Order::Asc));
+                }
+                keys.push((SortKey::Id, Order::Desc));
+                keys
+            }
+
+            #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
+            pub async fn filter(filter: BagItemFilter, pool: &SqlitePool) -> Result<BagItemPage, sqlx::Error>{
+                if filter.paging() == PagingMode::Cursor {
+                    return Self::filter_by_cursor(filter, pool).await;
                 }
+                let mut query = Self::filter_query(&filter);
                 let count = Self::count(Some(query.clone()), pool).await?;
-                let page = filter.page_num.map(|page| page - 1).unwrap_or(0);
-                let page_size = filter.page_size.unwrap_or(50);
+                for (column, order) in Self::sort_keys(&filter, true) {
+                    query.order_by_expr(column.expr(), order);
+                }
+                // Page numbers start at 1, and an empty page would never get anywhere
+                let page = filter.page_num.map(|page| page.max(1) - 1).unwrap_or(0);
+                let page_size = filter.page_size.unwrap_or(50).max(1);
                 let offset:u64 = page * page_size;
                 query = query
                     .offset(offset)
@@ -310,10 +1283,65 @@ cfg_if! {
                     page_num: page + 1,
                     page_size: page_size,
                     total_pages: count.div_ceil(page_size),
-                    total_results: count
+                    total_results: count,
+                    next_cursor: None,
+                    prev_cursor: None
                 })
             }
 
+            /// Keyset pagination. The page picks up right where the cursor's item left off, so
+            /// it only depends on that item's sort values and not on how many items come before.
+            async fn filter_by_cursor(filter: BagItemFilter, pool: &SqlitePool) -> Result<BagItemPage, sqlx::Error> {
+                let keys = Self::sort_keys(&filter, false);
+                let page_size = filter.page_size.unwrap_or(50).max(1);
+                let (cursor, backwards) = match (&filter.after, &filter.before) {
+                    (Some(after), _) => (Some(after), false),
+                    (None, Some(before)) => (Some(before), true),
+                    (None, None) => (None, false)
+                };
+                let values = match cursor.map(|c| ItemCursor::decode(c, &keys)) {
+                    Some(None) => {
+                        tracing::warn!("Ignoring a cursor that doesn't match the sort order");
+                        None
+                    },
+                    Some(values) => values,
+                    None => None
+                };
+
+                let mut query = Self::filter_query(&filter);
+                if let Some(values) = &values {
+                    query.cond_where(ItemCursor::condition(&keys, values, backwards));
+                }
+                for (column, order) in &keys {
+                    let order = match (order, backwards) {
+                        (Order::Asc, true) => Order::Desc,
+                        (Order::Desc, true) => Order::Asc,
+                        (order, _) => order.clone()
+                    };
+                    query.order_by_expr(column.expr(), order);
+                }
+                query.limit(page_size + 1);
+
+                let mut items = Self::get_many(query, pool).await?;
+                let more = items.len() as u64 > page_size;
+                items.truncate(page_size as usize);
+                let (has_prev, has_next) = if backwards {
+                    items.reverse();
+                    (more, true)
+                } else {
+                    (values.is_some(), more)
+                };
+                let cursor_for = |item: Option<&BagItem>| item.map(|item| ItemCursor::encode(&keys, item));
+                Ok(BagItemPage {
+                    next_cursor: cursor_for(items.last().filter(|_| has_next)),
+                    prev_cursor: cursor_for(items.first().filter(|_| has_prev)),
+                    items,
+                    page_num: 0,
+                    page_size,
+                    total_pages: 0,
+                    total_results: 0
+                })
+            }
         }
     }
 }
@@ -321,10 +1349,49 @@ cfg_if! {
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TakenBagItem {
     pub id: i64,
+    pub bag_id: i64,
     pub item: BagItem,
     pub extraction_time: DateTime<Utc>,
     pub rounds: u32,
+    /// Counts down as rounds are ended. The draw is finished when it reaches zero.
+    pub rounds_remaining: u32,
     pub done: bool,
+    pub abandoned: bool,
+    pub seed: Option<u64>,
+    /// Who drew the item. Empty for draws made before this was recorded.
+    pub drawn_by: Option<User>,
+    pub completed_by: Option<User>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+/// Narrows down the draw history. `from` and `to` are inclusive days in UTC.
+#[derive(Serialize, Default, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub struct TakenItemFilter {
+    pub bag_id: Option<i64>,
+    pub item_id: Option<i64>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub done: Option<bool>,
+    pub page_size: Option<u64>,
+    pub page_num: Option<u64>,
+}
+
+impl TakenItemFilter {
+    pub fn with_page(&self, page_num: u64) -> Self {
+        TakenItemFilter {
+            page_num: Some(page_num),
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Serialize, Default, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub struct TakenBagItemPage {
+    pub items: Vec<TakenBagItem>,
+    pub page_num: u64,
+    pub total_pages: u64,
+    pub page_size: u64,
+    pub total_results: u64,
 }
 
 cfg_if! {
@@ -334,128 +1401,364 @@ cfg_if! {
         pub enum TakenItemsTable {
             Table,
             Id,
+            #[iden="bag_id"]
+            BagId,
             #[iden="item_id"]
             ItemId,
             #[iden="extraction_time"]
             ExtractionTime,
             #[iden="num_rounds"]
             NumRounds,
-            Done
+            Done,
+            Seed,
+            #[iden="draw_pool"]
+            DrawPool,
+            #[iden="drawn_by"]
+            DrawnBy,
+            #[iden="completed_by"]
+            CompletedBy,
+            #[iden="completed_at"]
+            CompletedAt,
+            #[iden="rounds_remaining"]
+            RoundsRemaining,
+            Abandoned,
+            Decremented
+        }
+
+        /// How many times a draw is retried when it loses a race with another draw
+        const MAX_DRAW_ATTEMPTS: u64 = 10;
+
+        enum DrawAttempt {
+            Drawn(i64),
+            Empty,
+            Conflict
         }
 
         impl TakenBagItem {
 
             #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
-            pub async fn get_random(pool: &SqlitePool) -> Result<Option<TakenBagItem>, sqlx::Error> {
-                let item_use_subquery = Query::select()
-                    .from(TakenItemsTable::Table)
-                    .column((TakenItemsTable::Table, TakenItemsTable::ItemId))
-                    .expr_as(Func::count(Expr::col((TakenItemsTable::Table, TakenItemsTable::Id))), Alias::new("use_count"))
-                    .group_by_col((TakenItemsTable::Table, TakenItemsTable::ItemId))
-                    .to_owned();
-                let mut item_uses_left = Query::select()
-                    .from(BagItemsTable::Table)
-                    .join_subquery(
-                        JoinType::LeftJoin,
-                        item_use_subquery,
-                        Alias::new("usage"),
-                        Expr::col(
-                                (BagItemsTable::Table, BagItemsTable::Id))
-                            .eq(
-                                Expr::col(TakenItemsTable::ItemId)
-                            )
-                    )
-                    .expr_as(
-                        Expr::case(
-                            Expr::col((BagItemsTable::Table, BagItemsTable::Infinite)).eq(false),
-                            Expr::col((BagItemsTable::Table, BagItemsTable::Quantity)).sub(
-                                Expr::col((Alias::new("usage"), Alias::new("use_count"))).if_null(0)
-                            )
-                        ).finally(1),
-                        Alias::new("uses_left")
-                    )
-                    .columns([BagItemsTable::Id])
-                    .and_where(Expr::col(Alias::new("uses_left")).gte(1))
-                    .to_owned();
-                let (count_query, v) = Query::select()
-                    .expr_as(Expr::count(Expr::col(BagItemsTable::Id)), Alias::new("itemcount"))
-                    .from_subquery(
-                        item_uses_left.clone(),
-                        Alias::new("uses")
-                    )
+            pub async fn get_random(bag_id: i64, drawn_by: i64, tags: &DrawTags, pool: &SqlitePool) -> Result<RoadieResult<Option<TakenBagItem>>, sqlx::Error> {
+                let mut engine = DrawEngine::from_entropy();
+                Self::get_random_with_engine(bag_id, drawn_by, tags, &mut engine, pool).await
+            }
+
+            /// Draws an item inside a single transaction. If SQLite reports the database as busy
+            /// because another draw got there first, the whole draw is retried with fresh candidates.
+            /// Someone who hasn't finished their last draw gets that draw back instead of a new one.
+            /// `None` means there's nothing left to draw, and `DrawContention` that every attempt
+            /// lost its race, so the bag may well still have stock.
+            #[tracing::instrument(level = "info", skip(engine, pool), fields(error), ret, err)]
+            pub async fn get_random_with_engine<R: RngCore + Send>(bag_id: i64, drawn_by: i64, tags: &DrawTags, engine: &mut DrawEngine<R>, pool: &SqlitePool) -> Result<RoadieResult<Option<TakenBagItem>>, sqlx::Error> {
+                let mut attempt = 0;
+                loop {
+                    attempt += 1;
+                    match Self::try_draw(bag_id, drawn_by, tags, engine, pool).await {
+                        Ok(DrawAttempt::Drawn(id)) => return Self::by_id(id, pool).await.map(Ok),
+                        Ok(DrawAttempt::Empty) => return Ok(Ok(None)),
+                        Ok(DrawAttempt::Conflict) if attempt < MAX_DRAW_ATTEMPTS => {
+                            tracing::debug!("Drawn item was used up by another draw, retrying");
+                        },
+                        Err(e) if attempt < MAX_DRAW_ATTEMPTS && is_busy(&e) => {
+                            tracing::debug!("Database busy while drawing, retrying: {}", e);
+                        },
+                        Ok(DrawAttempt::Conflict) => {
+                            tracing::warn!("Giving up on drawing from bag {} after {} attempts", bag_id, attempt);
+                            return Ok(Err(RoadieAppError::DrawContention));
+                        },
+                        Err(e) => return Err(e)
+                    }
+                    tokio::time::sleep(Duration::from_millis(5 * attempt)).await;
+                }
+            }
+
+            async fn try_draw<R: RngCore + Send>(bag_id: i64, drawn_by: i64, tags: &DrawTags, engine: &mut DrawEngine<R>, pool: &SqlitePool) -> Result<DrawAttempt, sqlx::Error> {
+                let mut tx = pool.begin().await?;
+                if let Some(id) = Self::active_id(bag_id, drawn_by, &mut tx).await? {
+                    return Ok(DrawAttempt::Drawn(id));
+                }
+                let candidates = Self::draw_candidates(bag_id, tags, &mut tx).await?;
+                let outcome = match engine.draw(&candidates) {
+                    Some(outcome) => outcome,
+                    None => return Ok(DrawAttempt::Empty)
+                };
+
+                // Only take a copy of a finite item if there's still one left
+                let (q, v) = Query::update()
+                    .table(BagItemsTable::Table)
+                    .value(BagItemsTable::Quantity, Expr::col(BagItemsTable::Quantity).sub(1))
+                    .and_where(Expr::col(BagItemsTable::Id).eq(outcome.item_id))
+                    .and_where(Expr::col(BagItemsTable::Infinite).eq(false))
+                    .and_where(Expr::col(BagItemsTable::Quantity).gt(0))
                     .to_owned()
                     .build_sqlx(SqliteQueryBuilder);
-
-                let count:u64 = sqlx::query_with(&count_query, v.clone())
-                    .fetch_one(pool)
+                let taken = sqlx::query_with(&q, v)
+                    .execute(&mut *tx)
                     .await?
-                    .get::<i64, _>("itemcount")
-                    .try_into()
-                    .expect("Underflow");
-                if count == 0 {
-                    return Ok(None);
-                }
-                let mut rng = rand::thread_rng();
-                let item_offset = rng.gen_range(0..count);
-
-                let (q, v) = item_uses_left
-                    .column(BagItemsTable::Id)
-                    .offset(item_offset)
-                    .limit(1)
+                    .rows_affected();
+                let (q, v) = Query::select()
+                    .from(BagItemsTable::Table)
+                    .column(BagItemsTable::Infinite)
+                    .and_where(Expr::col(BagItemsTable::Id).eq(outcome.item_id))
                     .to_owned()
                     .build_sqlx(SqliteQueryBuilder);
-                let result = sqlx::query_with(&q, v)
-                    .fetch_one(pool)
-                    .await?;
-                let item_id:i64 = result.get("id");
-                let mut item = BagItem::by_id(item_id, pool).await?.expect(&format!("Invalid item ID {}", item_id));
-                if !item.infinite {
-                    item.quantity -= 1;
-                    item.update(pool).await?;
+                let infinite: bool = sqlx::query_with(&q, v)
+                    .fetch_one(&mut *tx)
+                    .await?
+                    .get(BagItemsTable::Infinite.as_str());
+                if taken == 0 && !infinite {
+                    tx.rollback().await?;
+                    return Ok(DrawAttempt::Conflict);
                 }
 
-                let num_rounds = rng.gen_range(1..=6);
+                let id = Self::insert(bag_id, drawn_by, &outcome, &candidates, taken > 0, &mut tx).await?;
+                tx.commit().await?;
+                Ok(DrawAttempt::Drawn(id))
+            }
+
+            /// Every item in the bag that has uses left and matches `tags`, weighted by its own
+            /// weight or the bag's weight for its size
+            #[tracing::instrument(level = "info", skip(conn), fields(error), err)]
+            pub async fn draw_candidates(bag_id: i64, tags: &DrawTags, conn: &mut SqliteConnection) -> Result<Vec<DrawCandidate>, sqlx::Error> {
+                let bag = match Bag::by_id(bag_id, &mut *conn).await? {
+                    Some(bag) => bag,
+                    None => return Ok(vec![])
+                };
+                // The stored quantity is the only record of what's left. Each draw decrements it,
+                // so past draws must not be counted against it again.
+                let mut query = Query::select();
+                query
+                    .from(BagItemsTable::Table)
+                    .columns([
+                        (BagItemsTable::Table, BagItemsTable::Id),
+                        (BagItemsTable::Table, BagItemsTable::Size),
+                        (BagItemsTable::Table, BagItemsTable::Weight),
+                        (BagItemsTable::Table, BagItemsTable::Rounds)
+                    ])
+                    .and_where(Expr::col((BagItemsTable::Table, BagItemsTable::BagId)).eq(bag_id))
+                    .and_where(Expr::col((BagItemsTable::Table, BagItemsTable::DeletedAt)).is_null())
+                    .cond_where(
+                        Cond::any()
+                            .add(Expr::col((BagItemsTable::Table, BagItemsTable::Infinite)).eq(true))
+                            .add(Expr::col((BagItemsTable::Table, BagItemsTable::Quantity)).gte(1))
+                    )
+                    .order_by((BagItemsTable::Table, BagItemsTable::Id), Order::Asc);
+                Tag::restrict(&mut query, &tags.with_tags, &tags.without_tags);
+                let (q, v) = query.build_sqlx(SqliteQueryBuilder);
 
-                Ok(Some(Self::insert(item_id, num_rounds, pool).await?))
+                Ok(sqlx::query_with(&q, v)
+                    .fetch_all(&mut *conn)
+                    .await?
+                    .iter()
+                    .map(|row| {
+                        let size: ItemSize = row.get::<u8, _>(BagItemsTable::Size.as_str()).into();
+                        DrawCandidate {
+                            item_id: row.get(BagItemsTable::Id.as_str()),
+                            weight: row.get::<Option<u32>, _>(BagItemsTable::Weight.as_str())
+                                .unwrap_or_else(|| bag.size_weights.weight_for(size)),
+                            rounds: row.get::<Option<String>, _>(BagItemsTable::Rounds.as_str())
+                                .and_then(|r| r.parse().ok())
+                                .unwrap_or(bag.default_rounds)
+                        }
+                    })
+                    .collect())
             }
 
-            #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
-            pub async fn insert(item_id: i64, num_rounds: i64, pool:&SqlitePool) -> Result<TakenBagItem, sqlx::Error> {
+            /// Records a draw along with the seed and candidates it was made from, so it can be replayed
+            #[tracing::instrument(level = "info", skip(candidates, conn), fields(error), ret, err)]
+            pub async fn insert(bag_id: i64, drawn_by: i64, outcome: &DrawOutcome, candidates: &[DrawCandidate], decremented: bool, conn: &mut SqliteConnection) -> Result<i64, sqlx::Error> {
+                let draw_pool = serde_json::to_string(candidates)
+                    .map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
                 let (q, v) = Query::insert()
                     .into_table(TakenItemsTable::Table)
-                    .columns([TakenItemsTable::ItemId, TakenItemsTable::NumRounds])
+                    .columns([
+                        TakenItemsTable::BagId,
+                        TakenItemsTable::ItemId,
+                        TakenItemsTable::NumRounds,
+                        TakenItemsTable::RoundsRemaining,
+                        TakenItemsTable::Seed,
+                        TakenItemsTable::DrawPool,
+                        TakenItemsTable::DrawnBy,
+                        TakenItemsTable::Decremented
+                    ])
                     .values_panic([
-                        item_id.into(),
-                        num_rounds.into()
+                        bag_id.into(),
+                        outcome.item_id.into(),
+                        outcome.num_rounds.into(),
+                        outcome.num_rounds.into(),
+                        // SQLite only has signed integers, the seed is stored bit for bit
+                        (outcome.seed as i64).into(),
+                        draw_pool.into(),
+                        drawn_by.into(),
+                        decremented.into()
                     ])
                     .to_owned()
                     .build_sqlx(SqliteQueryBuilder);
                 let id = sqlx::query_with(&q, v)
-                    .execute(pool)
+                    .execute(conn)
                     .await?
                     .last_insert_rowid();
-                Ok(Self::by_id(id, pool)
-                    .await?
-                    .unwrap())
+                Ok(id)
             }
 
+            /// Runs the draw again from its stored seed and candidates. Returns `None` for draws
+            /// made before seeds were recorded.
             #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
-            pub async fn update(&self, pool: &SqlitePool) -> Result<(), sqlx::Error> {
-                let (q, v) = Query::update()
+            pub async fn replay(&self, pool: &SqlitePool) -> Result<Option<DrawOutcome>, sqlx::Error> {
+                let (q, v) = Query::select()
+                    .from(TakenItemsTable::Table)
+                    .columns([TakenItemsTable::Seed, TakenItemsTable::DrawPool])
+                    .and_where(Expr::col(TakenItemsTable::Id).eq(self.id))
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                let row = sqlx::query_with(&q, v)
+                    .fetch_one(pool)
+                    .await?;
+                let seed = row.try_get::<Option<i64>, _>(TakenItemsTable::Seed.as_str())?;
+                let draw_pool = row.try_get::<Option<String>, _>(TakenItemsTable::DrawPool.as_str())?;
+                match (seed, draw_pool) {
+                    (Some(seed), Some(draw_pool)) => {
+                        let candidates: Vec<DrawCandidate> = serde_json::from_str(&draw_pool)
+                            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+                        Ok(replay(seed as u64, &candidates))
+                    },
+                    _ => Ok(None)
+                }
+            }
+
+            /// Runs `query` against this draw only while it's still in progress, then reloads it.
+            /// Returns `None` when the draw had already been finished.
+            async fn transition(&self, mut query: UpdateStatement, pool: &SqlitePool) -> Result<Option<Self>, sqlx::Error> {
+                let (q, v) = query
                     .table(TakenItemsTable::Table)
-                    .values([
-                        (TakenItemsTable::ItemId, self.item.id.into()),
-                        (TakenItemsTable::ExtractionTime, self.extraction_time.into()),
-                        (TakenItemsTable::NumRounds, self.rounds.into()),
-                        (TakenItemsTable::Done, self.done.into())
-                    ])
                     .and_where(Expr::col(TakenItemsTable::Id).eq(self.id))
+                    .and_where(Expr::col(TakenItemsTable::Done).eq(false))
                     .to_owned()
                     .build_sqlx(SqliteQueryBuilder);
-                sqlx::query_with(&q, v)
+                let updated = sqlx::query_with(&q, v)
                     .execute(pool)
+                    .await?
+                    .rows_affected();
+                if updated == 0 {
+                    Ok(None)
+                } else {
+                    Self::by_id(self.id, pool).await
+                }
+            }
+
+            /// Marks the draw as done by `user_id`
+            #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
+            pub async fn complete(&self, user_id: i64, pool: &SqlitePool) -> Result<Option<Self>, sqlx::Error> {
+                self.transition(
+                    Query::update()
+                        .values([
+                            (TakenItemsTable::Done, true.into()),
+                            (TakenItemsTable::CompletedBy, user_id.into()),
+                            (TakenItemsTable::CompletedAt, Utc::now().into())
+                        ])
+                        .to_owned(),
+                    pool
+                ).await
+            }
+
+            /// Moves the draw on by one round. Playing the last round marks the draw as done by `user_id`.
+            #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
+            pub async fn advance_round(&self, user_id: i64, pool: &SqlitePool) -> Result<Option<Self>, sqlx::Error> {
+                // Every expression in the SET clause sees the row as it was before the update
+                let last_round = || Expr::col(TakenItemsTable::RoundsRemaining).lte(1);
+                self.transition(
+                    Query::update()
+                        .values([
+                            (TakenItemsTable::RoundsRemaining, Expr::col(TakenItemsTable::RoundsRemaining).sub(1)),
+                            (TakenItemsTable::Done, last_round().into()),
+                            (TakenItemsTable::CompletedBy, Expr::case(last_round(), user_id)
+                                .finally(Expr::col(TakenItemsTable::CompletedBy))
+                                .into()),
+                            (TakenItemsTable::CompletedAt, Expr::case(last_round(), Utc::now())
+                                .finally(Expr::col(TakenItemsTable::CompletedAt))
+                                .into())
+                        ])
+                        .and_where(Expr::col(TakenItemsTable::RoundsRemaining).gt(0))
+                        .to_owned(),
+                    pool
+                ).await
+            }
+
+            /// Takes the draw back as if it never happened, putting back the copy it took, if any.
+            /// Only the latest draw its player made in the bag can be undone; returns false for
+            /// any other.
+            #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
+            pub async fn undo(&self, pool: &SqlitePool) -> Result<bool, sqlx::Error> {
+                let mut tx = pool.begin().await?;
+                let drawn_by = self.drawn_by.as_ref().map(|u| u.id);
+                let latest = Query::select()
+                    .expr(Expr::col(TakenItemsTable::Id).max())
+                    .from(TakenItemsTable::Table)
+                    .and_where(Expr::col(TakenItemsTable::BagId).eq(self.bag_id))
+                    .and_where(Expr::col(TakenItemsTable::DrawnBy).eq(drawn_by))
+                    .to_owned();
+                let (q, v) = Query::delete()
+                    .from_table(TakenItemsTable::Table)
+                    .and_where(Expr::col(TakenItemsTable::Id).eq(self.id))
+                    .and_where(Expr::col(TakenItemsTable::Id).in_subquery(latest))
+                    .returning_col(TakenItemsTable::Decremented)
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                let decremented = sqlx::query_with(&q, v)
+                    .fetch_optional(&mut *tx)
+                    .await?
+                    .map(|row| row.get::<bool, _>(TakenItemsTable::Decremented.as_str()));
+                let decremented = match decremented {
+                    Some(decremented) => decremented,
+                    None => {
+                        tx.rollback().await?;
+                        return Ok(false);
+                    }
+                };
+                // Only put back a copy the draw actually took, and not if a restock has already
+                // refilled the item since, or it would end up over its par level
+                if !decremented {
+                    tx.commit().await?;
+                    return Ok(true);
+                }
+
+                let restocked_since = Query::select()
+                    .expr(Expr::val(1))
+                    .from(RestocksTable::Table)
+                    .and_where(Expr::col(RestocksTable::ItemId).eq(self.item.id))
+                    .and_where(
+                        Expr::expr(Func::cust(Alias::new("datetime")).arg(Expr::col(RestocksTable::RestockedAt)))
+                            .gte(Func::cust(Alias::new("datetime")).arg(self.extraction_time))
+                    )
+                    .to_owned();
+                let (q, v) = Query::update()
+                    .table(BagItemsTable::Table)
+                    .value(BagItemsTable::Quantity, Expr::col(BagItemsTable::Quantity).add(1))
+                    .and_where(Expr::col(BagItemsTable::Id).eq(self.item.id))
+                    .and_where(Expr::col(BagItemsTable::Infinite).eq(false))
+                    .and_where(Expr::exists(restocked_since).not())
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                sqlx::query_with(&q, v)
+                    .execute(&mut *tx)
                     .await?;
-                Ok(())
+                tx.commit().await?;
+                Ok(true)
+            }
+
+            /// Gives up on the draw without finishing it
+            #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
+            pub async fn abandon(&self, user_id: i64, pool: &SqlitePool) -> Result<Option<Self>, sqlx::Error> {
+                self.transition(
+                    Query::update()
+                        .values([
+                            (TakenItemsTable::Done, true.into()),
+                            (TakenItemsTable::Abandoned, true.into()),
+                            (TakenItemsTable::CompletedBy, user_id.into()),
+                            (TakenItemsTable::CompletedAt, Utc::now().into())
+                        ])
+                        .to_owned(),
+                    pool
+                ).await
             }
 
             #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
@@ -486,18 +1789,38 @@ cfg_if! {
                 let result = sqlx::query_with(&q, values)
                     .fetch_all(pool)
                     .await?;
+                // Draws of items that were hard deleted before the trash existed are left out
                 let tbis = try_join_all(result.iter().map(|row| async {
-                    let bi = BagItem::by_id(row.try_get(TakenItemsTable::ItemId.as_str())?, pool).await?;
-                    Ok(TakenBagItem {
+                    let bi = match BagItem::by_id(row.try_get(TakenItemsTable::ItemId.as_str())?, pool).await? {
+                        Some(bi) => bi,
+                        None => return Ok(None)
+                    };
+                    let drawn_by = match row.try_get::<Option<i64>, _>(TakenItemsTable::DrawnBy.as_str())? {
+                        Some(id) => SQLUser::by_id(id, pool).await?.map(|u| u.into()),
+                        None => None
+                    };
+                    let completed_by = match row.try_get::<Option<i64>, _>(TakenItemsTable::CompletedBy.as_str())? {
+                        Some(id) => SQLUser::by_id(id, pool).await?.map(|u| u.into()),
+                        None => None
+                    };
+                    Ok::<_, sqlx::Error>(Some(TakenBagItem {
                         id: row.try_get(TakenItemsTable::Id.as_str())?,
-                        item: bi.unwrap(),
+                        bag_id: row.try_get(TakenItemsTable::BagId.as_str())?,
+                        item: bi,
                         extraction_time: row.try_get::<DateTime<Utc>, _>(TakenItemsTable::ExtractionTime.as_str())?,
                         rounds: row.try_get(TakenItemsTable::NumRounds.as_str())?,
-                        done: row.try_get(TakenItemsTable::Done.as_str())?
-                    })
+                        rounds_remaining: row.try_get(TakenItemsTable::RoundsRemaining.as_str())?,
+                        done: row.try_get(TakenItemsTable::Done.as_str())?,
+                        abandoned: row.try_get(TakenItemsTable::Abandoned.as_str())?,
+                        seed: row.try_get::<Option<i64>, _>(TakenItemsTable::Seed.as_str())?
+                            .map(|seed| seed as u64),
+                        drawn_by,
+                        completed_by,
+                        completed_at: row.try_get::<Option<DateTime<Utc>>, _>(TakenItemsTable::CompletedAt.as_str())?
+                    }))
                     }));
 
-                tbis.await
+                Ok(tbis.await?.into_iter().flatten().collect())
             }
 
             #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
@@ -511,20 +1834,285 @@ cfg_if! {
                 ).await
             }
 
+            pub async fn count(query: Option<SelectStatement>, pool: &SqlitePool) -> Result<u64, sqlx::Error> {
+                let mut query = query.unwrap_or(Query::select());
+                let (q, v) = query
+                    .from(TakenItemsTable::Table)
+                    .expr_as(Expr::col(TakenItemsTable::Id).count(), Alias::new("takencount"))
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                sqlx::query_with(&q, v)
+                    .fetch_one(pool)
+                    .await
+                    .map(|r| {
+                        r.get::<i64, _>("takencount")
+                            .try_into()
+                            .expect("Underflow")
+                    })
+            }
+
+            /// A page of the draw history, newest draws first
+            #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
+            pub async fn filter(filter: TakenItemFilter, pool: &SqlitePool) -> Result<TakenBagItemPage, sqlx::Error> {
+                // Draws of items that were hard deleted before the trash existed can't be shown,
+                // so they're left out here where the count sees it too
+                let mut query = Query::select()
+                    .and_where(Expr::exists(
+                        Query::select()
+                            .expr(Expr::val(1))
+                            .from(BagItemsTable::Table)
+                            .and_where(
+                                Expr::col((BagItemsTable::Table, BagItemsTable::Id))
+                                    .equals((TakenItemsTable::Table, TakenItemsTable::ItemId))
+                            )
+                            .to_owned()
+                    ))
+                    .to_owned();
+                if let Some(bag_id) = filter.bag_id {
+                    query = query.and_where(Expr::col(TakenItemsTable::BagId).eq(bag_id)).take();
+                }
+                if let Some(item_id) = filter.item_id {
+                    query = query.and_where(Expr::col(TakenItemsTable::ItemId).eq(item_id)).take();
+                }
+                // Compare on the day alone so it works no matter how the timestamp was written
+                let extraction_day = || Func::cust(Alias::new("date")).arg(Expr::col(TakenItemsTable::ExtractionTime));
+                if let Some(from) = filter.from {
+                    query = query.and_where(Expr::expr(extraction_day()).gte(from.format("%Y-%m-%d").to_string())).take();
+                }
+                if let Some(to) = filter.to {
+                    query = query.and_where(Expr::expr(extraction_day()).lte(to.format("%Y-%m-%d").to_string())).take();
+                }
+                if let Some(done) = filter.done {
+                    query = query.and_where(Expr::col(TakenItemsTable::Done).eq(done)).take();
+                }
+                let count = Self::count(Some(query.clone()), pool).await?;
+                // Page numbers start at 1, and an empty page would never get anywhere
+                let page = filter.page_num.map(|page| page.max(1) - 1).unwrap_or(0);
+                let page_size = filter.page_size.unwrap_or(50).max(1);
+                let offset:u64 = page * page_size;
+                query = query
+                    .order_by(TakenItemsTable::Id, Order::Desc)
+                    .offset(offset)
+                    .limit(page_size)
+                    .to_owned();
+                let items = Self::get_many(query, pool).await?;
+                Ok(TakenBagItemPage {
+                    items,
+                    page_num: page + 1,
+                    page_size,
+                    total_pages: count.div_ceil(page_size),
+                    total_results: count
+                })
+            }
+
+            async fn active_id(bag_id: i64, user_id: i64, conn: &mut SqliteConnection) -> Result<Option<i64>, sqlx::Error> {
+                let (q, v) = Query::select()
+                    .column(TakenItemsTable::Id)
+                    .from(TakenItemsTable::Table)
+                    .and_where(Expr::col(TakenItemsTable::BagId).eq(bag_id))
+                    .and_where(Expr::col(TakenItemsTable::DrawnBy).eq(user_id))
+                    .and_where(Expr::col(TakenItemsTable::Done).eq(false))
+                    .order_by(TakenItemsTable::Id, Order::Desc)
+                    .limit(1)
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                Ok(sqlx::query_with(&q, v)
+                    .fetch_optional(conn)
+                    .await?
+                    .map(|row| row.get(TakenItemsTable::Id.as_str())))
+            }
+
+            /// The draw the user is still working through in the bag, if any
+            #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
+            pub async fn active_for(bag_id: i64, user_id: i64, pool: &SqlitePool) -> Result<Option<Self>, sqlx::Error> {
+                Self::get_one(
+                    Query::select()
+                        .and_where(Expr::col(TakenItemsTable::BagId).eq(bag_id))
+                        .and_where(Expr::col(TakenItemsTable::DrawnBy).eq(user_id))
+                        .and_where(Expr::col(TakenItemsTable::Done).eq(false))
+                        .order_by(TakenItemsTable::Id, Order::Desc)
+                        .to_owned(),
+                    pool
+                ).await
+            }
+
+            /// Every unfinished draw in the bag except the user's own, newest first
             #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
-            pub async fn last(pool: &SqlitePool) -> Result<Option<Self>, sqlx::Error> {
-                let tbi = Self::get_one(
+            pub async fn active_for_others(bag_id: i64, user_id: i64, pool: &SqlitePool) -> Result<Vec<Self>, sqlx::Error> {
+                Self::get_many(
                     Query::select()
+                        .and_where(Expr::col(TakenItemsTable::BagId).eq(bag_id))
+                        .and_where(Expr::col(TakenItemsTable::Done).eq(false))
+                        .cond_where(
+                            Cond::any()
+                                .add(Expr::col(TakenItemsTable::DrawnBy).ne(user_id))
+                                .add(Expr::col(TakenItemsTable::DrawnBy).is_null())
+                        )
                         .order_by(TakenItemsTable::Id, Order::Desc)
                         .to_owned(),
                     pool
-                ).await?;
+                ).await
+            }
+        }
+    }
+}
+
+/// One item being filled back up to its par level
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Restock {
+    pub id: i64,
+    pub bag_id: i64,
+    pub item: BagItem,
+    pub restocked_by: Option<User>,
+    pub restocked_at: DateTime<Utc>,
+    pub quantity_before: i32,
+    pub quantity_after: i32,
+}
+
+cfg_if! {
+    if #[cfg(feature="ssr")] {
+        #[derive(IdenStatic, EnumIter, Copy, Clone)]
+        #[iden="restocks"]
+        pub enum RestocksTable {
+            Table,
+            Id,
+            #[iden="bag_id"]
+            BagId,
+            #[iden="item_id"]
+            ItemId,
+            #[iden="restocked_by"]
+            RestockedBy,
+            #[iden="restocked_at"]
+            RestockedAt,
+            #[iden="quantity_before"]
+            QuantityBefore,
+            #[iden="quantity_after"]
+            QuantityAfter
+        }
+
+        impl Restock {
+            /// Fills every finite item matching `filter` back up to its par level and records it.
+            /// `item_id` narrows it down to a single item.
+            #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
+            pub async fn refill(filter: &BagItemFilter, item_id: Option<i64>, restocked_by: i64, pool: &SqlitePool) -> Result<Vec<Restock>, sqlx::Error> {
+                let mut query = BagItem::filter_query(filter);
+                if let Some(item_id) = item_id {
+                    query.and_where(Expr::col((BagItemsTable::Table, BagItemsTable::Id)).eq(item_id));
+                }
+                query
+                    .and_where(Expr::col((BagItemsTable::Table, BagItemsTable::Infinite)).eq(false))
+                    .and_where(
+                        Expr::col((BagItemsTable::Table, BagItemsTable::Quantity))
+                            .lt(Expr::col((BagItemsTable::Table, BagItemsTable::ParLevel)))
+                    );
+                let items = BagItem::get_many(query, pool).await?;
+
+                let restocked_at = Utc::now();
+                let mut restock_ids = Vec::new();
+                let mut tx = pool.begin().await?;
+                for item in items {
+                    let par_level = match item.par_level {
+                        Some(par_level) => par_level,
+                        None => continue
+                    };
+                    // Only refill from the quantity that was read, so a draw that got in between
+                    // isn't wiped out. The item gets picked up by the next restock instead.
+                    let (q, v) = Query::update()
+                        .table(BagItemsTable::Table)
+                        .value(BagItemsTable::Quantity, par_level)
+                        .and_where(Expr::col(BagItemsTable::Id).eq(item.id))
+                        .and_where(Expr::col(BagItemsTable::Quantity).eq(item.quantity))
+                        .to_owned()
+                        .build_sqlx(SqliteQueryBuilder);
+                    let updated = sqlx::query_with(&q, v)
+                        .execute(&mut *tx)
+                        .await?
+                        .rows_affected();
+                    if updated == 0 {
+                        continue;
+                    }
+
+                    let (q, v) = Query::insert()
+                        .into_table(RestocksTable::Table)
+                        .columns([
+                            RestocksTable::BagId,
+                            RestocksTable::ItemId,
+                            RestocksTable::RestockedBy,
+                            RestocksTable::RestockedAt,
+                            RestocksTable::QuantityBefore,
+                            RestocksTable::QuantityAfter
+                        ])
+                        .values_panic([
+                            item.bag_id.into(),
+                            item.id.into(),
+                            restocked_by.into(),
+                            restocked_at.into(),
+                            item.quantity.into(),
+                            par_level.into()
+                        ])
+                        .to_owned()
+                        .build_sqlx(SqliteQueryBuilder);
+                    let id = sqlx::query_with(&q, v)
+                        .execute(&mut *tx)
+                        .await?
+                        .last_insert_rowid();
+                    restock_ids.push(id);
+                }
+                tx.commit().await?;
+
+                Self::get_many(
+                    Query::select()
+                        .and_where(Expr::col(RestocksTable::Id).is_in(restock_ids))
+                        .order_by(RestocksTable::Id, Order::Asc)
+                        .to_owned(),
+                    pool
+                ).await
+            }
+
+            /// The most recent restocks in the bag, newest first
+            #[tracing::instrument(level = "info", skip(pool), fields(error), ret, err)]
+            pub async fn for_bag(bag_id: i64, limit: u64, pool: &SqlitePool) -> Result<Vec<Restock>, sqlx::Error> {
+                Self::get_many(
+                    Query::select()
+                        .and_where(Expr::col(RestocksTable::BagId).eq(bag_id))
+                        .order_by(RestocksTable::Id, Order::Desc)
+                        .limit(limit)
+                        .to_owned(),
+                    pool
+                ).await
+            }
 
-                match tbi {
-                    Some(t) if t.done => Ok(None),
-                    Some(t) => Ok(Some(t)),
-                    None => Ok(None)
+            /// Restocks of items that have since been deleted are left out
+            async fn get_many(mut query: SelectStatement, pool: &SqlitePool) -> Result<Vec<Self>, sqlx::Error> {
+                let (q, values) = query
+                    .from(RestocksTable::Table)
+                    .column(Asterisk)
+                    .to_owned()
+                    .build_sqlx(SqliteQueryBuilder);
+                let rows = sqlx::query_with(&q, values)
+                    .fetch_all(pool)
+                    .await?;
+                let mut restocks = Vec::with_capacity(rows.len());
+                for row in rows {
+                    let item = match BagItem::by_id(row.try_get(RestocksTable::ItemId.as_str())?, pool).await? {
+                        Some(item) => item,
+                        None => continue
+                    };
+                    let restocked_by = match row.try_get::<Option<i64>, _>(RestocksTable::RestockedBy.as_str())? {
+                        Some(id) => SQLUser::by_id(id, pool).await?.map(|u| u.into()),
+                        None => None
+                    };
+                    restocks.push(Restock {
+                        id: row.try_get(RestocksTable::Id.as_str())?,
+                        bag_id: row.try_get(RestocksTable::BagId.as_str())?,
+                        item,
+                        restocked_by,
+                        restocked_at: row.try_get::<DateTime<Utc>, _>(RestocksTable::RestockedAt.as_str())?,
+                        quantity_before: row.try_get(RestocksTable::QuantityBefore.as_str())?,
+                        quantity_after: row.try_get(RestocksTable::QuantityAfter.as_str())?
+                    });
                 }
+                Ok(restocks)
             }
         }
     }
@@ -3,7 +3,7 @@ use cfg_if::cfg_if;
 cfg_if! {
     if #[cfg(feature = "ssr")] {
         #[cfg(test)]
-        mod tests {
+        pub(crate) mod tests {
             use crate::tests::tests::get_test_server;
             use crate::auth::tests::tests::create_test_user;
             use crate::errors::*;
@@ -14,24 +14,62 @@ cfg_if! {
             use chrono::prelude::*;
             use leptos::logging;
             use crate::bag::model::*;
+            use crate::bag::draw::*;
+            use crate::bag::dice::*;
+            use crate::bag::export::*;
+            use crate::bag::import::*;
             use tracing::span;
             use http::status::StatusCode;
+            use crate::auth::{Role, User};
+            use crate::auth::model::{SQLUser, SQLUserPermission, ADMIN_TOKEN};
+            use crate::backup::*;
+            use std::future::IntoFuture;
+
+            pub(crate) async fn create_test_bag(user: &User, pool: &SqlitePool) -> Result<Bag> {
+                let bag = Bag {
+                    id: -1,
+                    created_by: user.clone(),
+                    name: "Some bag".into(),
+                    description: "Some bag description".into(),
+                    size_weights: SizeWeights::default(),
+                    default_rounds: DiceExpr::default(),
+                    undo_window_secs: 60,
+                    created_at: Utc::now()
+                };
+                Ok(bag.insert(pool).await?)
+            }
+
+            /// A finite small item with nothing else set. It isn't stored, so tests can change
+            /// what they need before inserting it.
+            pub(crate) fn create_test_item(bag_id: i64, name: &str, added_by: &User) -> BagItem {
+                BagItem {
+                    bag_id,
+                    added_by: added_by.clone(),
+                    created_at: Utc::now(),
+                    description: "Some description".into(),
+                    name: name.into(),
+                    id: -1,
+                    infinite: false,
+                    quantity: 1,
+                    size: ItemSize::Small,
+                    weight: None,
+                    rounds: None,
+                    par_level: None,
+                    deleted_at: None,
+                    tags: vec![]
+                }
+            }
 
             #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
             #[sqlx::test]
             async fn test_item_e2e(pool: SqlitePool) -> Result<()> {
                 let test_server = get_test_server(&pool).await?;
                 let test_user = create_test_user(&test_server, None).await;
+                let bag = create_test_bag(&test_user, &pool).await?;
 
                 let bi = BagItem {
-                    added_by: test_user,
-                    created_at: Utc::now(),
-                    description: "Some description".into(),
-                    name: "Some item".into(),
-                    id: -1,
-                    infinite: false,
-                    quantity: 1,
-                    size: ItemSize::Large
+                    size: ItemSize::Large,
+                    ..create_test_item(bag.id, "Some item", &test_user)
                 };
 
                 let new_bi = bi.insert(&pool).await?;
@@ -52,6 +90,15 @@ cfg_if! {
 
                 by_id2.delete(&pool).await?;
                 let by_id2 = BagItem::by_id(by_id.id, &pool).await?;
+                assert_eq!(by_id2.and_then(|bi| bi.deleted_at).is_some(), true);
+                let trash = BagItem::trash(bag.id, &pool).await?;
+                assert_eq!(trash.len(), 1);
+                trash[0].clone().restore(&pool).await?;
+                assert_eq!(BagItem::trash(bag.id, &pool).await?.len(), 0);
+
+                let id = by_id.id;
+                BagItem::by_id(id, &pool).await?.unwrap().purge(&pool).await?;
+                let by_id2 = BagItem::by_id(id, &pool).await?;
                 assert_eq!(by_id2.is_some(), false);
                 Ok(())
             }
@@ -63,17 +110,9 @@ cfg_if! {
                 let test_user = create_test_user(&test_server, None).await;
                 let test_user2 = create_test_user(&test_server, Some("scott2".into())).await;
                 assert_ne!(test_user.id, test_user2.id);
+                let bag = create_test_bag(&test_user, &pool).await?;
                 for _i in 0..10 {
-                    let bi = BagItem {
-                        added_by: test_user.clone(),
-                        created_at: Utc::now(),
-                        description: "Some description".into(),
-                        name: "Some item".into(),
-                        id: -1,
-                        infinite: false,
-                        quantity: 1,
-                        size: ItemSize::Small
-                    };
+                    let bi = create_test_item(bag.id, "Some item", &test_user);
 
                     let new_bi = bi.insert(&pool).await?;
                     assert_ne!(new_bi.id, -1);
@@ -81,14 +120,9 @@ cfg_if! {
 
                 for _i in 0..10 {
                     let bi = BagItem {
-                        added_by: test_user.clone(),
-                        created_at: Utc::now(),
-                        description: "Some description".into(),
-                        name: "Some item".into(),
-                        id: -1,
                         infinite: true,
-                        quantity: 1,
-                        size: ItemSize::Medium
+                        size: ItemSize::Medium,
+                        ..create_test_item(bag.id, "Some item", &test_user)
                     };
 
                     let new_bi = bi.insert(&pool).await?;
@@ -97,14 +131,9 @@ cfg_if! {
 
                 for _i in 0..10 {
                     let bi = BagItem {
-                        added_by: test_user2.clone(),
-                        created_at: Utc::now(),
-                        description: "Some description".into(),
-                        name: "Some item".into(),
-                        id: -1,
-                        infinite: false,
                         quantity: 50,
-                        size: ItemSize::Large
+                        size: ItemSize::Large,
+                        ..create_test_item(bag.id, "Some item", &test_user2)
                     };
 
                     let new_bi = bi.insert(&pool).await?;
@@ -136,6 +165,15 @@ cfg_if! {
                 assert_eq!(page.total_results, 10);
                 assert_eq!(page.items.len(), 10);
                 assert_eq!(page.total_pages, 1);
+
+                let clamped = BagItem::filter(BagItemFilter {
+                    added_by: Some(vec![test_user.id]),
+                    page_size: Some(0),
+                    page_num: Some(0),
+                    ..Default::default()
+                }, &pool).await?;
+                assert_eq!((clamped.page_num, clamped.page_size, clamped.total_pages), (1, 1, 20));
+                assert_eq!(clamped.items.len(), 1);
                 Ok(())
             }
 
@@ -144,28 +182,43 @@ cfg_if! {
             async fn test_item_random(pool: SqlitePool) -> Result<()> {
                 let test_server = get_test_server(&pool).await?;
                 let test_user = create_test_user(&test_server, None).await;
+                let bag = create_test_bag(&test_user, &pool).await?;
+                let other_bag = create_test_bag(&test_user, &pool).await?;
 
                 let bi = BagItem {
-                    added_by: test_user,
-                    created_at: Utc::now(),
-                    description: "Some description".into(),
-                    name: "Some item".into(),
-                    id: -1,
-                    infinite: false,
-                    quantity: 1,
-                    size: ItemSize::Large
+                    size: ItemSize::Large,
+                    ..create_test_item(bag.id, "Some item", &test_user)
                 };
 
                 let new_bi = bi.insert(&pool).await?;
                 assert_ne!(new_bi.id, -1);
 
-                let random_item = TakenBagItem::get_random(&pool).await?;
+                let other_bag_item = TakenBagItem::get_random(other_bag.id, test_user.id, &DrawTags::default(), &pool).await??;
+                assert_eq!(other_bag_item.is_some(), false);
+
+                let random_item = TakenBagItem::get_random(bag.id, test_user.id, &DrawTags::default(), &pool).await??;
                 assert_eq!(random_item.is_some(), true);
                 let random_item = random_item.unwrap();
                 assert_eq!(random_item.item.id, new_bi.id);
+                assert_eq!(random_item.bag_id, bag.id);
+                assert_eq!(random_item.drawn_by.as_ref().map(|u| u.id), Some(test_user.id));
+                assert_eq!(random_item.completed_by, None);
 
-                let random_item2 = TakenBagItem::get_random(&pool).await?;
-                assert_eq!(random_item2.is_some(), false);
+                assert_eq!(TakenBagItem::active_for(other_bag.id, test_user.id, &pool).await?.is_some(), false);
+                assert_eq!(TakenBagItem::active_for(bag.id, test_user.id, &pool).await?.is_some(), true);
+                assert_eq!(TakenBagItem::active_for(bag.id, test_user.id + 1, &pool).await?.is_some(), false);
+                let others = TakenBagItem::active_for_others(bag.id, test_user.id + 1, &pool).await?;
+                assert_eq!(others.len(), 1);
+                assert_eq!(others[0].id, random_item.id);
+
+                // Drawing again before finishing hands back the same draw
+                let random_item2 = TakenBagItem::get_random(bag.id, test_user.id, &DrawTags::default(), &pool).await??;
+                assert_eq!(random_item2.map(|t| t.id), Some(random_item.id));
+
+                random_item.complete(test_user.id, &pool).await?;
+                assert_eq!(TakenBagItem::active_for(bag.id, test_user.id, &pool).await?.is_some(), false);
+                let random_item3 = TakenBagItem::get_random(bag.id, test_user.id, &DrawTags::default(), &pool).await??;
+                assert_eq!(random_item3.is_some(), false);
 
                 let for_item_vec = TakenBagItem::for_item(new_bi.id, &pool).await?;
                 assert_eq!(for_item_vec.len(), 1);
@@ -173,22 +226,1629 @@ cfg_if! {
                 Ok(())
             }
 
+            #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
+            #[sqlx::test]
+            async fn test_draw_quantity(pool: SqlitePool) -> Result<()> {
+                let test_server = get_test_server(&pool).await?;
+                let test_user = create_test_user(&test_server, None).await;
+                let bag = create_test_bag(&test_user, &pool).await?;
+
+                // Each draw uses up exactly one copy, so an item with quantity N comes out N times
+                for quantity in [1, 2, 5] {
+                    let bi = BagItem {
+                        quantity,
+                        size: ItemSize::Medium,
+                        par_level: Some(quantity),
+                        ..create_test_item(bag.id, &format!("{} copies", quantity), &test_user)
+                    }.insert(&pool).await?;
+
+                    for drawn in 1..=quantity {
+                        let tbi = TakenBagItem::get_random(bag.id, test_user.id, &DrawTags::default(), &pool).await??;
+                        let tbi = tbi.expect("Item ran out before every copy was drawn");
+                        assert_eq!(tbi.item.id, bi.id);
+                        assert_eq!(tbi.item.quantity, quantity - drawn);
+                        tbi.complete(test_user.id, &pool).await?;
+                    }
+                    assert_eq!(TakenBagItem::get_random(bag.id, test_user.id, &DrawTags::default(), &pool).await??, None);
+                    assert_eq!(BagItem::by_id(bi.id, &pool).await?.unwrap().quantity, 0);
+                    assert_eq!(TakenBagItem::for_item(bi.id, &pool).await?.len(), quantity as usize);
+
+                    // Restocking makes every copy drawable again, despite the earlier draws
+                    Restock::refill(&BagItemFilter::default(), Some(bi.id), test_user.id, &pool).await?;
+                    for _i in 0..quantity {
+                        let tbi = TakenBagItem::get_random(bag.id, test_user.id, &DrawTags::default(), &pool).await??;
+                        assert_eq!(tbi.as_ref().map(|t| t.item.id), Some(bi.id));
+                        tbi.unwrap().complete(test_user.id, &pool).await?;
+                    }
+                    assert_eq!(TakenBagItem::get_random(bag.id, test_user.id, &DrawTags::default(), &pool).await??, None);
+                }
+                Ok(())
+            }
+
+            #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
+            #[sqlx::test]
+            async fn test_weighted_random(pool: SqlitePool) -> Result<()> {
+                use rand::SeedableRng;
+                use rand::rngs::StdRng;
+                use std::collections::HashMap;
+
+                let test_server = get_test_server(&pool).await?;
+                let test_user = create_test_user(&test_server, None).await;
+                let bag = create_test_bag(&test_user, &pool).await?;
+
+                // Small, medium and large fall back to the bag's default weights of 1, 2 and 3,
+                // the last item overrides its weight and can never be drawn
+                let mut item_ids = vec![];
+                for (size, weight) in [
+                    (ItemSize::Small, None),
+                    (ItemSize::Medium, None),
+                    (ItemSize::Large, None),
+                    (ItemSize::Large, Some(0))
+                ] {
+                    let bi = BagItem {
+                        infinite: true,
+                        size,
+                        weight,
+                        ..create_test_item(bag.id, &format!("{} item", size), &test_user)
+                    };
+                    item_ids.push(bi.insert(&pool).await?.id);
+                }
+
+                let draws = 1200;
+                let mut engine = DrawEngine::new(StdRng::seed_from_u64(1234));
+                let mut counts: HashMap<i64, u32> = HashMap::new();
+                for _i in 0..draws {
+                    let tbi = TakenBagItem::get_random_with_engine(bag.id, test_user.id, &DrawTags::default(), &mut engine, &pool).await??
+                        .expect("Infinite items should always be drawable");
+                    *counts.entry(tbi.item.id).or_default() += 1;
+                    tbi.complete(test_user.id, &pool).await?;
+                }
+
+                let share = |id: i64| counts.get(&id).copied().unwrap_or_default() as f64 / draws as f64;
+                assert!((share(item_ids[0]) - 1.0 / 6.0).abs() < 0.05, "small share was {}", share(item_ids[0]));
+                assert!((share(item_ids[1]) - 2.0 / 6.0).abs() < 0.05, "medium share was {}", share(item_ids[1]));
+                assert!((share(item_ids[2]) - 3.0 / 6.0).abs() < 0.05, "large share was {}", share(item_ids[2]));
+                assert_eq!(counts.get(&item_ids[3]), None);
+                Ok(())
+            }
+
+            #[test]
+            fn test_dice_expr() {
+                use rand::SeedableRng;
+                use rand::rngs::StdRng;
+
+                let parsed = |s: &str| s.parse::<DiceExpr>();
+                assert_eq!(parsed("1d6"), Ok(DiceExpr::default()));
+                assert_eq!(parsed("d6"), Ok(DiceExpr::default()));
+                assert_eq!(parsed(" 2D4 + 1 "), Ok(DiceExpr { count: 2, sides: 4, modifier: 1 }));
+                assert_eq!(parsed("3d8-2"), Ok(DiceExpr { count: 3, sides: 8, modifier: -2 }));
+                assert_eq!(parsed("1"), Ok(DiceExpr::fixed(1)));
+                for invalid in ["", "0", "-1", "d", "1d", "0d6", "1d0", "1d6+", "1d4-1", "2x6", "1d6+1d4", "101d6"] {
+                    assert_eq!(parsed(invalid), Err(RoadieAppError::InvalidDiceExpr(invalid.into())), "{}", invalid);
+                }
+                for expr in ["1d6", "2d4+1", "3d8-2", "4"] {
+                    assert_eq!(parsed(expr).unwrap().to_string(), expr);
+                }
+
+                let mut rng = StdRng::seed_from_u64(7);
+                for expr in ["1d6", "2d4+1", "3d8-2", "4"] {
+                    let dice = parsed(expr).unwrap();
+                    for _i in 0..100 {
+                        let rounds = dice.roll(&mut rng) as i32;
+                        assert!((dice.min()..=dice.max()).contains(&rounds), "{} rolled {}", expr, rounds);
+                    }
+                }
+
+                let form = BagItemForm {
+                    name: "Some item".into(),
+                    size: Some(ItemSize::Small),
+                    rounds: Some("2d".into()),
+                    ..Default::default()
+                };
+                match form.validate() {
+                    Some(RoadieAppError::MultipleErrors(errors)) => assert_eq!(
+                        errors.get("rounds"),
+                        Some(&RoadieAppError::InvalidDiceExpr("2d".into()).to_string())
+                    ),
+                    other => panic!("Expected a rounds error, got {:?}", other)
+                }
+                assert_eq!(BagItemForm { rounds: Some(" ".into()), ..form.clone() }.validate(), None);
+                assert_eq!(BagItemForm { rounds: Some("2d4".into()), ..form }.validate(), None);
+            }
+
+            #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
+            #[sqlx::test]
+            async fn test_draw_rounds(pool: SqlitePool) -> Result<()> {
+                let test_server = get_test_server(&pool).await?;
+                let test_user = create_test_user(&test_server, None).await;
+                let mut bag = create_test_bag(&test_user, &pool).await?;
+                bag.default_rounds = DiceExpr::fixed(2);
+                bag.update(&pool).await?;
+                assert_eq!(Bag::by_id(bag.id, &pool).await?.unwrap().default_rounds, DiceExpr::fixed(2));
+
+                let item = BagItem {
+                    infinite: true,
+                    weight: Some(1),
+                    ..create_test_item(bag.id, "Bag default", &test_user)
+                }.insert(&pool).await?;
+                let mut quick = BagItem {
+                    name: "Always one round".into(),
+                    rounds: Some(DiceExpr::fixed(1)),
+                    ..item.clone()
+                }.insert(&pool).await?;
+                assert_eq!(BagItem::by_id(quick.id, &pool).await?.unwrap().rounds, Some(DiceExpr::fixed(1)));
+
+                for _i in 0..20 {
+                    let tbi = TakenBagItem::get_random(bag.id, test_user.id, &DrawTags::default(), &pool).await??.unwrap();
+                    let expected = if tbi.item.id == quick.id { 1 } else { 2 };
+                    assert_eq!(tbi.rounds, expected);
+                    assert_eq!(tbi.rounds_remaining, expected);
+                    tbi.complete(test_user.id, &pool).await?;
+                }
+
+                // Draws replay with the durations they were made with
+                quick.rounds = Some("3d6+10".parse()?);
+                quick.update(&pool).await?;
+                let tbi = TakenBagItem::get_random(bag.id, test_user.id, &DrawTags::default(), &pool).await??.unwrap();
+                let outcome = tbi.replay(&pool).await?.unwrap();
+                assert_eq!(outcome.num_rounds, tbi.rounds);
+                if tbi.item.id == quick.id {
+                    assert!((13..=28).contains(&tbi.rounds));
+                }
+                Ok(())
+            }
+
+            #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
+            #[sqlx::test]
+            async fn test_restock(pool: SqlitePool) -> Result<()> {
+                let test_server = get_test_server(&pool).await?;
+                let test_user = create_test_user(&test_server, None).await;
+                let bag = create_test_bag(&test_user, &pool).await?;
+                let small = BagItem {
+                    quantity: 3,
+                    par_level: Some(5),
+                    ..create_test_item(bag.id, "Small", &test_user)
+                }.insert(&pool).await?;
+                let large = BagItem {
+                    quantity: 0,
+                    size: ItemSize::Large,
+                    par_level: Some(2),
+                    ..create_test_item(bag.id, "Large", &test_user)
+                }.insert(&pool).await?;
+                let endless = BagItem {
+                    infinite: true,
+                    size: ItemSize::Large,
+                    par_level: Some(4),
+                    ..create_test_item(bag.id, "Endless", &test_user)
+                }.insert(&pool).await?;
+                let no_par = BagItem {
+                    quantity: 0,
+                    ..create_test_item(bag.id, "No par", &test_user)
+                }.insert(&pool).await?;
+                let quantity = |id: i64| {
+                    let pool = pool.clone();
+                    async move { BagItem::by_id(id, &pool).await.unwrap().unwrap().quantity }
+                };
+
+                // A single item
+                let filter = BagItemFilter { bag_id: Some(bag.id), ..Default::default() };
+                let restocks = Restock::refill(&filter, Some(small.id), test_user.id, &pool).await?;
+                assert_eq!(restocks.len(), 1);
+                assert_eq!(restocks[0].item.id, small.id);
+                assert_eq!((restocks[0].quantity_before, restocks[0].quantity_after), (3, 5));
+                assert_eq!(restocks[0].restocked_by.as_ref().map(|u| u.id), Some(test_user.id));
+                assert_eq!(quantity(small.id).await, 5);
+                assert_eq!(quantity(large.id).await, 0);
+
+                // A filtered set
+                let filter = BagItemFilter { bag_id: Some(bag.id), size: Some(vec![ItemSize::Large.into()]), ..Default::default() };
+                let restocks = Restock::refill(&filter, None, test_user.id, &pool).await?;
+                assert_eq!(restocks.iter().map(|r| r.item.id).collect::<Vec<_>>(), vec![large.id]);
+                assert_eq!(quantity(large.id).await, 2);
+                assert_eq!(quantity(endless.id).await, 1);
+
+                // The whole bag, through the API. Items at par, infinite ones and ones without
+                // a par level are left alone.
+                let mut drawn = BagItem::by_id(small.id, &pool).await?.unwrap();
+                drawn.quantity = 1;
+                drawn.update(&pool).await?;
+                let response = test_server.post("/api/restock_items")
+                    .text(qs::to_string(&RestockItems { bag_id: bag.id, item_id: None, filter: None })?)
+                    .content_type("application/x-www-form-urlencoded")
+                    .await;
+                let restocks = response.json::<RoadieResult<Vec<Restock>>>().unwrap();
+                assert_eq!(restocks.iter().map(|r| r.item.id).collect::<Vec<_>>(), vec![small.id]);
+                assert_eq!(quantity(small.id).await, 5);
+                assert_eq!(quantity(no_par.id).await, 0);
+
+                let history = Restock::for_bag(bag.id, 50, &pool).await?;
+                assert_eq!(
+                    history.iter().map(|r| (r.item.id, r.quantity_before)).collect::<Vec<_>>(),
+                    vec![(small.id, 1), (large.id, 0), (small.id, 3)]
+                );
+
+                // Players can see restocks but not make them
+                let player_server = get_test_server(&pool).await?;
+                let player = create_test_user(&player_server, Some("player".into())).await;
+                bag.set_role(player.id, Role::Player, &pool).await?;
+                let response = player_server.post("/api/restock_items")
+                    .text(qs::to_string(&RestockItems { bag_id: bag.id, item_id: Some(small.id), filter: None })?)
+                    .content_type("application/x-www-form-urlencoded")
+                    .await;
+                response.assert_status(StatusCode::FORBIDDEN);
+                let response = player_server.post("/api/list_restocks")
+                    .text(qs::to_string(&ListRestocks { bag_id: bag.id })?)
+                    .content_type("application/x-www-form-urlencoded")
+                    .await;
+                assert_eq!(response.json::<RoadieResult<Vec<Restock>>>(), Ok(history));
+                Ok(())
+            }
+
+            #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
+            #[sqlx::test]
+            async fn test_replay_draw(pool: SqlitePool) -> Result<()> {
+                use rand::SeedableRng;
+                use rand::rngs::StdRng;
+
+                let candidates = vec![
+                    DrawCandidate { item_id: 1, weight: 1, rounds: DiceExpr::default() },
+                    DrawCandidate { item_id: 2, weight: 5, rounds: DiceExpr::default() },
+                    DrawCandidate { item_id: 3, weight: 0, rounds: DiceExpr::default() }
+                ];
+                let mut engine1 = DrawEngine::new(StdRng::seed_from_u64(99));
+                let mut engine2 = DrawEngine::new(StdRng::seed_from_u64(99));
+                for _i in 0..20 {
+                    let outcome = engine1.draw(&candidates).unwrap();
+                    assert_eq!(Some(outcome.clone()), engine2.draw(&candidates));
+                    assert_eq!(Some(outcome.clone()), replay(outcome.seed, &candidates));
+                    assert_ne!(outcome.item_id, 3);
+                    assert!((1..=6).contains(&outcome.num_rounds));
+                }
+                assert_eq!(engine1.draw(&[]), None);
+
+                let test_server = get_test_server(&pool).await?;
+                let test_user = create_test_user(&test_server, None).await;
+                let bag = create_test_bag(&test_user, &pool).await?;
+                for size in [ItemSize::Small, ItemSize::Medium, ItemSize::Large] {
+                    let bi = BagItem {
+                        quantity: 2,
+                        size,
+                        ..create_test_item(bag.id, &format!("{} item", size), &test_user)
+                    };
+                    bi.insert(&pool).await?;
+                }
+
+                let mut engine = DrawEngine::new(StdRng::seed_from_u64(7));
+                let tbi = TakenBagItem::get_random_with_engine(bag.id, test_user.id, &DrawTags::default(), &mut engine, &pool).await??.unwrap();
+                assert_eq!(tbi.seed.is_some(), true);
+                tbi.complete(test_user.id, &pool).await?;
+
+                // Draw everything else out of the bag, the replay still uses the bag as it was
+                while let Some(next) = TakenBagItem::get_random(bag.id, test_user.id, &DrawTags::default(), &pool).await?? {
+                    next.complete(test_user.id, &pool).await?;
+                }
+                let outcome = tbi.replay(&pool).await?.unwrap();
+                assert_eq!(Some(outcome.seed), tbi.seed);
+                assert_eq!(outcome.item_id, tbi.item.id);
+                assert_eq!(outcome.num_rounds, tbi.rounds);
+                Ok(())
+            }
+
             use serde_qs as qs;
 
+            #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
+            #[sqlx::test]
+            async fn test_history_filter(pool: SqlitePool) -> Result<()> {
+                let test_server = get_test_server(&pool).await?;
+                let test_user = create_test_user(&test_server, None).await;
+                let bag = create_test_bag(&test_user, &pool).await?;
+
+                let bi = BagItem {
+                    infinite: true,
+                    size: ItemSize::Large,
+                    ..create_test_item(bag.id, "Some item", &test_user)
+                };
+                let first = bi.clone().insert(&pool).await?;
+                let second = BagItem {
+                    name: "Other item".into(),
+                    weight: Some(0),
+                    rounds: None,
+                    par_level: None,
+                    ..bi
+                }.insert(&pool).await?;
+
+                for _ in 0..5 {
+                    let tbi = TakenBagItem::get_random(bag.id, test_user.id, &DrawTags::default(), &pool).await??.unwrap();
+                    tbi.complete(test_user.id, &pool).await?;
+                }
+                // Swap the weights around so the last draw can only be the second item
+                let mut first = first;
+                first.weight = Some(0);
+                first.update(&pool).await?;
+                let mut second = second;
+                second.weight = None;
+                second.update(&pool).await?;
+                let last = TakenBagItem::get_random(bag.id, test_user.id, &DrawTags::default(), &pool).await??.unwrap();
+                assert_eq!(last.item.id, second.id);
+                // A draw of an item that's gone entirely is left out of the rows and the count alike
+                sqlx::query("INSERT INTO taken_items (bag_id, item_id, num_rounds, done) VALUES (?, 9999, 1, TRUE)")
+                    .bind(bag.id)
+                    .execute(&pool)
+                    .await?;
+
+                let all = TakenBagItem::filter(TakenItemFilter {
+                    bag_id: Some(bag.id),
+                    ..Default::default()
+                }, &pool).await?;
+                assert_eq!(all.total_results, 6);
+                assert_eq!(all.items.len(), 6);
+                assert_eq!(all.items[0].id, last.id);
+
+                let for_first = TakenBagItem::filter(TakenItemFilter {
+                    bag_id: Some(bag.id),
+                    item_id: Some(first.id),
+                    ..Default::default()
+                }, &pool).await?;
+                assert_eq!(for_first.total_results, 5);
+
+                let not_done = TakenBagItem::filter(TakenItemFilter {
+                    bag_id: Some(bag.id),
+                    done: Some(false),
+                    ..Default::default()
+                }, &pool).await?;
+                assert_eq!(not_done.total_results, 1);
+                assert_eq!(not_done.items[0].item.id, second.id);
+
+                let today = Utc::now().date_naive();
+                let in_range = TakenBagItem::filter(TakenItemFilter {
+                    bag_id: Some(bag.id),
+                    from: Some(today),
+                    to: Some(today),
+                    ..Default::default()
+                }, &pool).await?;
+                assert_eq!(in_range.total_results, 6);
+                let out_of_range = TakenBagItem::filter(TakenItemFilter {
+                    bag_id: Some(bag.id),
+                    from: today.succ_opt(),
+                    ..Default::default()
+                }, &pool).await?;
+                assert_eq!(out_of_range.total_results, 0);
+
+                let paged = TakenBagItem::filter(TakenItemFilter {
+                    bag_id: Some(bag.id),
+                    page_size: Some(4),
+                    page_num: Some(2),
+                    ..Default::default()
+                }, &pool).await?;
+                assert_eq!(paged.total_pages, 2);
+                assert_eq!(paged.page_num, 2);
+                assert_eq!(paged.items.len(), 2);
+
+                // Page 0 and empty pages are read as the smallest ones that make sense
+                let clamped = TakenBagItem::filter(TakenItemFilter {
+                    bag_id: Some(bag.id),
+                    page_size: Some(0),
+                    page_num: Some(0),
+                    ..Default::default()
+                }, &pool).await?;
+                assert_eq!((clamped.page_num, clamped.page_size, clamped.total_pages), (1, 1, 6));
+                assert_eq!(clamped.items.len(), 1);
+                Ok(())
+            }
+
+            #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
+            #[sqlx::test]
+            async fn test_concurrent_take_random(pool: SqlitePool) -> Result<()> {
+                let test_server = get_test_server(&pool).await?;
+                let test_user = create_test_user(&test_server, None).await;
+                let bag = create_test_bag(&test_user, &pool).await?;
+
+                let items = vec![
+                    BagItem {
+                        description: "Scarce item".into(),
+                        quantity: 3,
+                        ..create_test_item(bag.id, "Scarce item", &test_user)
+                    }.insert(&pool).await?,
+                    BagItem {
+                        quantity: 2,
+                        ..create_test_item(bag.id, "Rare item", &test_user)
+                    }.insert(&pool).await?,
+                ];
+                let before: i32 = items.iter().map(|i| i.quantity).sum();
+
+                // Every player gets their own session so they can all draw at once
+                let mut players = vec![test_server];
+                for i in 0..8 {
+                    let server = get_test_server(&pool).await?;
+                    let player = create_test_user(&server, Some(format!("player{}", i))).await;
+                    bag.set_role(player.id, Role::Player, &pool).await?;
+                    players.push(server);
+                }
+
+                let tr = qs::to_string(&TakeRandom { bag_id: bag.id, tags: None })?;
+                let requests = players.iter().map(|server| {
+                    server.post("/api/take_random")
+                        .text(tr.clone())
+                        .content_type("application/x-www-form-urlencoded")
+                        .into_future()
+                });
+                let responses = futures::future::join_all(requests).await;
+
+                let mut drawn = 0;
+                for response in responses {
+                    let res = response.json::<RoadieResult<Option<TakenBagItem>>>();
+                    if let Some(tbi) = res.expect("Draw failed") {
+                        assert!(items.iter().any(|i| i.id == tbi.item.id));
+                        drawn += 1;
+                    }
+                }
+                // Nine players and five copies, so every copy goes and nobody is told the bag is empty early
+                assert_eq!(drawn, 5);
+
+                // Every draw took exactly one copy, and no item was drawn past its last one
+                let mut after = 0;
+                let mut recorded = 0;
+                for bi in &items {
+                    let remaining = BagItem::by_id(bi.id, &pool).await?.unwrap();
+                    assert!(remaining.quantity >= 0);
+                    after += remaining.quantity;
+                    recorded += TakenBagItem::for_item(bi.id, &pool).await?.len() as i32;
+                }
+                assert_eq!(before - after, drawn);
+                assert_eq!(recorded, drawn);
+                Ok(())
+            }
+
+            #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
+            #[sqlx::test]
+            async fn test_draw_players(pool: SqlitePool) -> Result<()> {
+                let test_server = get_test_server(&pool).await?;
+                let test_user = create_test_user(&test_server, None).await;
+                let bag = create_test_bag(&test_user, &pool).await?;
+                let player_server = get_test_server(&pool).await?;
+                let player = create_test_user(&player_server, Some("player".into())).await;
+                bag.set_role(player.id, Role::Player, &pool).await?;
+                let bi = create_test_item(bag.id, "Some item", &test_user).insert(&pool).await?;
+
+                // The draw remembers who made it
+                let tr = qs::to_string(&TakeRandom { bag_id: bag.id, tags: None })?;
+                let response = player_server.post("/api/take_random")
+                    .text(tr.clone())
+                    .content_type("application/x-www-form-urlencoded")
+                    .await;
+                let tbi = response.json::<RoadieResult<Option<TakenBagItem>>>().unwrap().unwrap();
+                assert_eq!(tbi.item.id, bi.id);
+                assert_eq!(tbi.drawn_by.as_ref().map(|u| u.id), Some(player.id));
+                assert_eq!(tbi.completed_by, None);
+                assert_eq!(tbi.completed_at, None);
+
+                let response = player_server.post("/api/take_random")
+                    .text(tr)
+                    .content_type("application/x-www-form-urlencoded")
+                    .await;
+                response.assert_status(StatusCode::CONFLICT);
+                assert_eq!(response.json::<RoadieResult<Option<TakenBagItem>>>(), Err(RoadieAppError::DrawInProgress));
+
+                // And who finished it
+                let cd = CompleteDraw {
+                    bag_id: bag.id,
+                    draw_id: tbi.id
+                };
+                let response = player_server.post("/api/complete_draw")
+                    .text(qs::to_string(&cd)?)
+                    .content_type("application/x-www-form-urlencoded")
+                    .await;
+                response.assert_status(StatusCode::OK);
+                let tbi = TakenBagItem::by_id(tbi.id, &pool).await?.unwrap();
+                assert_eq!(tbi.done, true);
+                assert_eq!(tbi.completed_by.map(|u| u.id), Some(player.id));
+                assert_eq!(tbi.completed_at.is_some(), true);
+                Ok(())
+            }
+
+            #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
+            #[sqlx::test]
+            async fn test_draw_transitions(pool: SqlitePool) -> Result<()> {
+                let _admin_server = get_test_server(&pool).await?;
+                let _admin = create_test_user(&_admin_server, Some("siteadmin".into())).await;
+                let test_server = get_test_server(&pool).await?;
+                let test_user = create_test_user(&test_server, None).await;
+                let bag = create_test_bag(&test_user, &pool).await?;
+                let other_server = get_test_server(&pool).await?;
+                let other = create_test_user(&other_server, Some("other".into())).await;
+                bag.set_role(other.id, Role::Player, &pool).await?;
+
+                BagItem {
+                    infinite: true,
+                    size: ItemSize::Large,
+                    ..create_test_item(bag.id, "Some item", &test_user)
+                }.insert(&pool).await?;
+
+                let post = |server: &axum_test::TestServer, path: &str, body: String| {
+                    server.post(path)
+                        .text(body)
+                        .content_type("application/x-www-form-urlencoded")
+                };
+
+                let take = qs::to_string(&TakeRandom { bag_id: bag.id, tags: None })?;
+                let response = post(&other_server, "/api/take_random", take.clone()).await;
+                let tbi = response.json::<RoadieResult<Option<TakenBagItem>>>().unwrap().unwrap();
+                assert_eq!(tbi.rounds_remaining, tbi.rounds);
+                let draw = qs::to_string(&AbandonDraw { bag_id: bag.id, draw_id: tbi.id })?;
+
+                // An editor who didn't draw it can't move it along
+                let outsider_server = get_test_server(&pool).await?;
+                let outsider = create_test_user(&outsider_server, Some("outsider".into())).await;
+                bag.set_role(outsider.id, Role::Editor, &pool).await?;
+                let response = post(&outsider_server, "/api/abandon_draw", draw.clone()).await;
+                response.assert_status(StatusCode::FORBIDDEN);
+
+                // The bag's owner can, even though somebody else drew it
+                let response = post(&test_server, "/api/abandon_draw", draw.clone()).await;
+                let abandoned = response.json::<RoadieResult<TakenBagItem>>().unwrap();
+                assert_eq!(abandoned.done, true);
+                assert_eq!(abandoned.abandoned, true);
+                assert_eq!(abandoned.item.id, tbi.item.id);
+                assert_eq!(abandoned.completed_by.map(|u| u.id), Some(test_user.id));
+                let response = post(&other_server, "/api/advance_round", draw.clone()).await;
+                response.assert_status(StatusCode::CONFLICT);
+                assert_eq!(response.json::<RoadieResult<TakenBagItem>>(), Err(RoadieAppError::DrawAlreadyFinished));
+
+                // Advancing past the last round finishes the draw
+                let response = post(&other_server, "/api/take_random", take.clone()).await;
+                let tbi = response.json::<RoadieResult<Option<TakenBagItem>>>().unwrap().unwrap();
+                let draw = qs::to_string(&AdvanceRound { bag_id: bag.id, draw_id: tbi.id })?;
+                for remaining in (0..tbi.rounds).rev() {
+                    let response = post(&other_server, "/api/advance_round", draw.clone()).await;
+                    let ended = response.json::<RoadieResult<TakenBagItem>>().unwrap();
+                    assert_eq!(ended.rounds_remaining, remaining);
+                    assert_eq!(ended.done, remaining == 0);
+                    assert_eq!(ended.abandoned, false);
+                    assert_eq!(ended.completed_by.map(|u| u.id), Some(other.id).filter(|_| remaining == 0));
+                }
+                assert_eq!(TakenBagItem::active_for(bag.id, other.id, &pool).await?, None);
+                let response = post(&other_server, "/api/advance_round", draw.clone()).await;
+                response.assert_status(StatusCode::CONFLICT);
+                let response = post(&other_server, "/api/complete_draw", draw).await;
+                response.assert_status(StatusCode::CONFLICT);
+                assert_eq!(response.json::<RoadieResult<TakenBagItem>>(), Err(RoadieAppError::DrawAlreadyFinished));
+
+                // Draws migrated with every round played can still be completed, but not advanced
+                let response = post(&other_server, "/api/take_random", take).await;
+                let tbi = response.json::<RoadieResult<Option<TakenBagItem>>>().unwrap().unwrap();
+                sqlx::query("UPDATE taken_items SET rounds_remaining = 0 WHERE id = ?")
+                    .bind(tbi.id)
+                    .execute(&pool)
+                    .await?;
+                let draw = qs::to_string(&CompleteDraw { bag_id: bag.id, draw_id: tbi.id })?;
+                let response = post(&other_server, "/api/advance_round", draw.clone()).await;
+                response.assert_status(StatusCode::CONFLICT);
+                assert_eq!(response.json::<RoadieResult<TakenBagItem>>(), Err(RoadieAppError::NoRoundsLeft));
+                let response = post(&other_server, "/api/complete_draw", draw).await;
+                assert_eq!(response.json::<RoadieResult<TakenBagItem>>().unwrap().done, true);
+                Ok(())
+            }
+
+            #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
+            #[sqlx::test]
+            async fn test_undo_draw(pool: SqlitePool) -> Result<()> {
+                let test_server = get_test_server(&pool).await?;
+                let owner = create_test_user(&test_server, Some("bagowner".into())).await;
+                let bag = create_test_bag(&owner, &pool).await?;
+                let player_server = get_test_server(&pool).await?;
+                let player = create_test_user(&player_server, Some("player".into())).await;
+                bag.set_role(player.id, Role::Player, &pool).await?;
+
+                let bi = BagItem {
+                    quantity: 2,
+                    ..create_test_item(bag.id, "Some item", &owner)
+                }.insert(&pool).await?;
+
+                let post = |server: &axum_test::TestServer, path: &str, body: String| {
+                    server.post(path)
+                        .text(body)
+                        .content_type("application/x-www-form-urlencoded")
+                };
+                let take = qs::to_string(&TakeRandom { bag_id: bag.id, tags: None })?;
+                let response = post(&player_server, "/api/take_random", take.clone()).await;
+                let tbi = response.json::<RoadieResult<Option<TakenBagItem>>>().unwrap().unwrap();
+                assert_eq!(BagItem::by_id(bi.id, &pool).await?.unwrap().quantity, 1);
+                let undo = qs::to_string(&UndoDraw { bag_id: bag.id, draw_id: tbi.id })?;
+
+                // Not even the bag's owner can take back someone else's draw
+                let response = post(&test_server, "/api/undo_draw", undo.clone()).await;
+                response.assert_status(StatusCode::FORBIDDEN);
+
+                let response = post(&player_server, "/api/undo_draw", undo.clone()).await;
+                assert_eq!(response.json::<RoadieResult<()>>(), Ok(()));
+                assert_eq!(TakenBagItem::by_id(tbi.id, &pool).await?, None);
+                assert_eq!(TakenBagItem::active_for(bag.id, player.id, &pool).await?, None);
+                assert_eq!(BagItem::by_id(bi.id, &pool).await?.unwrap().quantity, 2);
+                let response = post(&player_server, "/api/undo_draw", undo).await;
+                response.assert_status(StatusCode::NOT_FOUND);
+
+                // Only the latest draw can be undone
+                let response = post(&player_server, "/api/take_random", take.clone()).await;
+                let first = response.json::<RoadieResult<Option<TakenBagItem>>>().unwrap().unwrap();
+                first.complete(player.id, &pool).await?;
+                let response = post(&player_server, "/api/take_random", take.clone()).await;
+                let second = response.json::<RoadieResult<Option<TakenBagItem>>>().unwrap().unwrap();
+                let response = post(&player_server, "/api/undo_draw", qs::to_string(&UndoDraw { bag_id: bag.id, draw_id: first.id })?).await;
+                response.assert_status(StatusCode::CONFLICT);
+                assert_eq!(response.json::<RoadieResult<()>>(), Err(RoadieAppError::NotLatestDraw));
+                assert_eq!(BagItem::by_id(bi.id, &pool).await?.unwrap().quantity, 0);
+
+                // ...and only while the bag's undo window is open
+                sqlx::query("UPDATE taken_items SET extraction_time = datetime('now', '-2 minutes') WHERE id = ?")
+                    .bind(second.id)
+                    .execute(&pool)
+                    .await?;
+                let response = post(&player_server, "/api/undo_draw", qs::to_string(&UndoDraw { bag_id: bag.id, draw_id: second.id })?).await;
+                response.assert_status(StatusCode::CONFLICT);
+                assert_eq!(response.json::<RoadieResult<()>>(), Err(RoadieAppError::UndoWindowPassed));
+                assert!(TakenBagItem::by_id(second.id, &pool).await?.is_some());
+                second.complete(player.id, &pool).await?;
+
+                // Undo only puts back what the draw took, so nothing for an item that was
+                // infinite when it was drawn
+                let endless = BagItem {
+                    infinite: true,
+                    quantity: 0,
+                    ..create_test_item(bag.id, "Endless item", &owner)
+                }.insert(&pool).await?;
+                let drawn = TakenBagItem::get_random(bag.id, player.id, &DrawTags::default(), &pool).await??.unwrap();
+                assert_eq!(drawn.item.id, endless.id);
+                BagItem { infinite: false, ..drawn.item.clone() }.update(&pool).await?;
+                assert!(drawn.undo(&pool).await?);
+                assert_eq!(BagItem::by_id(endless.id, &pool).await?.unwrap().quantity, 0);
+
+                // ...and nothing once a restock has already refilled it
+                let rope = BagItem {
+                    par_level: Some(1),
+                    ..create_test_item(bag.id, "Rope", &owner)
+                }.insert(&pool).await?;
+                let drawn = TakenBagItem::get_random(bag.id, player.id, &DrawTags::default(), &pool).await??.unwrap();
+                assert_eq!(drawn.item.id, rope.id);
+                Restock::refill(&BagItemFilter::default(), Some(rope.id), owner.id, &pool).await?;
+                assert!(drawn.undo(&pool).await?);
+                assert_eq!(BagItem::by_id(rope.id, &pool).await?.unwrap().quantity, 1);
+                Ok(())
+            }
+
+            #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
+            #[sqlx::test]
+            async fn test_item_trash(pool: SqlitePool) -> Result<()> {
+                let test_server = get_test_server(&pool).await?;
+                let owner = create_test_user(&test_server, Some("bagowner".into())).await;
+                let bag = create_test_bag(&owner, &pool).await?;
+                let editor_server = get_test_server(&pool).await?;
+                let editor = create_test_user(&editor_server, Some("editor".into())).await;
+                bag.set_role(editor.id, Role::Editor, &pool).await?;
+
+                let bi = BagItem {
+                    quantity: 2,
+                    par_level: Some(2),
+                    ..create_test_item(bag.id, "Some item", &owner)
+                }.insert(&pool).await?;
+                let tbi = TakenBagItem::get_random(bag.id, owner.id, &DrawTags::default(), &pool).await??.unwrap();
+                tbi.complete(owner.id, &pool).await?;
+                Restock::refill(&BagItemFilter::default(), Some(bi.id), owner.id, &pool).await?;
+
+                let post = |server: &axum_test::TestServer, path: &str, body: String| {
+                    server.post(path)
+                        .text(body)
+                        .content_type("application/x-www-form-urlencoded")
+                };
+                let item = qs::to_string(&DeleteBagItem { bag_id: bag.id, id: bi.id })?;
+
+                // Items that aren't in the trash can't be restored or purged
+                let response = post(&test_server, "/api/purge_bag_item", item.clone()).await;
+                response.assert_status(StatusCode::NOT_FOUND);
+
+                let response = post(&test_server, "/api/delete_bag_item", item.clone()).await;
+                response.assert_status(StatusCode::OK);
+
+                // Trashed items are out of the list and the draws, but stay in the history
+                let filter = BagItemFilter { bag_id: Some(bag.id), ..Default::default() };
+                assert_eq!(BagItem::filter(filter.clone(), &pool).await?.items.len(), 0);
+                assert_eq!(TakenBagItem::get_random(bag.id, owner.id, &DrawTags::default(), &pool).await??, None);
+                let history = TakenBagItem::for_item(bi.id, &pool).await?;
+                assert_eq!(history.len(), 1);
+                assert_eq!(history[0].item.deleted_at.is_some(), true);
+                let response = post(&test_server, "/api/get_bag_item", qs::to_string(&GetBagItem { bag_id: bag.id, item_id: bi.id })?).await;
+                response.assert_status(StatusCode::NOT_FOUND);
+
+                let list = qs::to_string(&ListTrash { bag_id: bag.id })?;
+                let response = post(&test_server, "/api/list_trash", list).await;
+                let trash = response.json::<RoadieResult<Vec<BagItem>>>().unwrap();
+                assert_eq!(trash.iter().map(|t| t.id).collect::<Vec<_>>(), vec![bi.id]);
+
+                // Only the item's creator or the bag's owner can take it out of the trash
+                let response = post(&editor_server, "/api/restore_bag_item", item.clone()).await;
+                response.assert_status(StatusCode::FORBIDDEN);
+                let response = post(&editor_server, "/api/purge_bag_item", item.clone()).await;
+                response.assert_status(StatusCode::FORBIDDEN);
+
+                let response = post(&test_server, "/api/restore_bag_item", item.clone()).await;
+                assert_eq!(response.json::<RoadieResult<()>>(), Ok(()));
+                assert_eq!(BagItem::filter(filter, &pool).await?.items.len(), 1);
+                assert_eq!(BagItem::trash(bag.id, &pool).await?.len(), 0);
+
+                // Purging takes the history and restocks with it
+                let response = post(&test_server, "/api/delete_bag_item", item.clone()).await;
+                response.assert_status(StatusCode::OK);
+                let response = post(&test_server, "/api/purge_bag_item", item.clone()).await;
+                assert_eq!(response.json::<RoadieResult<()>>(), Ok(()));
+                assert_eq!(BagItem::by_id(bi.id, &pool).await?, None);
+                assert_eq!(TakenBagItem::for_item(bi.id, &pool).await?.len(), 0);
+                assert_eq!(Restock::for_bag(bag.id, 50, &pool).await?.len(), 0);
+                let response = post(&test_server, "/api/restore_bag_item", item).await;
+                response.assert_status(StatusCode::NOT_FOUND);
+                Ok(())
+            }
+
+            #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
+            #[sqlx::test]
+            async fn test_item_tags(pool: SqlitePool) -> Result<()> {
+                let test_server = get_test_server(&pool).await?;
+                let test_user = create_test_user(&test_server, None).await;
+                let bag = create_test_bag(&test_user, &pool).await?;
+
+                let goblin = BagItem {
+                    infinite: true,
+                    tags: parse_tags("Dungeon, monster, ,monster"),
+                    ..create_test_item(bag.id, "Goblin", &test_user)
+                }.insert(&pool).await?;
+                let gold = BagItem {
+                    infinite: true,
+                    tags: parse_tags("dungeon, treasure"),
+                    ..create_test_item(bag.id, "Gold", &test_user)
+                }.insert(&pool).await?;
+                let wolf = BagItem {
+                    infinite: true,
+                    tags: parse_tags(" Forest ,MONSTER"),
+                    ..create_test_item(bag.id, "Wolf", &test_user)
+                }.insert(&pool).await?;
+                assert_eq!(BagItem::by_id(goblin.id, &pool).await?.unwrap().tags, vec!["dungeon", "monster"]);
+                assert_eq!(BagItem::by_id(wolf.id, &pool).await?.unwrap().tags, vec!["forest", "monster"]);
+
+                let names = |tags: Vec<&str>| {
+                    let filter = BagItemFilter {
+                        bag_id: Some(bag.id),
+                        tags: Some(tags.into_iter().map(String::from).collect()),
+                        ..Default::default()
+                    };
+                    let pool = pool.clone();
+                    async move {
+                        let mut names = BagItem::filter(filter, &pool).await.unwrap()
+                            .items
+                            .into_iter()
+                            .map(|i| i.name)
+                            .collect::<Vec<_>>();
+                        names.sort();
+                        names
+                    }
+                };
+                assert_eq!(names(vec!["dungeon"]).await, vec!["Goblin", "Gold"]);
+                assert_eq!(names(vec!["Dungeon", "monster"]).await, vec!["Goblin"]);
+                assert_eq!(names(vec!["swamp"]).await, Vec::<String>::new());
+
+                let response = test_server.post("/api/list_tags")
+                    .text(qs::to_string(&ListTags { bag_id: bag.id })?)
+                    .content_type("application/x-www-form-urlencoded")
+                    .await;
+                assert_eq!(
+                    response.json::<RoadieResult<Vec<String>>>(),
+                    Ok(vec!["dungeon".into(), "forest".into(), "monster".into(), "treasure".into()])
+                );
+
+                // Draws only come from items with every wanted tag and none of the unwanted ones
+                let tags = |with_tags: Vec<&str>, without_tags: Vec<&str>| DrawTags {
+                    with_tags: with_tags.into_iter().map(String::from).collect(),
+                    without_tags: without_tags.into_iter().map(String::from).collect()
+                };
+                for _ in 0..10 {
+                    let tbi = TakenBagItem::get_random(bag.id, test_user.id, &tags(vec![], vec!["monster"]), &pool).await??.unwrap();
+                    assert_eq!(tbi.item.id, gold.id);
+                    tbi.complete(test_user.id, &pool).await?;
+                }
+                assert_eq!(TakenBagItem::get_random(bag.id, test_user.id, &tags(vec!["forest"], vec!["monster"]), &pool).await??, None);
+
+                let take = TakeRandom { bag_id: bag.id, tags: Some(tags(vec!["monster", "forest"], vec![])) };
+                let response = test_server.post("/api/take_random")
+                    .text(qs::to_string(&take)?)
+                    .content_type("application/x-www-form-urlencoded")
+                    .await;
+                let tbi = response.json::<RoadieResult<Option<TakenBagItem>>>().unwrap().unwrap();
+                assert_eq!(tbi.item.id, wolf.id);
+
+                // Tags nothing uses anymore go away
+                let mut wolf = BagItem::by_id(wolf.id, &pool).await?.unwrap();
+                wolf.tags = vec![];
+                wolf.update(&pool).await?;
+                assert_eq!(Tag::for_bag(bag.id, &pool).await?, vec!["dungeon", "monster", "treasure"]);
+
+                let form = BagItemForm {
+                    name: "Some item".into(),
+                    size: Some(ItemSize::Small),
+                    tags: "fine, this-tag-is-far-too-long-to-be-useful".into(),
+                    ..Default::default()
+                };
+                match form.validate() {
+                    Some(RoadieAppError::MultipleErrors(errors)) => assert_eq!(errors.contains_key("tags"), true),
+                    other => panic!("Expected a tags error, got {:?}", other)
+                }
+                Ok(())
+            }
+
+            #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
+            #[sqlx::test]
+            async fn test_item_search(pool: SqlitePool) -> Result<()> {
+                let test_server = get_test_server(&pool).await?;
+                let test_user = create_test_user(&test_server, None).await;
+                let bag = create_test_bag(&test_user, &pool).await?;
+
+                let item = |name: &str, description: &str| BagItem {
+                    description: description.into(),
+                    infinite: true,
+                    ..create_test_item(bag.id, name, &test_user)
+                };
+                let shield = item("Shield", "Blocks arrows, spears, clubs and the odd swordsman on a good day").insert(&pool).await?;
+                let sword = item("Sword", "A sword for sword fights").insert(&pool).await?;
+                let potion = item("Potion", "Heals a little").insert(&pool).await?;
+
+                let search = |q: &str| {
+                    let filter = BagItemFilter {
+                        bag_id: Some(bag.id),
+                        q: Some(q.into()),
+                        ..Default::default()
+                    };
+                    let pool = pool.clone();
+                    async move {
+                        let page = BagItem::filter(filter, &pool).await.unwrap();
+                        assert_eq!(page.total_results, page.items.len() as u64);
+                        page.items.into_iter().map(|i| i.id).collect::<Vec<_>>()
+                    }
+                };
+                // Words match on their own and as prefixes, best matches first
+                assert_eq!(search("sword").await, vec![sword.id, shield.id]);
+                assert_eq!(search("SWO").await, vec![sword.id, shield.id]);
+                assert_eq!(search("heals little").await, vec![potion.id]);
+                assert_eq!(search("heals sword").await, Vec::<i64>::new());
+                // Nothing typed means no search
+                assert_eq!(search("  ").await.len(), 3);
+                // FTS5 syntax is taken literally
+                assert_eq!(search("\"sword -").await, vec![sword.id, shield.id]);
+
+                // The index follows updates, the trash and deletes
+                let mut potion = BagItem::by_id(potion.id, &pool).await?.unwrap();
+                potion.name = "Sword oil".into();
+                potion.update(&pool).await?;
+                assert_eq!(search("heals").await, vec![potion.id]);
+                assert_eq!(search("oil").await, vec![potion.id]);
+                BagItem::by_id(sword.id, &pool).await?.unwrap().delete(&pool).await?;
+                assert_eq!(search("sword").await.contains(&sword.id), false);
+                BagItem::by_id(sword.id, &pool).await?.unwrap().purge(&pool).await?;
+                assert_eq!(search("fights").await, Vec::<i64>::new());
+                Ok(())
+            }
+
+            #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
+            #[sqlx::test]
+            async fn test_item_sort(pool: SqlitePool) -> Result<()> {
+                let test_server = get_test_server(&pool).await?;
+                let test_user = create_test_user(&test_server, None).await;
+                let bag = create_test_bag(&test_user, &pool).await?;
+
+                let item = |name: &str, quantity: i32, size: ItemSize| BagItem {
+                    quantity,
+                    size,
+                    ..create_test_item(bag.id, name, &test_user)
+                };
+                for (name, quantity, size) in [
+                    ("Bow", 2, ItemSize::Medium),
+                    ("Axe", 2, ItemSize::Large),
+                    ("Dagger", 5, ItemSize::Small),
+                    ("Club", 1, ItemSize::Medium)
+                ] {
+                    item(name, quantity, size).insert(&pool).await?;
+                }
+
+                let sorted = |sort: Vec<(ItemSortColumn, SortDirection)>, page_size: u64, page_num: u64| {
+                    let filter = BagItemFilter {
+                        bag_id: Some(bag.id),
+                        sort: Some(sort.into_iter().map(|(column, direction)| ItemSort { column, direction }).collect()),
+                        page_size: Some(page_size),
+                        page_num: Some(page_num),
+                        ..Default::default()
+                    };
+                    let pool = pool.clone();
+                    async move {
+                        BagItem::filter(filter, &pool).await.unwrap()
+                            .items
+                            .into_iter()
+                            .map(|i| i.name)
+                            .collect::<Vec<_>>()
+                    }
+                };
+                use ItemSortColumn::*;
+                use SortDirection::*;
+                assert_eq!(sorted(vec![(Name, Asc)], 50, 1).await, vec!["Axe", "Bow", "Club", "Dagger"]);
+                assert_eq!(sorted(vec![(Quantity, Desc), (Name, Asc)], 50, 1).await, vec!["Dagger", "Axe", "Bow", "Club"]);
+                assert_eq!(sorted(vec![(Size, Asc), (Quantity, Asc)], 50, 1).await, vec!["Dagger", "Club", "Bow", "Axe"]);
+                // The sort covers every page, not just the one being looked at
+                assert_eq!(sorted(vec![(Name, Desc)], 2, 2).await, vec!["Bow", "Axe"]);
+                // Without a sort key the newest items come first
+                assert_eq!(sorted(vec![], 50, 1).await, vec!["Club", "Dagger", "Axe", "Bow"]);
+
+                // Header clicks cycle through ascending, descending and off, newest key first
+                let filter = BagItemFilter { page_num: Some(3), ..Default::default() };
+                let filter = filter.with_sort_toggled(Name);
+                assert_eq!(filter.page_num, Some(1));
+                assert_eq!(filter.sort_direction(Name), Some(Asc));
+                let filter = filter.with_sort_toggled(Quantity);
+                assert_eq!(filter.sort, Some(vec![ItemSort { column: Quantity, direction: Asc }, ItemSort { column: Name, direction: Asc }]));
+                let filter = filter.with_sort_toggled(Quantity);
+                assert_eq!(filter.sort_direction(Quantity), Some(Desc));
+                let filter = filter.with_sort_toggled(Quantity);
+                assert_eq!(filter.sort, Some(vec![ItemSort { column: Name, direction: Asc }]));
+                let filter = filter.with_sort_toggled(Name).with_sort_toggled(Name);
+                assert_eq!(filter.sort, None);
+                let filter = filter.with_sort_toggled(Name).with_sort_toggled(Size).with_sort_toggled(Quantity).with_sort_toggled(AddedBy);
+                assert_eq!(filter.sort.as_ref().map(|s| s.len()), Some(MAX_SORT_KEYS));
+                assert_eq!(filter.sort_direction(Name), None);
+
+                // Sort keys survive the query string
+                let qs_config = qs::Config::new(0, false);
+                let parsed = qs_config.deserialize_str::<BagItemFilter>(&qs::to_string(&filter)?)?;
+                assert_eq!(parsed, filter);
+                Ok(())
+            }
+
+            #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
+            #[sqlx::test]
+            async fn test_cursor_pagination(pool: SqlitePool) -> Result<()> {
+                let test_server = get_test_server(&pool).await?;
+                let test_user = create_test_user(&test_server, None).await;
+                let bag = create_test_bag(&test_user, &pool).await?;
+
+                let item = |name: &str, quantity: i32| BagItem {
+                    quantity,
+                    ..create_test_item(bag.id, name, &test_user)
+                };
+                for (name, quantity) in [("b", 1), ("c", 2), ("d", 1), ("e", 2), ("f", 1), ("g", 2), ("h", 1)] {
+                    item(name, quantity).insert(&pool).await?;
+                }
+
+                let base = BagItemFilter {
+                    bag_id: Some(bag.id),
+                    paging: Some(PagingMode::Cursor),
+                    sort: Some(vec![
+                        ItemSort { column: ItemSortColumn::Quantity, direction: SortDirection::Desc },
+                        ItemSort { column: ItemSortColumn::Name, direction: SortDirection::Asc }
+                    ]),
+                    page_size: Some(3),
+                    ..Default::default()
+                };
+                let names = |page: &BagItemPage| page.items.iter().map(|i| i.name.clone()).collect::<Vec<_>>();
+
+                let first = BagItem::filter(base.clone(), &pool).await?;
+                assert_eq!(names(&first), vec!["c", "e", "g"]);
+                assert_eq!(first.prev_cursor, None);
+                assert_eq!(first.total_results, 0);
+
+                // Items added in front of the cursor don't shift the next page
+                item("a", 2).insert(&pool).await?;
+                let second = BagItem::filter(base.with_cursors(first.next_cursor.clone(), None), &pool).await?;
+                assert_eq!(names(&second), vec!["b", "d", "f"]);
+                let third = BagItem::filter(base.with_cursors(second.next_cursor.clone(), None), &pool).await?;
+                assert_eq!(names(&third), vec!["h"]);
+                assert_eq!(third.next_cursor, None);
+
+                // Going back picks up the new item
+                let back = BagItem::filter(base.with_cursors(None, third.prev_cursor.clone()), &pool).await?;
+                assert_eq!(names(&back), vec!["b", "d", "f"]);
+                let back = BagItem::filter(base.with_cursors(None, back.prev_cursor.clone()), &pool).await?;
+                assert_eq!(names(&back), vec!["c", "e", "g"]);
+                assert_eq!(back.next_cursor.is_some(), true);
+                let back = BagItem::filter(base.with_cursors(None, back.prev_cursor.clone()), &pool).await?;
+                assert_eq!(names(&back), vec!["a"]);
+                assert_eq!(back.prev_cursor, None);
+
+                // Without a sort the newest items come first, and new items stay out of later pages
+                let newest = BagItemFilter { sort: None, ..base.clone() };
+                let first = BagItem::filter(newest.clone(), &pool).await?;
+                assert_eq!(names(&first), vec!["a", "h", "g"]);
+                item("i", 1).insert(&pool).await?;
+                let second = BagItem::filter(newest.with_cursors(first.next_cursor.clone(), None), &pool).await?;
+                assert_eq!(names(&second), vec!["f", "e", "d"]);
+
+                // A cursor from another sort order starts over
+                let restarted = BagItem::filter(base.with_cursors(second.next_cursor.clone(), None), &pool).await?;
+                assert_eq!(names(&restarted), vec!["a", "c", "e"]);
+                let garbled = BagItem::filter(base.with_cursors(Some("zz".into()), None), &pool).await?;
+                assert_eq!(names(&garbled), vec!["a", "c", "e"]);
+
+                // Offset paging still counts
+                let offset = BagItem::filter(BagItemFilter { paging: None, ..base.with_page(3) }, &pool).await?;
+                assert_eq!(names(&offset), vec!["f", "h", "i"]);
+                assert_eq!(offset.total_results, 9);
+                assert_eq!(offset.next_cursor, None);
+                Ok(())
+            }
+
+            #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
+            #[sqlx::test]
+            async fn test_item_import(pool: SqlitePool) -> Result<()> {
+                let test_server = get_test_server(&pool).await?;
+                let test_user = create_test_user(&test_server, None).await;
+                let bag = create_test_bag(&test_user, &pool).await?;
+                create_test_item(bag.id, "Rope", &test_user).insert(&pool).await?;
+
+                let import = |format: ImportFormat, contents: &str, dry_run: Option<bool>, skip_duplicates: Option<bool>| {
+                    let ib = ImportBagItems {
+                        bag_id: bag.id,
+                        format,
+                        contents: contents.into(),
+                        dry_run,
+                        skip_duplicates
+                    };
+                    test_server.post("/api/import_bag_items")
+                        .text(qs::to_string(&ib).unwrap())
+                        .content_type("application/x-www-form-urlencoded")
+                };
+                let names = || async {
+                    let mut names = BagItem::names(bag.id, &pool).await.unwrap();
+                    names.sort();
+                    names
+                };
+
+                let csv = "Name,Description,Quantity,Size,Infinite\n\
+                    rope,Hemp,2,small,\n\
+                    Torch,,3,Medium,true\n\
+                    Potion,\"Heals, a bit\",1,LARGE,false\n";
+
+                // A dry run reports what would happen without adding anything
+                let response = import(ImportFormat::Csv, csv, Some(true), Some(true)).await;
+                response.assert_status_ok();
+                let report = response.json::<RoadieResult<ImportReport>>().unwrap();
+                assert_eq!(report, ImportReport {
+                    imported: vec!["Torch".into(), "Potion".into()],
+                    skipped: vec!["rope".into()],
+                    dry_run: true
+                });
+                assert_eq!(names().await, vec!["Rope"]);
+
+                let response = import(ImportFormat::Csv, csv, None, Some(true)).await;
+                response.assert_status_ok();
+                assert_eq!(response.json::<RoadieResult<ImportReport>>().unwrap().dry_run, false);
+                assert_eq!(names().await, vec!["Potion", "Rope", "Torch"]);
+                let page = BagItem::filter(BagItemFilter {
+                    bag_id: Some(bag.id),
+                    q: Some("Potion".into()),
+                    ..Default::default()
+                }, &pool).await?;
+                assert_eq!(page.items[0].description, "Heals, a bit");
+                assert_eq!(page.items[0].size, ItemSize::Large);
+                assert_eq!(page.items[0].added_by.id, test_user.id);
+
+                // Without skipping, duplicate names are added like they would be through the form
+                let json = r#"[{"name": "Lantern", "quantity": 1, "size": "small", "tags": "light"}, {"name": "Torch", "quantity": 1, "size": "small"}]"#;
+                let response = import(ImportFormat::Json, json, None, None).await;
+                response.assert_status_ok();
+                assert_eq!(names().await, vec!["Lantern", "Potion", "Rope", "Torch", "Torch"]);
+                assert_eq!(Tag::for_bag(bag.id, &pool).await?, vec!["light"]);
+
+                // One bad row stops the whole import, and every bad row is reported
+                let bad_csv = "name,quantity,size\n,1,small\nSword,lots,small\nShield,1,huge\nBow,1,large\n";
+                let response = import(ImportFormat::Csv, bad_csv, None, None).await;
+                response.assert_status(StatusCode::EXPECTATION_FAILED);
+                match response.json::<RoadieResult<ImportReport>>() {
+                    Err(RoadieAppError::MultipleErrors(errors)) => {
+                        let mut rows: Vec<_> = errors.keys().cloned().collect();
+                        rows.sort();
+                        assert_eq!(rows, vec!["row 1", "row 2", "row 3"]);
+                        assert_eq!(errors["row 1"], format!("name: {}", RoadieAppError::ItemNameNonEmpty));
+                        assert_eq!(errors["row 3"], format!("size: {}", RoadieAppError::ItemSizeMustBeSet));
+                    }
+                    other => panic!("Expected row errors, got {:?}", other)
+                }
+                assert_eq!(names().await.len(), 5);
+
+                let response = import(ImportFormat::Json, "{\"name\": \"Bow\"}", None, None).await;
+                response.assert_status(StatusCode::EXPECTATION_FAILED);
+                match response.json::<RoadieResult<ImportReport>>() {
+                    Err(RoadieAppError::MultipleErrors(errors)) => assert!(errors.contains_key("file")),
+                    other => panic!("Expected a file error, got {:?}", other)
+                }
+                let response = import(ImportFormat::Csv, "name,quantity,size\n", None, None).await;
+                assert_eq!(
+                    response.json::<RoadieResult<ImportReport>>(),
+                    Err(RoadieAppError::MultipleErrors([("file".to_string(), RoadieAppError::ImportEmpty.to_string())].into()))
+                );
+
+                // Viewers can't import
+                let viewer = create_test_user(&test_server, Some("viewer".into())).await;
+                bag.set_role(viewer.id, Role::Viewer, &pool).await?;
+                let response = import(ImportFormat::Json, json, None, None).await;
+                response.assert_status(StatusCode::FORBIDDEN);
+                assert_eq!(names().await.len(), 5);
+                Ok(())
+            }
+
+            #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
+            #[sqlx::test]
+            async fn test_export(pool: SqlitePool) -> Result<()> {
+                let test_server = get_test_server(&pool).await?;
+                let test_user = create_test_user(&test_server, None).await;
+                let bag = create_test_bag(&test_user, &pool).await?;
+
+                let item = |name: String, quantity: i32, tags: Vec<String>| BagItem {
+                    description: format!("All about {}", name),
+                    quantity,
+                    size: ItemSize::Medium,
+                    tags,
+                    ..create_test_item(bag.id, &name, &test_user)
+                };
+                // More than one page's worth, so the export has to stream several of them
+                let mut items = (0..520).map(|i| item(format!("Item {:03}", i), 1, vec![])).collect::<Vec<_>>();
+                items.push(item("Sword".into(), 2, vec!["weapon".into()]));
+                items.push(item("Axe".into(), 3, vec!["weapon".into(), "heavy".into()]));
+                items.push(item("Broken bow".into(), 0, vec!["weapon".into()]));
+                BagItem::insert_many(items, &pool).await?;
+                let drawn = TakenBagItem::get_random(bag.id, test_user.id, &DrawTags::default(), &pool).await??.unwrap();
+
+                let response = test_server.get(&format!("/export/bag/{}/items/csv", bag.id)).await;
+                response.assert_status_ok();
+                assert_eq!(response.header("content-type"), "text/csv; charset=utf-8");
+                assert_eq!(
+                    response.header("content-disposition"),
+                    format!("attachment; filename=\"bag-{}-items.csv\"", bag.id).as_str()
+                );
+                let csv = response.text();
+                assert!(csv.starts_with("id,name,description,quantity,size"));
+                let records = csv::Reader::from_reader(csv.as_bytes())
+                    .deserialize::<ItemRecord>()
+                    .collect::<Result<Vec<_>, _>>()?;
+                assert_eq!(records.len(), 523);
+                let mut ids = records.iter().map(|r| r.id).collect::<Vec<_>>();
+                ids.dedup();
+                assert_eq!(ids.len(), 523);
+
+                // The filter and sort from the item list carry over
+                let filter = BagItemFilter {
+                    tags: Some(vec!["weapon".into()]),
+                    sort: Some(vec![ItemSort { column: ItemSortColumn::Quantity, direction: SortDirection::Desc }]),
+                    page_num: Some(3),
+                    ..Default::default()
+                };
+                let response = test_server.get(&items_export_href(bag.id, ExportFormat::Json, &filter)).await;
+                response.assert_status_ok();
+                let records = response.json::<Vec<ItemRecord>>();
+                assert_eq!(records.iter().map(|r| r.name.as_str()).collect::<Vec<_>>(), vec!["Axe", "Sword", "Broken bow"]);
+                assert_eq!(records[0].tags, "heavy, weapon");
+                assert_eq!(records[0].added_by, test_user.username);
+
+                let response = test_server.get(&items_export_href(bag.id, ExportFormat::Ndjson, &filter)).await;
+                response.assert_status_ok();
+                let records = response.text()
+                    .lines()
+                    .map(serde_json::from_str::<ItemRecord>)
+                    .collect::<Result<Vec<_>, _>>()?;
+                assert_eq!(records.len(), 3);
+
+                let filter = BagItemFilter { q: Some("nothing like this".into()), ..Default::default() };
+                let response = test_server.get(&items_export_href(bag.id, ExportFormat::Json, &filter)).await;
+                assert_eq!(response.json::<Vec<ItemRecord>>(), vec![]);
+
+                // An item export can be imported into another bag, including items that have run out
+                let other_bag = create_test_bag(&test_user, &pool).await?;
+                let filter = BagItemFilter { tags: Some(vec!["weapon".into()]), ..Default::default() };
+                let csv = test_server.get(&items_export_href(bag.id, ExportFormat::Csv, &filter)).await.text();
+                let ib = ImportBagItems {
+                    bag_id: other_bag.id,
+                    format: ImportFormat::Csv,
+                    contents: csv,
+                    dry_run: None,
+                    skip_duplicates: None
+                };
+                let response = test_server.post("/api/import_bag_items")
+                    .text(qs::to_string(&ib)?)
+                    .content_type("application/x-www-form-urlencoded")
+                    .await;
+                response.assert_status_ok();
+                let mut names = BagItem::names(other_bag.id, &pool).await?;
+                names.sort();
+                assert_eq!(names, vec!["Axe", "Broken bow", "Sword"]);
+                assert_eq!(Tag::for_bag(other_bag.id, &pool).await?, vec!["heavy", "weapon"]);
+
+                let response = test_server.get(&history_export_href(bag.id, ExportFormat::Json, &TakenItemFilter::default())).await;
+                response.assert_status_ok();
+                let draws = response.json::<Vec<DrawRecord>>();
+                assert_eq!(draws.len(), 1);
+                assert_eq!(draws[0].id, drawn.id);
+                assert_eq!(draws[0].item, drawn.item.name);
+                assert_eq!(draws[0].drawn_by, Some(test_user.username.clone()));
+                let filter = TakenItemFilter { done: Some(true), ..Default::default() };
+                let response = test_server.get(&history_export_href(bag.id, ExportFormat::Csv, &filter)).await;
+                // A CSV export still has its header when nothing matches
+                assert_eq!(response.text(), format!("{}\n", DrawRecord::COLUMNS.join(",")));
+
+                test_server.get(&format!("/export/bag/{}/items/xml", bag.id)).await.assert_status_not_ok();
+                test_server.get("/export/bag/9999/items/csv").await.assert_status(StatusCode::NOT_FOUND);
+
+                // Only people who can see the bag can export it
+                create_test_user(&test_server, Some("outsider".into())).await;
+                test_server.get(&format!("/export/bag/{}/items/csv", bag.id)).await.assert_status(StatusCode::FORBIDDEN);
+                test_server.get(&format!("/export/bag/{}/history/csv", bag.id)).await.assert_status(StatusCode::FORBIDDEN);
+                test_server.post("/api/auth_logout").await;
+                test_server.get(&format!("/export/bag/{}/items/csv", bag.id)).await.assert_status(StatusCode::UNAUTHORIZED);
+                Ok(())
+            }
+
+            async fn empty_pool() -> Result<SqlitePool> {
+                // A single connection, since every connection to :memory: gets its own database
+                let pool = sqlx::sqlite::SqlitePoolOptions::new()
+                    .max_connections(1)
+                    .connect("sqlite::memory:")
+                    .await?;
+                sqlx::migrate!().run(&pool).await?;
+                Ok(pool)
+            }
+
+            #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
+            #[sqlx::test]
+            async fn test_backup_round_trip(pool: SqlitePool) -> Result<()> {
+                let test_server = get_test_server(&pool).await?;
+                let player = create_test_user(&test_server, Some("player".into())).await;
+                let test_user = create_test_user(&test_server, None).await;
+                let bag = create_test_bag(&test_user, &pool).await?;
+                bag.set_role(player.id, Role::Player, &pool).await?;
+
+                BagItem::insert_many(vec![
+                    BagItem {
+                        par_level: Some(3),
+                        tags: vec!["weapon".into()],
+                        ..create_test_item(bag.id, "Sword", &test_user)
+                    },
+                    BagItem {
+                        quantity: 2,
+                        tags: vec!["weapon".into(), "heavy".into()],
+                        ..create_test_item(bag.id, "Axe", &test_user)
+                    },
+                    BagItem {
+                        quantity: 4,
+                        ..create_test_item(bag.id, "Rope", &test_user)
+                    },
+                ], &pool).await?;
+                TakenBagItem::get_random(bag.id, player.id, &DrawTags::default(), &pool).await??.unwrap();
+                TakenBagItem::get_random(bag.id, test_user.id, &DrawTags::default(), &pool).await??.unwrap();
+                let filter = BagItemFilter { bag_id: Some(bag.id), ..Default::default() };
+                Restock::refill(&filter, None, test_user.id, &pool).await?;
+
+                let backup = Backup::create(BackupScope::Instance, true, &pool).await?;
+                assert_eq!(backup.schema_version, schema_version());
+                assert_eq!(backup.users.len(), 2);
+                assert_eq!(backup.items.len(), 3);
+                assert_eq!(backup.draws.len(), 2);
+                assert_eq!(backup.restocks.len(), 1);
+                assert!(backup.users.iter().all(|u| u.password.is_some()));
+                let mut file = vec![];
+                backup.write(&mut file)?;
+                assert_eq!(Backup::read(file.as_slice())?, backup);
+
+                // Into an empty database, everything comes back as it was
+                let restored = empty_pool().await?;
+                let report = backup.restore(ConflictPolicy::Fail, &restored).await?;
+                assert_eq!(report.users_created, 2);
+                assert_eq!(report.bags_restored, 1);
+                assert_eq!((report.items, report.draws, report.restocks), (3, 2, 1));
+                let copy = Backup::create(BackupScope::Instance, true, &restored).await?;
+                assert_eq!(copy.users, backup.users);
+                // Site wide tokens like admin stay behind, only bag roles are restored
+                let bag_permissions = backup.permissions.iter()
+                    .filter(|p| Role::from_token(&p.token).is_some())
+                    .cloned()
+                    .collect::<Vec<_>>();
+                assert_eq!(copy.permissions, bag_permissions);
+                assert_eq!(copy.bags, backup.bags);
+                assert_eq!(copy.items, backup.items);
+                assert_eq!(copy.draws, backup.draws);
+                assert_eq!(copy.restocks, backup.restocks);
+                assert!(has_role_in(&restored, player.id, bag.id, Role::Player).await?);
+
+                // Restoring over itself depends on the conflict policy
+                assert!(matches!(
+                    backup.restore(ConflictPolicy::Fail, &restored).await,
+                    Err(BackupError::Conflict(_))
+                ));
+                let report = backup.restore(ConflictPolicy::Skip, &restored).await?;
+                assert_eq!((report.users_matched, report.bags_skipped, report.items), (2, 1, 0));
+                let report = backup.restore(ConflictPolicy::Replace, &restored).await?;
+                assert_eq!((report.users_matched, report.bags_replaced, report.items), (2, 1, 3));
+                let copy = Backup::create(BackupScope::Instance, true, &restored).await?;
+                assert_eq!(copy.items, backup.items);
+                assert_eq!(copy.draws, backup.draws);
+
+                // A bag backup leaves passwords out unless asked, and its ids are moved aside
+                // when the database already uses them
+                let backup = Backup::create(BackupScope::Bag(bag.id), false, &pool).await?;
+                assert!(!backup.includes_passwords);
+                assert!(backup.users.iter().all(|u| u.password.is_none()));
+                let restored = empty_pool().await?;
+                let someone = SQLUser::create("someone".into(), "5678".into(), &restored).await?;
+                sqlx::query("INSERT INTO bags (id, created_by, name, description) VALUES (?, ?, 'Other', '')")
+                    .bind(bag.id)
+                    .bind(someone)
+                    .execute(&restored)
+                    .await?;
+                let sword = backup.items.iter().find(|i| i.name == "Sword").unwrap();
+                sqlx::query("INSERT INTO bagitems (id, bag_id, added_by, name, description) VALUES (?, ?, ?, 'Lantern', '')")
+                    .bind(sword.id)
+                    .bind(bag.id)
+                    .bind(someone)
+                    .execute(&restored)
+                    .await?;
+                let report = backup.restore(ConflictPolicy::Fail, &restored).await?;
+                assert_eq!((report.users_created, report.bags_restored, report.items), (2, 1, 3));
+
+                let copy = Backup::create(BackupScope::Instance, false, &restored).await?;
+                let new_bag = copy.bags.iter().find(|b| b.name == bag.name).unwrap();
+                assert_ne!(new_bag.id, bag.id);
+                let items = copy.items.iter().filter(|i| i.bag_id == new_bag.id).collect::<Vec<_>>();
+                assert_eq!(items.len(), 3);
+                let new_sword = items.iter().find(|i| i.name == "Sword").unwrap();
+                assert_ne!(new_sword.id, sword.id);
+                assert_eq!(new_sword.tags, vec!["weapon"]);
+                for draw in copy.draws.iter() {
+                    assert_eq!(draw.bag_id, new_bag.id);
+                    assert!(items.iter().any(|i| i.id == draw.item_id));
+                    let candidates: Vec<DrawCandidate> = serde_json::from_str(draw.draw_pool.as_ref().unwrap())?;
+                    assert!(candidates.iter().all(|c| items.iter().any(|i| i.id == c.item_id)));
+                }
+                assert_eq!(copy.restocks[0].item_id, new_sword.id);
+                let new_player = SQLUser::by_username("player".into(), &restored).await?.unwrap();
+                assert!(has_role_in(&restored, new_player.id, new_bag.id, Role::Player).await?);
+                // Without a hash the old password doesn't work any more
+                let new_user = SQLUser::by_username(test_user.username.clone(), &restored).await?.unwrap();
+                assert!(!bcrypt::verify("1234", &new_user.password)?);
+
+                // Only backups this build understands are read
+                assert!(matches!(Backup::read("{}".as_bytes()), Err(BackupError::NotABackup)));
+                let newer = Backup { schema_version: schema_version() + 1, ..backup.clone() };
+                let mut file = vec![];
+                newer.write(&mut file)?;
+                assert!(matches!(Backup::read(file.as_slice()), Err(BackupError::NewerSchema { .. })));
+                let newer = Backup { format_version: BACKUP_FORMAT_VERSION + 1, ..backup };
+                let mut file = vec![];
+                newer.write(&mut file)?;
+                assert!(matches!(Backup::read(file.as_slice()), Err(BackupError::UnsupportedFormat(_))));
+                Ok(())
+            }
+
+            #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
+            #[sqlx::test]
+            async fn test_restore_skips_admin(pool: SqlitePool) -> Result<()> {
+                let test_server = get_test_server(&pool).await?;
+                let admin = create_test_user(&test_server, Some("someone".into())).await;
+                assert!(SQLUserPermission::exists(admin.id, ADMIN_TOKEN, &pool).await?);
+                let bag = create_test_bag(&admin, &pool).await?;
+                let backup = Backup::create(BackupScope::Instance, false, &pool).await?;
+                assert!(backup.permissions.iter().any(|p| p.token == ADMIN_TOKEN));
+
+                // Somebody else signed up as "someone" on the instance being restored into
+                let restored = empty_pool().await?;
+                let someone = SQLUser::create("someone".into(), "5678".into(), &restored).await?;
+                let report = backup.restore(ConflictPolicy::Skip, &restored).await?;
+                assert_eq!(report.users_matched, 1);
+                assert!(!SQLUserPermission::exists(someone, ADMIN_TOKEN, &restored).await?);
+                let copy = Backup::create(BackupScope::Instance, false, &restored).await?;
+                let new_bag = copy.bags.iter().find(|b| b.name == bag.name).unwrap();
+                assert!(has_role_in(&restored, someone, new_bag.id, Role::Owner).await?);
+                Ok(())
+            }
+
+            async fn has_role_in(pool: &SqlitePool, user_id: i64, bag_id: i64, role: Role) -> Result<bool> {
+                let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM user_permissions WHERE user_id = ? AND token = ?")
+                    .bind(user_id)
+                    .bind(role.token(bag_id))
+                    .fetch_one(pool)
+                    .await?;
+                Ok(count > 0)
+            }
+
+            #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
+            #[sqlx::test]
+            async fn test_bag_roles(pool: SqlitePool) -> Result<()> {
+                let test_server = get_test_server(&pool).await?;
+                let owner = create_test_user(&test_server, Some("bagowner".into())).await;
+                let bag = create_test_bag(&owner, &pool).await?;
+                assert_eq!(bag.role_of(owner.id, &pool).await?, Some(Role::Owner));
+                assert_eq!(Role::from_token(&Role::Editor.token(bag.id)), Some((bag.id, Role::Editor)));
+
+                let member = create_test_user(&test_server, None).await;
+
+                // Not a member yet, so the bag is invisible and off limits
+                let response = test_server.post("/api/list_bags").await;
+                let res = response.json::<RoadieResult<Vec<Bag>>>();
+                assert_eq!(res.unwrap().len(), 0);
+                let response = test_server.post("/api/get_bag")
+                    .text(qs::to_string(&GetBag { bag_id: bag.id })?)
+                    .content_type("application/x-www-form-urlencoded")
+                    .await;
+                response.assert_status(StatusCode::FORBIDDEN);
+
+                bag.set_role(member.id, Role::Viewer, &pool).await?;
+                let response = test_server.post("/api/list_bags").await;
+                let res = response.json::<RoadieResult<Vec<Bag>>>();
+                assert_eq!(res.unwrap().len(), 1);
+
+                let tr = qs::to_string(&TakeRandom { bag_id: bag.id, tags: None })?;
+                let response = test_server.post("/api/take_random")
+                    .text(tr.clone())
+                    .content_type("application/x-www-form-urlencoded")
+                    .await;
+                response.assert_status(StatusCode::FORBIDDEN);
+                assert_eq!(response.json::<RoadieResult<Option<TakenBagItem>>>(), Err(RoadieAppError::Unauthorized));
+
+                bag.set_role(member.id, Role::Player, &pool).await?;
+                let response = test_server.post("/api/take_random")
+                    .text(tr)
+                    .content_type("application/x-www-form-urlencoded")
+                    .await;
+                response.assert_status(StatusCode::OK);
+
+                let bi = CreateUpdateBagItem {
+                    bag_id: bag.id,
+                    item: BagItemForm {
+                        name: "Some item".into(),
+                        size: Some(ItemSize::Small),
+                        ..Default::default()
+                    }
+                };
+                let response = test_server.post("/api/create_update_bag_item")
+                    .text(qs::to_string(&bi)?)
+                    .content_type("application/x-www-form-urlencoded")
+                    .await;
+                response.assert_status(StatusCode::FORBIDDEN);
+
+                // Only owners manage members
+                let gr = GrantRole {
+                    bag_id: bag.id,
+                    username: member.username.clone(),
+                    role: Role::Owner
+                };
+                let response = test_server.post("/api/grant_role")
+                    .text(qs::to_string(&gr)?)
+                    .content_type("application/x-www-form-urlencoded")
+                    .await;
+                response.assert_status(StatusCode::FORBIDDEN);
+                assert_eq!(bag.role_of(member.id, &pool).await?, Some(Role::Player));
+
+                // The owner can't leave the bag without an owner
+                let new_owner = create_test_user(&test_server, Some("bagowner2".into())).await;
+                bag.set_role(new_owner.id, Role::Owner, &pool).await?;
+                bag.remove_member(owner.id, &pool).await?;
+                let rr = RevokeRole {
+                    bag_id: bag.id,
+                    user_id: new_owner.id
+                };
+                let response = test_server.post("/api/revoke_role")
+                    .text(qs::to_string(&rr)?)
+                    .content_type("application/x-www-form-urlencoded")
+                    .await;
+                response.assert_status(StatusCode::BAD_REQUEST);
+                assert_eq!(response.json::<RoadieResult<()>>(), Err(RoadieAppError::LastBagOwner));
+
+                let rr = RevokeRole {
+                    bag_id: bag.id,
+                    user_id: member.id
+                };
+                let response = test_server.post("/api/revoke_role")
+                    .text(qs::to_string(&rr)?)
+                    .content_type("application/x-www-form-urlencoded")
+                    .await;
+                response.assert_status(StatusCode::OK);
+                assert_eq!(bag.role_of(member.id, &pool).await?, None);
+                Ok(())
+            }
+
+            #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
+            #[sqlx::test]
+            async fn test_item_ownership(pool: SqlitePool) -> Result<()> {
+                let test_server = get_test_server(&pool).await?;
+                // The first user is the site admin, so keep them out of the way
+                let _admin = create_test_user(&test_server, Some("siteadmin".into())).await;
+                let owner = create_test_user(&test_server, Some("bagowner".into())).await;
+                let bag = create_test_bag(&owner, &pool).await?;
+                let creator = create_test_user(&test_server, Some("creator".into())).await;
+                bag.set_role(creator.id, Role::Editor, &pool).await?;
+
+                let bi = CreateUpdateBagItem {
+                    bag_id: bag.id,
+                    item: BagItemForm {
+                        name: "Creator's item".into(),
+                        size: Some(ItemSize::Small),
+                        ..Default::default()
+                    }
+                };
+                let response = test_server.post("/api/create_update_bag_item")
+                    .text(qs::to_string(&bi)?)
+                    .content_type("application/x-www-form-urlencoded")
+                    .await;
+                let mut item = response.json::<RoadieResult<BagItemForm>>().unwrap();
+
+                // Another editor can't touch it
+                let other = create_test_user(&test_server, Some("other".into())).await;
+                bag.set_role(other.id, Role::Editor, &pool).await?;
+                item.name = "Stolen item".into();
+                let bi = CreateUpdateBagItem {
+                    bag_id: bag.id,
+                    item: item.clone()
+                };
+                let response = test_server.post("/api/create_update_bag_item")
+                    .text(qs::to_string(&bi)?)
+                    .content_type("application/x-www-form-urlencoded")
+                    .await;
+                response.assert_status(StatusCode::FORBIDDEN);
+                assert_eq!(response.json::<RoadieResult<BagItemForm>>(), Err(RoadieAppError::Unauthorized));
+
+                let di = DeleteBagItem {
+                    bag_id: bag.id,
+                    id: item.id
+                };
+                let response = test_server.post("/api/delete_bag_item")
+                    .text(qs::to_string(&di)?)
+                    .content_type("application/x-www-form-urlencoded")
+                    .await;
+                response.assert_status(StatusCode::FORBIDDEN);
+                assert_eq!(response.json::<RoadieResult<()>>(), Err(RoadieAppError::Unauthorized));
+
+                let unchanged = BagItem::by_id(item.id, &pool).await?.unwrap();
+                assert_eq!(unchanged.name, "Creator's item");
+
+                // The bag's owner can
+                let second_owner = create_test_user(&test_server, Some("bagowner2".into())).await;
+                bag.set_role(second_owner.id, Role::Owner, &pool).await?;
+                let response = test_server.post("/api/create_update_bag_item")
+                    .text(qs::to_string(&bi)?)
+                    .content_type("application/x-www-form-urlencoded")
+                    .await;
+                assert_eq!(response.json::<RoadieResult<BagItemForm>>().is_ok(), true);
+                let response = test_server.post("/api/delete_bag_item")
+                    .text(qs::to_string(&di)?)
+                    .content_type("application/x-www-form-urlencoded")
+                    .await;
+                response.assert_status(StatusCode::OK);
+                assert_eq!(BagItem::by_id(item.id, &pool).await?.unwrap().deleted_at.is_some(), true);
+                Ok(())
+            }
+
             #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
             #[sqlx::test]
             async fn test_bagitem_api(pool: SqlitePool) -> Result<()> {
                 let test_server = get_test_server(&pool).await?;
 
                 let span = span!(tracing::Level::INFO, "test_bagitem_api").entered();
+                let bag_owner = create_test_user(&test_server, Some("bagowner".into())).await;
+                let bag = create_test_bag(&bag_owner, &pool).await?;
+                let response = test_server.post("/api/auth_logout")
+                    .await;
+                response.assert_status(StatusCode::FOUND);
+
                 let bi = CreateUpdateBagItem {
+                    bag_id: bag.id,
                     item: BagItemForm {
                         id: -1,
                         description: "Some description".into(),
                         name: "Some item".into(),
                         infinite: Some(false),
                         quantity: 1,
-                        size: Some(ItemSize::Large)
+                        size: Some(ItemSize::Large),
+                        weight: None,
+                        rounds: None,
+                        par_level: None,
+                        tags: "".into()
                     }
                 };
 
@@ -200,7 +1860,8 @@ cfg_if! {
                 let res = response.json::<RoadieResult<BagItemForm>>();
                 assert_eq!(res, Err(RoadieAppError::Unauthorized));
 
-                let _test_user = create_test_user(&test_server, None).await;
+                let test_user = create_test_user(&test_server, None).await;
+                bag.set_role(test_user.id, Role::Editor, &pool).await?;
 
                 let response = test_server.post("/api/create_update_bag_item")
                     .text(qs::to_string(&bi)?)
@@ -213,6 +1874,7 @@ cfg_if! {
                 assert_ne!(bag_item.id, -1);
                 bag_item.name = "Some other item".into();
                 let bi = CreateUpdateBagItem {
+                    bag_id: bag.id,
                     item: bag_item.clone()
                 };
 
@@ -225,6 +1887,7 @@ cfg_if! {
                 assert_eq!(res.is_ok(), true);
 
                 let gbi = GetBagItem {
+                    bag_id: bag.id,
                     item_id: bag_item.id
                 };
                 let response = test_server.post("/api/get_bag_item")
@@ -237,6 +1900,19 @@ cfg_if! {
                 assert_eq!(response.status_code(), StatusCode::OK);
 
                 let gbi = GetBagItem {
+                    bag_id: bag.id + 1,
+                    item_id: bag_item.id
+                };
+                let response = test_server.post("/api/get_bag_item")
+                    .text(qs::to_string(&gbi)?)
+                    .content_type("application/x-www-form-urlencoded")
+                    .await;
+                let res = response.json::<RoadieResult<Option<BagItem>>>();
+                assert_eq!(res.is_ok(), false);
+                assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+
+                let gbi = GetBagItem {
+                    bag_id: bag.id,
                     item_id: bag_item.id+1
                 };
                 let response = test_server.post("/api/get_bag_item")
@@ -248,6 +1924,7 @@ cfg_if! {
                 assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
 
                 let di = DeleteBagItem {
+                    bag_id: bag.id,
                     id: bag_item.id
                 };
                 let response = test_server.post("/api/delete_bag_item")
@@ -258,6 +1935,7 @@ cfg_if! {
                 assert_eq!(res.is_ok(), true);
 
                 let gbi = GetBagItem {
+                    bag_id: bag.id,
                     item_id: bag_item.id
                 };
                 let response = test_server.post("/api/get_bag_item")
@@ -0,0 +1,380 @@
+use cfg_if::cfg_if;
+
+cfg_if! {
+    if #[cfg(feature = "ssr")] {
+        use anyhow::{anyhow, bail, Context, Result};
+        use clap::{Parser, Subcommand};
+        use futures::StreamExt;
+        use sqlx::SqlitePool;
+        use std::fs::File;
+        use std::io::{self, BufRead, BufReader, BufWriter, Write};
+        use std::path::{Path, PathBuf};
+        use strum::IntoEnumIterator;
+
+        use crate::auth::model::{SQLUser, SQLUserPermission, ADMIN_TOKEN};
+        use crate::auth::{Role, User};
+        use crate::backup::{schema_version, Backup, BackupScope, ConflictPolicy};
+        use crate::bag::export::*;
+        use crate::bag::import::*;
+        use crate::bag::model::*;
+        use crate::config::{Config, ConfigArgs};
+        use crate::errors::RoadieAppError;
+        use crate::service::{get_db_pool, migrate, serve};
+
+        /// Runs a roadiebag instance, or manages one without going through the web UI
+        #[derive(Parser, Debug)]
+        #[command(name = "roadiebag", version, about)]
+        pub struct Cli {
+            #[command(flatten)]
+            pub config: ConfigArgs,
+            #[command(subcommand)]
+            pub command: Option<Command>,
+        }
+
+        #[derive(Subcommand, Debug)]
+        pub enum Command {
+            /// Start the web server. This is what runs when no command is given.
+            Serve {
+                /// Address to listen on, instead of the one in the Leptos configuration
+                #[arg(long)]
+                site_addr: Option<String>,
+            },
+            /// Bring the database schema up to date
+            Migrate,
+            /// Add a user. The first user on an instance is made an admin, like on the sign up page.
+            CreateUser {
+                username: String,
+                /// Read from standard input when left out
+                #[arg(long)]
+                password: Option<String>,
+                /// Let the user do anything in any bag
+                #[arg(long)]
+                admin: bool,
+            },
+            /// Set a new password for a user
+            ResetPassword {
+                username: String,
+                /// Read from standard input when left out
+                #[arg(long)]
+                password: Option<String>,
+            },
+            /// Give a user a role in a bag, replacing the one they had
+            Grant {
+                username: String,
+                bag_id: i64,
+                /// viewer, player, editor or owner
+                #[arg(value_parser = parse_role)]
+                role: Role,
+            },
+            /// Add the items in a CSV or JSON file to a bag
+            Import {
+                bag_id: i64,
+                file: PathBuf,
+                /// Who the items are added by
+                #[arg(long = "as", value_name = "USERNAME")]
+                user: String,
+                /// csv or json. Taken from the file's extension when left out.
+                #[arg(long, value_parser = parse_import_format)]
+                format: Option<ImportFormat>,
+                /// Only check the file
+                #[arg(long)]
+                dry_run: bool,
+                /// Leave out items with the same name as one already in the bag
+                #[arg(long)]
+                skip_duplicates: bool,
+            },
+            /// Write out a bag's items, or its draw history
+            Export {
+                bag_id: i64,
+                /// Export the draws instead of the items
+                #[arg(long)]
+                history: bool,
+                /// csv, json or ndjson
+                #[arg(long, value_parser = parse_export_format, default_value = "csv")]
+                format: ExportFormat,
+                /// Written to standard output when left out
+                #[arg(short, long)]
+                output: Option<PathBuf>,
+            },
+            /// Back up the whole instance, or a single bag
+            Backup {
+                /// Only back up this bag and the users in it
+                #[arg(long)]
+                bag: Option<i64>,
+                /// Keep the users' password hashes, so they can log in after a restore
+                #[arg(long)]
+                include_passwords: bool,
+                /// Written to standard output when left out
+                #[arg(short, long)]
+                output: Option<PathBuf>,
+            },
+            /// Restore a backup into this instance
+            Restore {
+                file: PathBuf,
+                /// What to do with users and bags that are already here: fail, skip or replace
+                #[arg(long, default_value_t = ConflictPolicy::Fail)]
+                on_conflict: ConflictPolicy,
+            },
+            /// Draw an item from a bag
+            Draw {
+                bag_id: i64,
+                /// Who the item is drawn by
+                #[arg(long = "as", value_name = "USERNAME")]
+                user: String,
+                /// Only draw items with this tag. Can be given more than once.
+                #[arg(long = "with-tag", value_name = "TAG")]
+                with_tags: Vec<String>,
+                /// Never draw items with this tag. Can be given more than once.
+                #[arg(long = "without-tag", value_name = "TAG")]
+                without_tags: Vec<String>,
+            },
+        }
+
+        fn parse_role(s: &str) -> Result<Role, String> {
+            Role::iter()
+                .find(|r| r.to_string().eq_ignore_ascii_case(s))
+                .ok_or_else(|| format!("{} isn't a role, use viewer, player, editor or owner", s))
+        }
+
+        fn parse_import_format(s: &str) -> Result<ImportFormat, String> {
+            ImportFormat::iter()
+                .find(|f| f.key().eq_ignore_ascii_case(s))
+                .ok_or_else(|| format!("{} isn't a format, use csv or json", s))
+        }
+
+        fn parse_export_format(s: &str) -> Result<ExportFormat, String> {
+            ExportFormat::iter()
+                .find(|f| f.key().eq_ignore_ascii_case(s))
+                .ok_or_else(|| format!("{} isn't a format, use csv, json or ndjson", s))
+        }
+
+        impl Cli {
+            pub async fn run(self) -> Result<()> {
+                let config = Config::load(&self.config)?;
+                match self.command.unwrap_or(Command::Serve { site_addr: None }) {
+                    Command::Serve { site_addr } => serve(config, site_addr).await,
+                    command => {
+                        let pool = get_db_pool(&config.database).await?;
+                        command.run_with(&pool).await
+                    }
+                }
+            }
+        }
+
+        impl Command {
+            /// Runs everything but `serve` against `pool`
+            pub async fn run_with(self, pool: &SqlitePool) -> Result<()> {
+                if !matches!(self, Command::Migrate) {
+                    check_schema(pool).await?;
+                }
+                match self {
+                    Command::Serve { .. } => bail!("The server can't be started on an existing pool"),
+                    Command::Migrate => {
+                        migrate(pool).await?;
+                        println!("Database is at schema version {}", schema_version());
+                    }
+                    Command::CreateUser { username, password, admin } => {
+                        let username = username.trim().to_string();
+                        if username.is_empty() {
+                            bail!("The username can't be empty");
+                        }
+                        if SQLUser::by_username(username.clone(), pool).await?.is_some() {
+                            bail!("User {} already exists", username);
+                        }
+                        let password = password_or_stdin(password)?;
+                        let user_id = SQLUser::create(username.clone(), password, pool).await?;
+                        let first = SQLUserPermission::grant_to_first_user(user_id, ADMIN_TOKEN.into(), pool).await?;
+                        if admin || first {
+                            if !first {
+                                SQLUserPermission::grant(user_id, ADMIN_TOKEN.into(), pool).await?;
+                            }
+                            println!("Created admin {} ({})", username, user_id);
+                        } else {
+                            println!("Created user {} ({})", username, user_id);
+                        }
+                    }
+                    Command::ResetPassword { username, password } => {
+                        let user = find_user(&username, pool).await?;
+                        let password = password_or_stdin(password)?;
+                        SQLUser::set_password(user.id, password, pool).await?;
+                        println!("Set a new password for {}", user.username);
+                    }
+                    Command::Grant { username, bag_id, role } => {
+                        let user = find_user(&username, pool).await?;
+                        let bag = find_bag(bag_id, pool).await?;
+                        bag.set_role(user.id, role, pool).await?;
+                        println!("{} is now {} in {}", user.username, role, bag.name);
+                    }
+                    Command::Import { bag_id, file, user, format, dry_run, skip_duplicates } => {
+                        let added_by = find_user(&user, pool).await?;
+                        find_bag(bag_id, pool).await?;
+                        let format = format.unwrap_or_else(|| format_of(&file));
+                        let contents = std::fs::read_to_string(&file)
+                            .with_context(|| format!("Couldn't read {}", file.display()))?;
+                        match import_items(bag_id, format, &contents, dry_run, skip_duplicates, added_by, pool).await? {
+                            Ok(report) => {
+                                let verb = if report.dry_run { "Would import" } else { "Imported" };
+                                println!("{} {} items", verb, report.imported.len());
+                                if !report.skipped.is_empty() {
+                                    println!("Skipped {} already in the bag: {}", report.skipped.len(), report.skipped.join(", "));
+                                }
+                            }
+                            Err(errors) => {
+                                let mut errors: Vec<_> = errors.into_iter().collect();
+                                errors.sort_by_key(|(key, _)| {
+                                    key.strip_prefix("row ")
+                                        .and_then(|n| n.parse::<usize>().ok())
+                                        .unwrap_or(0)
+                                });
+                                for (key, error) in errors {
+                                    eprintln!("{}: {}", key, error);
+                                }
+                                bail!("Nothing was imported from {}", file.display());
+                            }
+                        }
+                    }
+                    Command::Export { bag_id, history, format, output } => {
+                        find_bag(bag_id, pool).await?;
+                        let mut chunks = if history {
+                            encode_pages(format, draw_pages(bag_id, TakenItemFilter::default(), pool.clone()))
+                        } else {
+                            encode_pages(format, item_pages(bag_id, BagItemFilter::default(), pool.clone()))
+                        };
+                        let mut out = output_to(&output)?;
+                        while let Some(chunk) = chunks.next().await {
+                            out.write_all(chunk.map_err(|e| anyhow!(e))?.as_bytes())?;
+                        }
+                        out.flush()?;
+                    }
+                    Command::Backup { bag, include_passwords, output } => {
+                        let scope = bag.map(BackupScope::Bag).unwrap_or(BackupScope::Instance);
+                        let backup = Backup::create(scope, include_passwords, pool).await?;
+                        let mut out = output_to(&output)?;
+                        backup.write(&mut out)?;
+                        out.flush()?;
+                        eprintln!(
+                            "Backed up {} users, {} bags, {} items, {} draws and {} restocks",
+                            backup.users.len(),
+                            backup.bags.len(),
+                            backup.items.len(),
+                            backup.draws.len(),
+                            backup.restocks.len()
+                        );
+                    }
+                    Command::Restore { file, on_conflict } => {
+                        let reader = File::open(&file)
+                            .with_context(|| format!("Couldn't open {}", file.display()))?;
+                        let backup = Backup::read(BufReader::new(reader))?;
+                        let report = backup.restore(on_conflict, pool).await?;
+                        println!(
+                            "Restored {} bags ({} replaced, {} skipped), {} items, {} draws, {} restocks and {} permissions",
+                            report.bags_restored + report.bags_replaced,
+                            report.bags_replaced,
+                            report.bags_skipped,
+                            report.items,
+                            report.draws,
+                            report.restocks,
+                            report.permissions
+                        );
+                        println!(
+                            "Created {} users and matched {} that were already here",
+                            report.users_created,
+                            report.users_matched
+                        );
+                    }
+                    Command::Draw { bag_id, user, with_tags, without_tags } => {
+                        let drawn_by = find_user(&user, pool).await?;
+                        find_bag(bag_id, pool).await?;
+                        if let Some(active) = TakenBagItem::active_for(bag_id, drawn_by.id, pool).await? {
+                            return Err(RoadieAppError::DrawInProgress)
+                                .with_context(|| format!("{} is still playing {} (draw {})", drawn_by.username, active.item.name, active.id));
+                        }
+                        let tags = DrawTags { with_tags, without_tags };
+                        match TakenBagItem::get_random(bag_id, drawn_by.id, &tags, pool).await?? {
+                            Some(drawn) => println!(
+                                "Drew {} for {} rounds (draw {})",
+                                drawn.item.name,
+                                drawn.rounds,
+                                drawn.id
+                            ),
+                            None => bail!("There's nothing left to draw in bag {}", bag_id),
+                        }
+                    }
+                }
+                Ok(())
+            }
+        }
+
+        /// Fails unless every migration this build knows about has been run, so commands don't
+        /// trip over missing tables or columns halfway through
+        async fn check_schema(pool: &SqlitePool) -> Result<()> {
+            let tracked: bool = sqlx::query_scalar(
+                "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'",
+            )
+            .fetch_one(pool)
+            .await?;
+            let applied: Option<i64> = if tracked {
+                sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success")
+                    .fetch_one(pool)
+                    .await?
+            } else {
+                None
+            };
+            if applied.unwrap_or(0) < schema_version() {
+                bail!("The database schema is out of date, run `roadiebag migrate` first");
+            }
+            Ok(())
+        }
+
+        async fn find_user(username: &str, pool: &SqlitePool) -> Result<User> {
+            match SQLUser::by_username(username.into(), pool).await? {
+                Some(u) => Ok(User {
+                    id: u.id,
+                    username: u.username,
+                    anonymous: false,
+                }),
+                None => bail!("User {} doesn't exist", username),
+            }
+        }
+
+        async fn find_bag(bag_id: i64, pool: &SqlitePool) -> Result<Bag> {
+            Bag::by_id(bag_id, pool)
+                .await?
+                .ok_or_else(|| anyhow!("Bag {} doesn't exist", bag_id))
+        }
+
+        /// Uses the password from the command line, or the first line of standard input so it
+        /// can be piped in without ending up in the shell history
+        fn password_or_stdin(password: Option<String>) -> Result<String> {
+            let password = match password {
+                Some(password) => password,
+                None => {
+                    eprint!("Password: ");
+                    let mut line = String::new();
+                    io::stdin().lock().read_line(&mut line)?;
+                    line.trim_end_matches(['\r', '\n']).to_string()
+                }
+            };
+            if password.trim().is_empty() {
+                bail!("The password can't be empty");
+            }
+            Ok(password)
+        }
+
+        fn format_of(file: &Path) -> ImportFormat {
+            match file.extension().and_then(|e| e.to_str()) {
+                Some(ext) if ext.eq_ignore_ascii_case("json") => ImportFormat::Json,
+                _ => ImportFormat::Csv,
+            }
+        }
+
+        fn output_to(output: &Option<PathBuf>) -> Result<Box<dyn Write>> {
+            Ok(match output {
+                Some(path) => Box::new(BufWriter::new(
+                    File::create(path).with_context(|| format!("Couldn't create {}", path.display()))?,
+                )),
+                None => Box::new(BufWriter::new(io::stdout().lock())),
+            })
+        }
+    }
+}
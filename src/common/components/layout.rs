@@ -1,4 +1,5 @@
 use crate::auth::frontend::AuthContext;
+use crate::bag::frontend::{BagContext, BagPicker};
 use leptos::*;
 use leptos_dom::*;
 use leptos_router::*;
@@ -43,6 +44,14 @@ pub fn Avatar() -> impl IntoView {
 
 #[component]
 pub fn Header() -> impl IntoView {
+    let bag_context = use_context::<BagContext>().expect("Failed to get BagContext");
+    let has_bag = Signal::derive(move || bag_context.bag_id.get().is_some());
+    let items_href = Signal::derive(move || bag_context.href("/items"));
+    let current_href = Signal::derive(move || bag_context.href(""));
+    let add_href = Signal::derive(move || bag_context.href("/items/add"));
+    let history_href = Signal::derive(move || bag_context.href("/history"));
+    let members_href = Signal::derive(move || bag_context.href("/members"));
+
     view! {
         <div class="navbar bg-base-100">
             <div class="navbar-start">
@@ -67,21 +76,38 @@ pub fn Header() -> impl IntoView {
                         tabindex="0"
                         class="menu menu-sm dropdown-content mt-3 z-[1] p-2 shadow bg-base-100 rounded-box w-52"
                     >
-                        <li>
-                            <A exact=true href="/items">
-                                "Item List"
-                            </A>
-                        </li>
                         <li>
                             <A exact=true href="/">
-                                "Current Item"
-                            </A>
-                        </li>
-                        <li>
-                            <A exact=true href="/items/add">
-                                "Add Item"
+                                "Bags"
                             </A>
                         </li>
+                        <Show when=has_bag>
+                            <li>
+                                <A exact=true href=move || items_href.get()>
+                                    "Item List"
+                                </A>
+                            </li>
+                            <li>
+                                <A exact=true href=move || current_href.get()>
+                                    "Current Item"
+                                </A>
+                            </li>
+                            <li>
+                                <A exact=true href=move || add_href.get()>
+                                    "Add Item"
+                                </A>
+                            </li>
+                            <li>
+                                <A exact=true href=move || history_href.get()>
+                                    "History"
+                                </A>
+                            </li>
+                            <li>
+                                <A exact=true href=move || members_href.get()>
+                                    "Members"
+                                </A>
+                            </li>
+                        </Show>
                     </ul>
                 </div>
                 <A href="/" class="btn btn-ghost normal-case text-xl">
@@ -90,24 +116,42 @@ pub fn Header() -> impl IntoView {
             </div>
             <div class="navbar-center hidden lg:flex">
                 <ul class="menu menu-horizontal px-1">
-                    <li>
-                        <A exact=true href="/items">
-                            "Item List"
-                        </A>
-                    </li>
                     <li>
                         <A exact=true href="/">
-                            "Current Item"
-                        </A>
-                    </li>
-                    <li>
-                        <A exact=true href="/items/add">
-                            "Add Item"
+                            "Bags"
                         </A>
                     </li>
+                    <Show when=has_bag>
+                        <li>
+                            <A exact=true href=move || items_href.get()>
+                                "Item List"
+                            </A>
+                        </li>
+                        <li>
+                            <A exact=true href=move || current_href.get()>
+                                "Current Item"
+                            </A>
+                        </li>
+                        <li>
+                            <A exact=true href=move || add_href.get()>
+                                "Add Item"
+                            </A>
+                        </li>
+                        <li>
+                            <A exact=true href=move || history_href.get()>
+                                "History"
+                            </A>
+                        </li>
+                        <li>
+                            <A exact=true href=move || members_href.get()>
+                                "Members"
+                            </A>
+                        </li>
+                    </Show>
                 </ul>
             </div>
             <div class="navbar-end">
+                <BagPicker/>
                 <Avatar/>
             </div>
         </div>
@@ -0,0 +1,341 @@
+use cfg_if::cfg_if;
+
+cfg_if! {
+    if #[cfg(feature = "ssr")] {
+        use axum_session::{SameSite, SessionConfig};
+        use clap::Args;
+        use serde::{Deserialize, Serialize};
+        use std::path::{Path, PathBuf};
+        use std::str::FromStr;
+        use strum::{Display, EnumString};
+        use thiserror::Error;
+        use tracing_subscriber::EnvFilter;
+
+        use crate::bag::api::BagForm;
+        use crate::bag::dice::DiceExpr;
+
+        /// Read when no other file is given, if it's there
+        pub const DEFAULT_CONFIG_FILE: &str = "roadiebag.toml";
+        /// Environment variables that override the file all start with this
+        pub const ENV_PREFIX: &str = "ROADIEBAG_";
+
+        #[derive(Debug, Error)]
+        pub enum ConfigError {
+            #[error("Couldn't read {}: {source}", .path.display())]
+            Read { path: PathBuf, source: std::io::Error },
+            #[error("{} isn't a valid configuration file: {source}", .path.display())]
+            Parse { path: PathBuf, source: toml::de::Error },
+            #[error("{var}={value} isn't valid: {reason}")]
+            Env { var: String, value: String, reason: String },
+            #[error("The configuration has problems:\n  {}", .0.join("\n  "))]
+            Invalid(Vec<String>),
+        }
+
+        /// Everything an instance can be set up with. Each setting comes from the first of these
+        /// that has it: a command line flag, an environment variable, the configuration file and
+        /// finally the defaults here.
+        #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+        #[serde(default, deny_unknown_fields)]
+        pub struct Config {
+            pub database: DatabaseConfig,
+            pub session: SessionSettings,
+            pub log: LogConfig,
+            pub draw: DrawDefaults,
+        }
+
+        #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+        #[serde(default, deny_unknown_fields)]
+        pub struct DatabaseConfig {
+            /// `DATABASE_URL`
+            pub url: String,
+            /// `ROADIEBAG_DATABASE_MAX_CONNECTIONS`
+            pub max_connections: u32,
+            /// Start a new database when there isn't one at `url` yet.
+            /// `ROADIEBAG_DATABASE_CREATE_IF_MISSING`
+            pub create_if_missing: bool,
+        }
+
+        impl Default for DatabaseConfig {
+            fn default() -> Self {
+                DatabaseConfig {
+                    url: "sqlite:roadiebag.db".into(),
+                    max_connections: 10,
+                    create_if_missing: true,
+                }
+            }
+        }
+
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Display, EnumString)]
+        #[serde(rename_all = "lowercase")]
+        #[strum(serialize_all = "lowercase")]
+        pub enum CookieSameSite {
+            Strict,
+            Lax,
+            None,
+        }
+
+        #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+        #[serde(default, deny_unknown_fields)]
+        pub struct SessionSettings {
+            /// How long a login lasts without "remember me".
+            /// `ROADIEBAG_SESSION_LIFETIME_HOURS`
+            pub lifetime_hours: u32,
+            /// How long a remembered login lasts. `ROADIEBAG_SESSION_REMEMBER_DAYS`
+            pub remember_days: u32,
+            /// `ROADIEBAG_SESSION_COOKIE_NAME`
+            pub cookie_name: String,
+            /// Only send the cookie over HTTPS. `ROADIEBAG_SESSION_COOKIE_SECURE`
+            pub cookie_secure: bool,
+            /// `ROADIEBAG_SESSION_COOKIE_SAME_SITE`
+            pub cookie_same_site: CookieSameSite,
+            /// Left to the browser when it isn't set. `ROADIEBAG_SESSION_COOKIE_DOMAIN`
+            pub cookie_domain: Option<String>,
+        }
+
+        impl Default for SessionSettings {
+            fn default() -> Self {
+                SessionSettings {
+                    lifetime_hours: 6,
+                    remember_days: 60,
+                    cookie_name: "session".into(),
+                    cookie_secure: false,
+                    cookie_same_site: CookieSameSite::Lax,
+                    cookie_domain: None,
+                }
+            }
+        }
+
+        impl SessionSettings {
+            pub fn session_config(&self) -> SessionConfig {
+                let config = SessionConfig::default()
+                    .with_table_name("axum_sessions")
+                    .with_lifetime(chrono::Duration::hours(self.lifetime_hours.into()))
+                    .with_max_lifetime(chrono::Duration::days(self.remember_days.into()))
+                    .with_max_age(Some(chrono::Duration::days(self.remember_days.into())))
+                    .with_session_name(self.cookie_name.clone())
+                    .with_secure(self.cookie_secure)
+                    .with_cookie_same_site(match self.cookie_same_site {
+                        CookieSameSite::Strict => SameSite::Strict,
+                        CookieSameSite::Lax => SameSite::Lax,
+                        CookieSameSite::None => SameSite::None,
+                    });
+                match &self.cookie_domain {
+                    Some(domain) => config.with_cookie_domain(domain.clone()),
+                    None => config,
+                }
+            }
+        }
+
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Display, EnumString)]
+        #[serde(rename_all = "lowercase")]
+        #[strum(serialize_all = "lowercase")]
+        pub enum LogFormat {
+            /// Human readable, for a terminal
+            Pretty,
+            /// Bunyan style JSON, for a log collector
+            Json,
+        }
+
+        #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+        #[serde(default, deny_unknown_fields)]
+        pub struct LogConfig {
+            /// `ROADIEBAG_LOG_FORMAT`
+            pub format: LogFormat,
+            /// A tracing filter like `info` or `roadiebag=debug,sqlx=warn`. `RUST_LOG` still
+            /// wins over it. `ROADIEBAG_LOG_LEVEL`
+            pub level: String,
+        }
+
+        impl Default for LogConfig {
+            fn default() -> Self {
+                LogConfig {
+                    format: LogFormat::Pretty,
+                    level: "info".into(),
+                }
+            }
+        }
+
+        /// What new bags start out with
+        #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+        #[serde(default, deny_unknown_fields)]
+        pub struct DrawDefaults {
+            /// `ROADIEBAG_DRAW_DEFAULT_ROUNDS`
+            pub default_rounds: String,
+            /// `ROADIEBAG_DRAW_UNDO_WINDOW_SECS`
+            pub undo_window_secs: u32,
+            /// `ROADIEBAG_DRAW_SMALL_WEIGHT`
+            pub small_weight: u32,
+            /// `ROADIEBAG_DRAW_MEDIUM_WEIGHT`
+            pub medium_weight: u32,
+            /// `ROADIEBAG_DRAW_LARGE_WEIGHT`
+            pub large_weight: u32,
+        }
+
+        impl Default for DrawDefaults {
+            fn default() -> Self {
+                let form = BagForm::default();
+                DrawDefaults {
+                    default_rounds: form.default_rounds,
+                    undo_window_secs: form.undo_window_secs,
+                    small_weight: form.small_weight,
+                    medium_weight: form.medium_weight,
+                    large_weight: form.large_weight,
+                }
+            }
+        }
+
+        impl DrawDefaults {
+            /// The form a new bag starts from
+            pub fn bag_form(&self) -> BagForm {
+                BagForm {
+                    default_rounds: self.default_rounds.clone(),
+                    undo_window_secs: self.undo_window_secs,
+                    small_weight: self.small_weight,
+                    medium_weight: self.medium_weight,
+                    large_weight: self.large_weight,
+                    ..Default::default()
+                }
+            }
+        }
+
+        /// Flags that override the configuration file and environment
+        #[derive(Args, Clone, Debug, Default)]
+        pub struct ConfigArgs {
+            /// Configuration file to read, instead of `roadiebag.toml` or `ROADIEBAG_CONFIG`
+            #[arg(long, global = true, value_name = "FILE")]
+            pub config: Option<PathBuf>,
+            #[arg(long, global = true, value_name = "URL")]
+            pub database_url: Option<String>,
+            #[arg(long, global = true, value_name = "N")]
+            pub max_connections: Option<u32>,
+            /// pretty or json
+            #[arg(long, global = true)]
+            pub log_format: Option<LogFormat>,
+            #[arg(long, global = true, value_name = "FILTER")]
+            pub log_level: Option<String>,
+        }
+
+        impl Config {
+            /// Builds the configuration from every layer and checks it
+            pub fn load(args: &ConfigArgs) -> Result<Config, ConfigError> {
+                Self::load_with(args, |name| std::env::var(name).ok())
+            }
+
+            /// Same as [`Config::load`], with environment variables looked up through `var`
+            pub fn load_with(args: &ConfigArgs, var: impl Fn(&str) -> Option<String>) -> Result<Config, ConfigError> {
+                let file = args.config.clone()
+                    .or_else(|| var(&format!("{}CONFIG", ENV_PREFIX)).map(PathBuf::from));
+                let mut config = match file {
+                    Some(path) => Self::from_file(&path)?,
+                    // The default file is optional, one that was asked for isn't
+                    None if Path::new(DEFAULT_CONFIG_FILE).exists() => Self::from_file(Path::new(DEFAULT_CONFIG_FILE))?,
+                    None => Config::default(),
+                };
+                config.apply_env(var)?;
+                config.apply_args(args);
+                config.validate()?;
+                Ok(config)
+            }
+
+            pub fn from_file(path: &Path) -> Result<Config, ConfigError> {
+                let contents = std::fs::read_to_string(path)
+                    .map_err(|source| ConfigError::Read { path: path.into(), source })?;
+                toml::from_str(&contents)
+                    .map_err(|source| ConfigError::Parse { path: path.into(), source })
+            }
+
+            pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+                // Shared with the sqlx tooling, so it doesn't take the prefix
+                if let Some(url) = var("DATABASE_URL") {
+                    self.database.url = url;
+                }
+                let var = |name: &str| var(&format!("{}{}", ENV_PREFIX, name)).map(|value| (format!("{}{}", ENV_PREFIX, name), value));
+                env_value(var("DATABASE_MAX_CONNECTIONS"), &mut self.database.max_connections)?;
+                env_value(var("DATABASE_CREATE_IF_MISSING"), &mut self.database.create_if_missing)?;
+                env_value(var("SESSION_LIFETIME_HOURS"), &mut self.session.lifetime_hours)?;
+                env_value(var("SESSION_REMEMBER_DAYS"), &mut self.session.remember_days)?;
+                env_value(var("SESSION_COOKIE_NAME"), &mut self.session.cookie_name)?;
+                env_value(var("SESSION_COOKIE_SECURE"), &mut self.session.cookie_secure)?;
+                env_value(var("SESSION_COOKIE_SAME_SITE"), &mut self.session.cookie_same_site)?;
+                if let Some((_, domain)) = var("SESSION_COOKIE_DOMAIN") {
+                    self.session.cookie_domain = Some(domain).filter(|d| !d.is_empty());
+                }
+                env_value(var("LOG_FORMAT"), &mut self.log.format)?;
+                env_value(var("LOG_LEVEL"), &mut self.log.level)?;
+                env_value(var("DRAW_DEFAULT_ROUNDS"), &mut self.draw.default_rounds)?;
+                env_value(var("DRAW_UNDO_WINDOW_SECS"), &mut self.draw.undo_window_secs)?;
+                env_value(var("DRAW_SMALL_WEIGHT"), &mut self.draw.small_weight)?;
+                env_value(var("DRAW_MEDIUM_WEIGHT"), &mut self.draw.medium_weight)?;
+                env_value(var("DRAW_LARGE_WEIGHT"), &mut self.draw.large_weight)?;
+                Ok(())
+            }
+
+            pub fn apply_args(&mut self, args: &ConfigArgs) {
+                if let Some(url) = &args.database_url {
+                    self.database.url = url.clone();
+                }
+                if let Some(max_connections) = args.max_connections {
+                    self.database.max_connections = max_connections;
+                }
+                if let Some(format) = args.log_format {
+                    self.log.format = format;
+                }
+                if let Some(level) = &args.log_level {
+                    self.log.level = level.clone();
+                }
+            }
+
+            /// Lists every setting that can't work, rather than stopping at the first one
+            pub fn validate(&self) -> Result<(), ConfigError> {
+                let mut problems = vec![];
+                if self.database.url.trim().is_empty() {
+                    problems.push("database.url can't be empty".to_string());
+                }
+                if self.database.max_connections == 0 {
+                    problems.push("database.max_connections has to be at least 1".to_string());
+                }
+                if self.session.lifetime_hours == 0 {
+                    problems.push("session.lifetime_hours has to be at least 1".to_string());
+                }
+                if self.session.remember_days == 0 {
+                    problems.push("session.remember_days has to be at least 1".to_string());
+                }
+                if self.session.cookie_name.trim().is_empty() {
+                    problems.push("session.cookie_name can't be empty".to_string());
+                }
+                if self.session.cookie_same_site == CookieSameSite::None && !self.session.cookie_secure {
+                    problems.push("session.cookie_same_site = \"none\" needs session.cookie_secure, browsers drop the cookie otherwise".to_string());
+                }
+                if let Err(e) = EnvFilter::try_new(&self.log.level) {
+                    problems.push(format!("log.level {:?} isn't a valid filter: {}", self.log.level, e));
+                }
+                if let Err(e) = self.draw.default_rounds.parse::<DiceExpr>() {
+                    problems.push(format!("draw.default_rounds: {}", e));
+                }
+                if self.draw.small_weight == 0 && self.draw.medium_weight == 0 && self.draw.large_weight == 0 {
+                    problems.push("draw.small_weight, draw.medium_weight and draw.large_weight can't all be 0".to_string());
+                }
+                if problems.is_empty() {
+                    Ok(())
+                } else {
+                    Err(ConfigError::Invalid(problems))
+                }
+            }
+        }
+
+        fn env_value<T>(var: Option<(String, String)>, field: &mut T) -> Result<(), ConfigError>
+        where
+            T: FromStr,
+            T::Err: std::fmt::Display,
+        {
+            if let Some((var, value)) = var {
+                *field = value.parse().map_err(|e: T::Err| ConfigError::Env {
+                    reason: e.to_string(),
+                    var,
+                    value,
+                })?;
+            }
+            Ok(())
+        }
+    }
+}
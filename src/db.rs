@@ -19,6 +19,20 @@ cfg_if! {
                 .ok_or_else(|| ServerFnError::ServerError("Pool missing.".into()))
         }
 
+        /// True when SQLite refused a statement because another connection holds the lock it
+        /// needs. Transactions that fail this way can be retried from the start.
+        pub fn is_busy(error: &sqlx::Error) -> bool {
+            match error {
+                sqlx::Error::Database(e) => e
+                    .code()
+                    .and_then(|code| code.parse::<i32>().ok())
+                    // Extended result codes keep the primary code in the low byte
+                    .map(|code| matches!(code & 0xff, 5 | 6))
+                    .unwrap_or(false),
+                _ => false
+            }
+        }
+
 
 
     }
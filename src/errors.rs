@@ -26,6 +26,38 @@ pub enum RoadieAppError {
     ItemSizeMustBeSet,
     #[error("Item quantity must be > 0")]
     ItemQntGtZero,
+    #[error("Item quantity can't be < 0")]
+    ItemQntNegative,
+    #[error("Item par level must be > 0")]
+    ItemParLevelGtZero,
+    #[error("Bag name can't be empty")]
+    BagNameNonEmpty,
+    #[error("At least one size weight must be > 0")]
+    SizeWeightsAllZero,
+    #[error("This draw was made before draws could be replayed")]
+    DrawNotReplayable,
+    #[error("A bag needs at least one owner")]
+    LastBagOwner,
+    #[error("Finish your current item before taking another")]
+    DrawInProgress,
+    #[error("Too many players are drawing from this bag right now, try again")]
+    DrawContention,
+    #[error("This draw is already over")]
+    DrawAlreadyFinished,
+    #[error("Every round of this draw has been played")]
+    NoRoundsLeft,
+    #[error("It's too late to undo this draw")]
+    UndoWindowPassed,
+    #[error("Only your most recent draw can be undone")]
+    NotLatestDraw,
+    #[error("\"{0}\" isn't a number of rounds like 3, 1d6 or 2d4+1 that always rolls at least 1")]
+    InvalidDiceExpr(String),
+    #[error("Tag \"{0}\" is longer than 32 characters")]
+    TagTooLong(String),
+    #[error("Couldn't read the import file: {0}")]
+    ImportUnreadable(String),
+    #[error("The import file doesn't have any items in it")]
+    ImportEmpty,
     #[error("Multiple errors")]
     MultipleErrors(HashMap<String, String>),
     #[error("Server error {0}")]
@@ -37,16 +69,31 @@ pub type RoadieResult<T> = Result<T, RoadieAppError>;
 impl RoadieAppError {
     pub fn status_code(&self) -> StatusCode {
         match self {
-            RoadieAppError::NotFound => StatusCode::NOT_FOUND,
+            RoadieAppError::NotFound | RoadieAppError::DrawNotReplayable => StatusCode::NOT_FOUND,
+            RoadieAppError::DrawContention => StatusCode::SERVICE_UNAVAILABLE,
             RoadieAppError::BadUserPassword | RoadieAppError::Unauthorized => {
                 StatusCode::UNAUTHORIZED
             }
             RoadieAppError::PasswordsDoNotMatch => StatusCode::EXPECTATION_FAILED,
+            RoadieAppError::DrawInProgress
+            | RoadieAppError::DrawAlreadyFinished
+            | RoadieAppError::NoRoundsLeft
+            | RoadieAppError::UndoWindowPassed
+            | RoadieAppError::NotLatestDraw => StatusCode::CONFLICT,
             RoadieAppError::InternalServerError | RoadieAppError::ServerError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             RoadieAppError::ValidationFailedError
             | RoadieAppError::ItemQntGtZero
+            | RoadieAppError::ItemQntNegative
             | RoadieAppError::ItemSizeMustBeSet
-            | RoadieAppError::ItemNameNonEmpty => StatusCode::EXPECTATION_FAILED,
+            | RoadieAppError::ItemNameNonEmpty
+            | RoadieAppError::ItemParLevelGtZero
+            | RoadieAppError::BagNameNonEmpty
+            | RoadieAppError::SizeWeightsAllZero
+            | RoadieAppError::InvalidDiceExpr(_)
+            | RoadieAppError::TagTooLong(_)
+            | RoadieAppError::ImportUnreadable(_)
+            | RoadieAppError::ImportEmpty
+            | RoadieAppError::LastBagOwner => StatusCode::EXPECTATION_FAILED,
             RoadieAppError::ValidationFailedForField(_) => StatusCode::EXPECTATION_FAILED,
             RoadieAppError::MultipleErrors(_) => StatusCode::EXPECTATION_FAILED,
         }
@@ -1,6 +1,9 @@
 use cfg_if::cfg_if;
 pub mod app;
 pub mod auth;
+pub mod backup;
+pub mod cli;
+pub mod config;
 pub mod db;
 pub mod error_template;
 //pub mod fileserv;
@@ -2,25 +2,17 @@ use cfg_if::cfg_if;
 
 cfg_if! {
     if #[cfg(feature="ssr")] {
-        use roadiebag::service::*;
+        use clap::Parser;
+        use roadiebag::cli::Cli;
         use dotenvy::dotenv;
 
         #[tokio::main]
         async fn main() {
             dotenv().ok();
-            init_logging().await;
-            let db_pool = get_db_pool().await;
-
-            let options = load_leptos_options(None, None).await;
-            let addr = options.site_addr.clone();
-            let app_state = get_app_state(db_pool, options);
-            let app = get_router(app_state).await;
-
-            tracing::info!("listening on http://{}", &addr);
-            axum::Server::bind(&addr)
-                .serve(app.into_make_service())
-                .await
-                .unwrap();
+            if let Err(e) = Cli::parse().run().await {
+                eprintln!("Error: {:#}", e);
+                std::process::exit(1);
+            }
         }
     } else {
         pub fn main() {
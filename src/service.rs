@@ -14,6 +14,7 @@ cfg_if! {
         use crate::state::AppState;
         use crate::fallback::file_and_error_handler;
         use crate::auth::{AuthSession, User};
+        use crate::config::{Config, DatabaseConfig, LogConfig, LogFormat};
         use crate::telemetry::*;
 
         use leptos::*;
@@ -21,10 +22,11 @@ cfg_if! {
         use leptos::{provide_context, get_configuration};
 
         use tower_http::trace::TraceLayer;
-        use sqlx::{SqlitePool, sqlite::SqlitePoolOptions};
-        use axum_session::{SessionConfig, SessionLayer, SessionStore};
+        use sqlx::{SqlitePool, sqlite::{SqliteConnectOptions, SqlitePoolOptions}};
+        use axum_session::{SessionLayer, SessionStore};
         use axum_session_auth::{AuthSessionLayer, AuthConfig, SessionSqlitePool};
-        use std::env;
+        use anyhow::{Context, Result};
+        use std::str::FromStr;
 
         #[tracing::instrument(level = "info", fields(error))]
         async fn server_fn_handler(State(app_state): State<AppState>, auth_session: AuthSession,
@@ -33,6 +35,7 @@ cfg_if! {
             handle_server_fns_with_context(path, headers, raw_query, move || {
                 provide_context(auth_session.clone());
                 provide_context(app_state.pool.clone());
+                provide_context(app_state.config.clone());
             }, request).await
         }
 
@@ -44,84 +47,82 @@ cfg_if! {
                 move || {
                     provide_context(auth_session.clone());
                     provide_context(app_state.pool.clone());
+                    provide_context(app_state.config.clone());
                 },
                 App
             );
             handler(req).await.into_response()
         }
 
-        pub async fn init_logging() {
-            /*let log_level = env::var("LOG_LEVEL")
-                    .map(|s| Level::from_str(&s).expect("Could not parse LOG_LEVEL"))
-                    .unwrap_or_else(|_| Level::Debug);
-            //simple_logger::init_with_level(log_level).expect("couldn't initialize logging");
-            logging::log!("Starting roadiebag with log level {:?}", log_level);*/
-
-
-            if env::var("LEPTOS_ENVIRONMENT").expect("Failed to find LEPTOS_ENVIRONMENT Env Var").to_lowercase() == "local" {
-                println!("LOCAL ENVIRONMENT");
-                init_subscriber(get_subscriber(
-                    "roadiebag".into(),
-                    "INFO".into(),
-                    std::io::stdout,
-                ));
-            } else if env::var("LEPTOS_ENVIRONMENT").expect("Failed to find LEPTOS_ENVIRONMENT Env Var") == "prod_no_trace" {
-                init_subscriber(get_subscriber(
+        pub async fn init_logging(config: &LogConfig) {
+            match config.format {
+                LogFormat::Json => init_subscriber(get_subscriber(
                     "roadiebag".into(),
-                    "INFO".into(),
+                    config.level.clone(),
                     std::io::stdout,
-                 ));
-            } else{
-                init_subscriber(
+                )),
+                LogFormat::Pretty => init_subscriber(
                     get_subscriber_with_tracing(
                         "roadiebag".into(),
-                        "INFO".into(),
+                        config.level.clone(),
                         std::io::stdout,
-                    ).await);
+                    ).await),
             }
             tracing::info!("Telemetry started");
         }
 
-        pub async fn get_db_pool() -> SqlitePool {
-            let database_url = env::var("DATABASE_URL").expect("Must set DATABASE_URL");
+        /// Opens the database. Migrations are left to [`migrate`], which `serve` runs on startup
+        pub async fn get_db_pool(config: &DatabaseConfig) -> Result<SqlitePool> {
+            let options = SqliteConnectOptions::from_str(&config.url)
+                .with_context(|| format!("{} isn't a SQLite database URL", config.url))?
+                .create_if_missing(config.create_if_missing);
             let pool = SqlitePoolOptions::new()
-                .connect(&database_url)
+                .max_connections(config.max_connections)
+                .connect_with(options)
                 .await
-                .expect("Could not make connection pool.");
-
+                .with_context(|| format!("Couldn't open the database at {}", config.url))?;
+            Ok(pool)
+        }
 
+        /// Runs any migrations that haven't been yet. The server does this when it starts, the
+        /// other commands leave it to `roadiebag migrate`.
+        pub async fn migrate(pool: &SqlitePool) -> Result<()> {
             sqlx::migrate!()
-                .run(&pool)
+                .run(pool)
                 .await
-                .expect("could not run SQLx migrations");
-
-            pool
+                .context("Couldn't bring the database schema up to date")
         }
 
-        pub async fn load_leptos_options(fname: Option<&str>, site_addr: Option<String>) -> LeptosOptions {
-            let mut conf = get_configuration(fname).await.unwrap();
+        pub async fn load_leptos_options(fname: Option<&str>, site_addr: Option<String>) -> Result<LeptosOptions> {
+            let mut conf = get_configuration(fname).await.context("Couldn't load the Leptos configuration")?;
             if let Some(addr) = site_addr {
-                conf.leptos_options.site_addr = addr.parse().unwrap();
+                conf.leptos_options.site_addr = addr
+                    .parse()
+                    .with_context(|| format!("{} isn't an address to listen on", addr))?;
             }
-            conf.leptos_options
+            Ok(conf.leptos_options)
         }
 
-        pub fn get_app_state(pool: SqlitePool, options: LeptosOptions) -> AppState {
+        pub fn get_app_state(pool: SqlitePool, options: LeptosOptions, config: Config) -> AppState {
             let routes = generate_route_list(App);
             AppState {
                 leptos_options: options,
                 pool: pool.clone(),
                 routes: routes.clone(),
+                config,
             }
         }
 
-        pub async fn get_router(app_state: AppState) -> Router {
-            let session_config = SessionConfig::default().with_table_name("axum_sessions");
+        pub async fn get_router(app_state: AppState) -> Result<Router> {
+            let session_config = app_state.config.session.session_config();
             let auth_config = AuthConfig::<i64>::default();
-            let session_store = SessionStore::<SessionSqlitePool>::new(Some(app_state.pool.clone().into()), session_config).await.unwrap();
+            let session_store = SessionStore::<SessionSqlitePool>::new(Some(app_state.pool.clone().into()), session_config)
+                .await
+                .context("creating session store")?;
 
             let app = Router::new()
                 .route("/api/*fn_name", get(server_fn_handler).post(server_fn_handler))
+                .merge(crate::bag::export::routes())
                 .leptos_routes_with_handler(app_state.routes.clone(), get(leptos_routes_handler) )
                 .layer(TraceLayer::new_for_http())
                 .fallback(file_and_error_handler)
@@ -129,9 +130,28 @@ cfg_if! {
                     .with_config(auth_config))
                 .layer(SessionLayer::new(session_store))
                 .with_state(app_state);
-            app
+            Ok(app)
         }
 
-
+        /// Runs the web server until it's stopped. `site_addr` overrides the address from the
+        /// Leptos configuration.
+        pub async fn serve(config: Config, site_addr: Option<String>) -> Result<()> {
+            init_logging(&config.log).await;
+            let db_pool = get_db_pool(&config.database).await?;
+            migrate(&db_pool).await?;
+
+            let options = load_leptos_options(None, site_addr).await?;
+            let addr = options.site_addr;
+            let app_state = get_app_state(db_pool, options, config);
+            let app = get_router(app_state).await?;
+
+            let server = axum::Server::try_bind(&addr)
+                .with_context(|| format!("Couldn't listen on {}", addr))?;
+            tracing::info!("listening on http://{}", &addr);
+            server
+                .serve(app.into_make_service())
+                .await
+                .context("The server stopped")
+        }
     }
 }
@@ -6,6 +6,7 @@ cfg_if! {
         use sqlx::SqlitePool;
         use axum::extract::FromRef;
         use leptos_router::RouteListing;
+        use crate::config::Config;
         /// This takes advantage of Axum's SubStates feature by deriving FromRef. This is the only way to have more than one
         /// item in Axum's State. Leptos requires you to have leptosOptions in your State struct for the leptos route handlers
         #[derive(FromRef, Debug, Clone)]
@@ -13,6 +14,7 @@ cfg_if! {
             pub leptos_options: LeptosOptions,
             pub pool: SqlitePool,
             pub routes: Vec<RouteListing>,
+            pub config: Config,
         }
     }
 }
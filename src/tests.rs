@@ -10,21 +10,259 @@ cfg_if! {
             use sqlx::SqlitePool;
             use axum_test::{TestServer, TestServerConfig};
             use dotenvy;
+            use crate::config::*;
+            use crate::auth::{Role, User};
+            use crate::auth::model::{SQLUser, SQLUserPermission, ADMIN_TOKEN};
+            use crate::bag::export::{DrawRecord, ItemRecord};
+            use crate::bag::model::{BagItem, TakenBagItem, TakenItemFilter};
+            use crate::bag::tests::tests::create_test_bag;
+            use crate::cli::Cli;
+            use crate::errors::RoadieAppError;
+            use clap::Parser;
 
             pub async fn get_test_server(pool: &SqlitePool) -> Result<TestServer> {
                 dotenvy::dotenv().ok();
                 use crate::service::{init_logging, load_leptos_options, get_app_state, get_router};
-                init_logging().await;
-                let options = load_leptos_options(None, None).await;
-                let state = get_app_state(pool.clone(), options);
+                let config = Config::default();
+                init_logging(&config.log).await;
+                let options = load_leptos_options(None, None).await?;
+                let state = get_app_state(pool.clone(), options, config);
                 let config = TestServerConfig::builder()
                     .default_content_type("application/json")
                     .save_cookies()
                     .build();
-                let router = get_router(state).await;
+                let router = get_router(state).await?;
 
                 TestServer::new_with_config(router, config)
             }
+
+            fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+                let vars: Vec<(String, String)> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+                move |name| vars.iter().find(|(k, _)| k == name).map(|(_, v)| v.clone())
+            }
+
+            #[test]
+            fn test_config_layers() -> Result<()> {
+                let file = std::env::temp_dir().join(format!("roadiebag-config-{}.toml", std::process::id()));
+                std::fs::write(&file, r#"
+                    [database]
+                    url = "sqlite:from-file.db"
+                    max_connections = 4
+
+                    [session]
+                    cookie_name = "roadiebag"
+                    cookie_secure = true
+
+                    [log]
+                    format = "json"
+
+                    [draw]
+                    default_rounds = "2d4"
+                "#)?;
+                let args = ConfigArgs { config: Some(file.clone()), ..Default::default() };
+
+                let config = Config::load_with(&args, env(&[]))?;
+                assert_eq!(config.database.url, "sqlite:from-file.db");
+                assert_eq!(config.database.max_connections, 4);
+                assert_eq!(config.session.cookie_name, "roadiebag");
+                assert!(config.session.cookie_secure);
+                // Anything the file leaves out keeps its default
+                assert_eq!(config.session.lifetime_hours, SessionSettings::default().lifetime_hours);
+                assert_eq!(config.draw.bag_form().default_rounds, "2d4");
+                assert_eq!(config.draw.undo_window_secs, DrawDefaults::default().undo_window_secs);
+                assert_eq!(config.log.format, LogFormat::Json);
+                // LEPTOS_ENVIRONMENT is left over from before the file and doesn't pick the format
+                let config = Config::load_with(&args, env(&[("LEPTOS_ENVIRONMENT", "dev")]))?;
+                assert_eq!(config.log.format, LogFormat::Json);
+
+                // Environment variables win over the file, and flags over both
+                let vars = env(&[
+                    ("DATABASE_URL", "sqlite:from-env.db"),
+                    ("ROADIEBAG_DATABASE_MAX_CONNECTIONS", "8"),
+                    ("ROADIEBAG_SESSION_COOKIE_SAME_SITE", "strict"),
+                    ("ROADIEBAG_SESSION_COOKIE_DOMAIN", "example.com"),
+                    ("ROADIEBAG_LOG_FORMAT", "pretty"),
+                    ("ROADIEBAG_DRAW_UNDO_WINDOW_SECS", "120"),
+                ]);
+                let config = Config::load_with(&args, &vars)?;
+                assert_eq!(config.database.url, "sqlite:from-env.db");
+                assert_eq!(config.database.max_connections, 8);
+                assert_eq!(config.session.cookie_same_site, CookieSameSite::Strict);
+                assert_eq!(config.session.cookie_domain, Some("example.com".into()));
+                assert_eq!(config.log.format, LogFormat::Pretty);
+                assert_eq!(config.draw.undo_window_secs, 120);
+                let args = ConfigArgs {
+                    database_url: Some("sqlite:from-flag.db".into()),
+                    log_format: Some(LogFormat::Json),
+                    ..args
+                };
+                let config = Config::load_with(&args, &vars)?;
+                assert_eq!(config.database.url, "sqlite:from-flag.db");
+                assert_eq!(config.database.max_connections, 8);
+                assert_eq!(config.log.format, LogFormat::Json);
+
+                // The file can also be picked through the environment
+                let config = Config::load_with(
+                    &ConfigArgs::default(),
+                    env(&[("ROADIEBAG_CONFIG", file.to_str().unwrap())])
+                )?;
+                assert_eq!(config.database.url, "sqlite:from-file.db");
+                std::fs::remove_file(&file)?;
+                Ok(())
+            }
+
+            #[test]
+            fn test_config_errors() -> Result<()> {
+                let missing = ConfigArgs {
+                    config: Some(std::env::temp_dir().join("roadiebag-no-such-config.toml")),
+                    ..Default::default()
+                };
+                assert!(matches!(Config::load_with(&missing, env(&[])), Err(ConfigError::Read { .. })));
+
+                let file = std::env::temp_dir().join(format!("roadiebag-bad-config-{}.toml", std::process::id()));
+                std::fs::write(&file, "[database]\nmax_connection = 4\n")?;
+                let args = ConfigArgs { config: Some(file.clone()), ..Default::default() };
+                match Config::load_with(&args, env(&[])) {
+                    Err(e @ ConfigError::Parse { .. }) => assert!(e.to_string().contains("max_connection")),
+                    other => panic!("Expected a parse error, got {:?}", other),
+                }
+                std::fs::remove_file(&file)?;
+
+                let error = Config::load_with(&ConfigArgs::default(), env(&[("ROADIEBAG_DATABASE_MAX_CONNECTIONS", "lots")]))
+                    .unwrap_err();
+                assert!(matches!(error, ConfigError::Env { ref var, .. } if var == "ROADIEBAG_DATABASE_MAX_CONNECTIONS"));
+
+                // Every problem is reported at once
+                let vars = env(&[
+                    ("ROADIEBAG_DATABASE_MAX_CONNECTIONS", "0"),
+                    ("ROADIEBAG_SESSION_COOKIE_SAME_SITE", "none"),
+                    ("ROADIEBAG_DRAW_DEFAULT_ROUNDS", "lots"),
+                    ("ROADIEBAG_DRAW_SMALL_WEIGHT", "0"),
+                    ("ROADIEBAG_DRAW_MEDIUM_WEIGHT", "0"),
+                    ("ROADIEBAG_DRAW_LARGE_WEIGHT", "0"),
+                ]);
+                match Config::load_with(&ConfigArgs::default(), vars) {
+                    Err(ConfigError::Invalid(problems)) => assert_eq!(problems.len(), 4),
+                    other => panic!("Expected the configuration to be invalid, got {:?}", other),
+                }
+                Ok(())
+            }
+
+            async fn run_cli(args: &[&str], pool: &SqlitePool) -> Result<()> {
+                let cli = Cli::try_parse_from(std::iter::once("roadiebag").chain(args.iter().copied()))?;
+                cli.command.unwrap().run_with(pool).await
+            }
+
+            #[tracing::instrument(level = "info", skip(pool), fields(error), err)]
+            #[sqlx::test]
+            async fn test_cli(pool: SqlitePool) -> Result<()> {
+                run_cli(&["create-user", "Root", "--password", "hunter2"], &pool).await?;
+                run_cli(&["create-user", "player", "--password", "5678"], &pool).await?;
+                assert!(run_cli(&["create-user", "root", "--password", "again"], &pool).await.is_err());
+                assert!(run_cli(&["create-user", "nobody", "--password", " "], &pool).await.is_err());
+                let root = SQLUser::by_username("root".into(), &pool).await?.unwrap();
+                let player = SQLUser::by_username("player".into(), &pool).await?.unwrap();
+                // The first user runs the instance, like on the sign up page
+                assert!(SQLUserPermission::exists(root.id, ADMIN_TOKEN, &pool).await?);
+                assert!(!SQLUserPermission::exists(player.id, ADMIN_TOKEN, &pool).await?);
+                assert!(bcrypt::verify("hunter2", &root.password)?);
+                // Running out of admins doesn't hand it to whoever comes next
+                SQLUserPermission::revoke(root.id, ADMIN_TOKEN.into(), &pool).await?;
+                run_cli(&["create-user", "latecomer", "--password", "0000"], &pool).await?;
+                let latecomer = SQLUser::by_username("latecomer".into(), &pool).await?.unwrap();
+                assert!(!SQLUserPermission::exists(latecomer.id, ADMIN_TOKEN, &pool).await?);
+                assert!(!SQLUserPermission::grant_to_first_user(root.id, ADMIN_TOKEN.into(), &pool).await?);
+                SQLUserPermission::grant(root.id, ADMIN_TOKEN.into(), &pool).await?;
+
+                run_cli(&["reset-password", "ROOT", "--password", "swordfish"], &pool).await?;
+                let root = SQLUser::by_username("root".into(), &pool).await?.unwrap();
+                assert!(bcrypt::verify("swordfish", &root.password)?);
+                assert!(run_cli(&["reset-password", "ghost", "--password", "boo"], &pool).await.is_err());
+
+                let user = User { id: root.id, username: root.username.clone(), anonymous: false };
+                let bag = create_test_bag(&user, &pool).await?;
+                let bag_id = bag.id.to_string();
+                run_cli(&["grant", "player", &bag_id, "player"], &pool).await?;
+                assert!(SQLUserPermission::exists(player.id, &Role::Player.token(bag.id), &pool).await?);
+                assert!(run_cli(&["grant", "player", &bag_id, "emperor"], &pool).await.is_err());
+                assert!(run_cli(&["grant", "player", "9999", "viewer"], &pool).await.is_err());
+
+                let dir = std::env::temp_dir();
+                let items_file = dir.join(format!("roadiebag-cli-{}-items.json", std::process::id()));
+                std::fs::write(&items_file, r#"[
+                    {"name": "Sword", "quantity": 2, "size": "medium", "tags": "weapon"},
+                    {"name": "Rope", "quantity": 1, "size": "small"}
+                ]"#)?;
+                let items_path = items_file.to_str().unwrap();
+                run_cli(&["import", &bag_id, items_path, "--as", "root", "--dry-run"], &pool).await?;
+                assert!(BagItem::names(bag.id, &pool).await?.is_empty());
+                run_cli(&["import", &bag_id, items_path, "--as", "root"], &pool).await?;
+                run_cli(&["import", &bag_id, items_path, "--as", "root", "--skip-duplicates"], &pool).await?;
+                let mut names = BagItem::names(bag.id, &pool).await?;
+                names.sort();
+                assert_eq!(names, vec!["Rope", "Sword"]);
+                std::fs::write(&items_file, r#"[{"name": "", "quantity": 1, "size": "huge"}]"#)?;
+                assert!(run_cli(&["import", &bag_id, items_path, "--as", "root"], &pool).await.is_err());
+                std::fs::remove_file(&items_file)?;
+
+                run_cli(&["draw", &bag_id, "--as", "player", "--with-tag", "weapon"], &pool).await?;
+                let history = TakenBagItem::filter(TakenItemFilter { bag_id: Some(bag.id), ..Default::default() }, &pool).await?;
+                assert_eq!(history.items.len(), 1);
+                assert_eq!(history.items[0].item.name, "Sword");
+                assert_eq!(history.items[0].drawn_by.as_ref().map(|u| u.id), Some(player.id));
+                // Like the API, a player can't draw again until their current draw is over
+                let err = run_cli(&["draw", &bag_id, "--as", "player"], &pool).await.unwrap_err();
+                assert_eq!(err.downcast_ref::<RoadieAppError>(), Some(&RoadieAppError::DrawInProgress));
+                history.items[0].complete(player.id, &pool).await?;
+                assert!(run_cli(&["draw", &bag_id, "--as", "player", "--with-tag", "armour"], &pool).await.is_err());
+
+                let export_file = dir.join(format!("roadiebag-cli-{}-export.csv", std::process::id()));
+                let export_path = export_file.to_str().unwrap();
+                run_cli(&["export", &bag_id, "-o", export_path], &pool).await?;
+                let records = csv::Reader::from_path(&export_file)?
+                    .deserialize::<ItemRecord>()
+                    .collect::<Result<Vec<_>, _>>()?;
+                assert_eq!(records.len(), 2);
+                run_cli(&["export", &bag_id, "--history", "--format", "ndjson", "-o", export_path], &pool).await?;
+                let draws = std::fs::read_to_string(&export_file)?
+                    .lines()
+                    .map(serde_json::from_str::<DrawRecord>)
+                    .collect::<Result<Vec<_>, _>>()?;
+                assert_eq!(draws.len(), 1);
+                assert!(run_cli(&["export", &bag_id, "--format", "xml"], &pool).await.is_err());
+                std::fs::remove_file(&export_file)?;
+
+                let backup_file = dir.join(format!("roadiebag-cli-{}-backup.json", std::process::id()));
+                let backup_path = backup_file.to_str().unwrap();
+                run_cli(&["backup", "--bag", &bag_id, "--include-passwords", "-o", backup_path], &pool).await?;
+                assert!(run_cli(&["restore", backup_path], &pool).await.is_err());
+                run_cli(&["restore", backup_path, "--on-conflict", "replace"], &pool).await?;
+                let mut names = BagItem::names(bag.id, &pool).await?;
+                names.sort();
+                assert_eq!(names, vec!["Rope", "Sword"]);
+                std::fs::remove_file(&backup_file)?;
+
+                Ok(())
+            }
+
+            #[tracing::instrument(level = "info", fields(error), err)]
+            #[tokio::test]
+            async fn test_cli_migrate() -> Result<()> {
+                // Not a #[sqlx::test], which would run the migrations first. One connection keeps
+                // the in-memory database alive between commands.
+                let pool = sqlx::sqlite::SqlitePoolOptions::new()
+                    .max_connections(1)
+                    .connect("sqlite::memory:")
+                    .await?;
+                // Nothing runs on a database that's behind until it's migrated
+                let err = run_cli(&["create-user", "root", "--password", "hunter2"], &pool).await.unwrap_err();
+                assert!(err.to_string().contains("roadiebag migrate"));
+                run_cli(&["migrate"], &pool).await?;
+                run_cli(&["migrate"], &pool).await?;
+                run_cli(&["create-user", "root", "--password", "hunter2"], &pool).await?;
+                assert!(SQLUser::by_username("root".into(), &pool).await?.is_some());
+                Ok(())
+            }
         }
     }
 }